@@ -8,12 +8,45 @@
 //! `TlfuCore` is not thread-safe. Users must wrap it in a `Mutex` or `RwLock`
 //! when sharing across threads.
 
-use std::collections::{HashMap, HashSet};
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
 
 use pyo3::prelude::*;
 
-use crate::errors::catch_panic;
-use crate::{metadata::Entry, timerwheel::TimerWheel, tlfu::DebugInfo, tlfu::TinyLfu};
+use crate::errors::{CacheError, catch_panic};
+use crate::lru::WindowPolicy;
+use crate::sketch::CounterWidth;
+use crate::tlfu::VictimOrder;
+use crate::{
+    metadata::Entry, timerwheel::TimerWheel, tlfu::DebugInfo, tlfu::EntryInfo, tlfu::TinyLfu,
+};
+
+/// Default number of per-batch eviction counts kept for `recent_eviction_rate`
+const DEFAULT_EVICTION_SAMPLES: usize = 64;
+
+/// Fraction of saturated sketch counters above which `health_details` flags the sketch
+const HEALTH_SKETCH_SATURATION: f64 = 0.1;
+
+/// Evictions per batch, as a fraction of capacity, above which `health_details`
+/// flags eviction pressure
+const HEALTH_EVICTION_PRESSURE: f64 = 0.1;
+
+/// Largest size for which `new_small` builds a plain LRU without a sketch
+const SMALL_CACHE_THRESHOLD: usize = 64;
+
+/// Outcome of creating or updating a single entry.
+enum Insert {
+    Updated,
+    Inserted,
+    Rejected,
+}
+
+/// Refresh-ahead trigger set by `TlfuCore::set_refresh_ahead`.
+struct RefreshAhead {
+    callback: Py<PyAny>,
+    freq_threshold: usize,
+    ttl_fraction: f64,
+}
 
 /// TinyLFU cache with TTL support
 ///
@@ -24,6 +57,21 @@ pub struct TlfuCore {
     policy: TinyLfu,
     pub(crate) wheel: TimerWheel,
     pub(crate) entries: HashMap<u64, Entry>,
+    groups: HashMap<u32, HashSet<u64>>,
+    pending_evicted: Vec<u64>,
+    promote_long_ttl_threshold: u64,
+    expiry_interceptor: Option<Py<PyAny>>,
+    flush_on_evict: Option<Py<PyAny>>,
+    pending_flush: Vec<u64>,
+    refresh_ahead: Option<RefreshAhead>,
+    eviction_samples: VecDeque<usize>,
+    eviction_samples_cap: usize,
+    strict: bool,
+    insertion_grace: u64,
+    expiry_penalty: usize,
+    expired_unread: HashMap<u64, usize>,
+    max_ttl: u64,
+    debug_invariants: bool,
 }
 
 #[pymethods]
@@ -45,7 +93,385 @@ impl TlfuCore {
             policy: TinyLfu::new(size),
             wheel: TimerWheel::new(),
             entries: HashMap::with_capacity(size),
+            groups: HashMap::new(),
+            pending_evicted: Vec::new(),
+            promote_long_ttl_threshold: 0,
+            expiry_interceptor: None,
+            flush_on_evict: None,
+            pending_flush: Vec::new(),
+            refresh_ahead: None,
+            eviction_samples: VecDeque::with_capacity(DEFAULT_EVICTION_SAMPLES),
+            eviction_samples_cap: DEFAULT_EVICTION_SAMPLES,
+            strict: false,
+            insertion_grace: 0,
+            expiry_penalty: 0,
+            expired_unread: HashMap::new(),
+            max_ttl: 0,
+            debug_invariants: false,
+        }
+    }
+
+    /// Creates a cache that is a plain LRU when `size` is tiny.
+    ///
+    /// Up to `SMALL_CACHE_THRESHOLD` entries the window/main split
+    /// degenerates and the frequency sketch is pure overhead, so such a cache
+    /// skips the sketch entirely: no table is allocated, there's no admission
+    /// contest or hill climbing, and eviction takes the least recently used
+    /// key. Larger sizes get a regular cache, as with `new`. The API behaves
+    /// the same either way; frequency-based settings have no effect on a
+    /// plain LRU.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    #[staticmethod]
+    pub fn new_small(size: usize) -> Self {
+        if size > SMALL_CACHE_THRESHOLD {
+            return Self::new(size);
+        }
+        Self {
+            policy: TinyLfu::new_lru(size),
+            ..Self::new(size)
+        }
+    }
+
+    /// Creates a new cache using the given recency policy for the admission window.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `policy` - `WindowPolicy::Lru` (default) or `WindowPolicy::Fifo` (second chance)
+    #[staticmethod]
+    pub fn with_window_policy(size: usize, policy: WindowPolicy) -> Self {
+        let mut core = Self::new(size);
+        core.policy.set_window_policy(policy);
+        core
+    }
+
+    /// Creates a new cache with a custom timer wheel level layout.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `bucket_spec` - Bucket count per wheel level, see `TimerWheel::with_levels`
+    ///
+    /// # Examples
+    ///
+    /// ```ignore
+    /// // extra level of ~6.5 day buckets for week-plus TTLs
+    /// let cache = TlfuCore::with_wheel_levels(1000, vec![64, 64, 32, 4, 4, 1])?;
+    /// ```
+    #[staticmethod]
+    pub fn with_wheel_levels(size: usize, bucket_spec: Vec<usize>) -> PyResult<Self> {
+        let wheel = TimerWheel::with_levels(bucket_spec)
+            .map_err(|e| CacheError::validation(e.to_string()))?;
+        Ok(Self {
+            wheel,
+            ..Self::new(size)
+        })
+    }
+
+    /// Creates a new cache with the given frequency sketch counter width.
+    ///
+    /// `CounterWidth::Eight` keeps telling hot keys apart past 15 accesses
+    /// per sample, which improves admission among popular keys, at twice the
+    /// sketch memory.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `width` - `CounterWidth::Four` (default) or `CounterWidth::Eight`
+    #[staticmethod]
+    pub fn with_counter_width(size: usize, width: CounterWidth) -> Self {
+        Self {
+            policy: TinyLfu::with_counter_width(size, width),
+            ..Self::new(size)
+        }
+    }
+
+    /// Creates a new cache whose frequency sketch hashes keys with a seed.
+    ///
+    /// Caches with different seeds distribute keys independently, so keys
+    /// crafted to collide in the sketch of one instance don't degrade
+    /// admission in another. A seed of 0 behaves exactly like `new`.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `seed` - Seed mixed into every key's sketch hash
+    #[staticmethod]
+    pub fn new_seeded(size: usize, seed: u64) -> Self {
+        Self {
+            policy: TinyLfu::with_sketch_seed(size, seed),
+            ..Self::new(size)
+        }
+    }
+
+    /// Creates a new cache with a custom frequency sketch block size.
+    ///
+    /// All counters of a key live in one block of the sketch table. The
+    /// default of 8 words matches a 64-byte cache line; other sizes may suit
+    /// platforms with different cache lines. This is a performance knob and
+    /// doesn't change what the sketch estimates.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `block_words` - 64-bit words per block, a power of two from 4 to 64
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` if `block_words` is out of range or not a power of two
+    #[staticmethod]
+    pub fn with_sketch_block_size(size: usize, block_words: usize) -> PyResult<Self> {
+        let policy = TinyLfu::with_sketch_block_words(size, block_words)
+            .map_err(|e| CacheError::validation(e.to_string()))?;
+        Ok(Self {
+            policy,
+            ..Self::new(size)
+        })
+    }
+
+    /// Creates a new cache whose frequency sketch is capped independently of capacity.
+    ///
+    /// By default the sketch is sized for the full capacity, which for huge
+    /// caches costs 8 bytes per entry. A capped sketch stays correctly
+    /// addressed but estimates less precisely and ages more often.
+    ///
+    /// # Arguments
+    ///
+    /// * `size` - Maximum number of entries to cache
+    /// * `max_sketch_size` - Upper bound on the number of keys the sketch is sized for
+    #[staticmethod]
+    pub fn with_max_sketch_size(size: usize, max_sketch_size: usize) -> Self {
+        Self {
+            policy: TinyLfu::with_max_sketch_size(size, max_sketch_size),
+            ..Self::new(size)
+        }
+    }
+
+    /// Sets the frequency above which a losing candidate may still be admitted.
+    ///
+    /// Above the threshold a pseudo-random tiebreak occasionally admits a
+    /// candidate whose frequency doesn't beat the victim's. Lower values make
+    /// the cache more resistant to a crafted key stream designed to evict a
+    /// victim deterministically, higher values favour the frequency estimate.
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - Minimum candidate frequency for the tiebreak, at least 1
+    pub fn set_hashdos_threshold(&mut self, threshold: usize) -> PyResult<()> {
+        if threshold < 1 {
+            return Err(CacheError::validation("hashdos threshold must be at least 1").into());
         }
+        self.policy.set_hashdos_threshold(threshold);
+        Ok(())
+    }
+
+    /// Sends new entries with a long TTL straight to the main space.
+    ///
+    /// A new entry whose TTL exceeds `threshold` nanoseconds (or that has no
+    /// TTL at all) is inserted into probation instead of the admission window.
+    /// This biases the policy in favour of long-lived keys, so only use it
+    /// when a long TTL reliably means the key is important, e.g. config or
+    /// reference data. A threshold of 0 disables it (the default).
+    ///
+    /// # Arguments
+    ///
+    /// * `threshold` - TTL in nanoseconds above which the window is skipped
+    pub fn set_promote_long_ttl_threshold(&mut self, threshold: u64) {
+        self.promote_long_ttl_threshold = threshold;
+    }
+
+    /// Requires new keys to have been seen at least `k` times before they're
+    /// admitted.
+    ///
+    /// Every `set` of an absent key counts towards its sketch frequency. If
+    /// the estimate, including that set, is still below `k` the key isn't
+    /// inserted and is reported back as evicted. This filters one-hit
+    /// wonders more strongly than the window contest. A `k` of 0 or 1
+    /// disables the gate (the default).
+    ///
+    /// # Arguments
+    ///
+    /// * `k` - Minimum estimated frequency for a new key
+    pub fn set_min_admission_frequency(&mut self, k: usize) {
+        self.policy.set_min_admission_frequency(k);
+    }
+
+    /// Sets whether inserting a new key counts towards its frequency.
+    ///
+    /// By default both inserts and accesses feed the frequency sketch. With
+    /// `false` only accesses do, so keys that are written often but rarely
+    /// read don't win admission contests on write volume alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `weight_writes` - Whether `set` of a new key bumps its frequency
+    pub fn set_weight_writes(&mut self, weight_writes: bool) {
+        self.policy.set_weight_writes(weight_writes);
+    }
+
+    /// Returns the current capacity of the SLRU protected segment.
+    #[must_use]
+    pub fn protected_capacity(&self) -> usize {
+        self.policy.protected_capacity()
+    }
+
+    /// Sets the capacity of the SLRU protected segment.
+    ///
+    /// Shrinking demotes the least recently used protected keys to
+    /// probation. The hill climber treats the new split as its baseline and
+    /// keeps adapting from there, moving capacity between window and
+    /// protected as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `cap` - Protected capacity, at most the main (non-window) capacity
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` if `cap` exceeds the main capacity
+    pub fn set_protected_capacity(&mut self, cap: usize) -> PyResult<()> {
+        self.policy
+            .set_protected_capacity(cap, &mut self.entries)
+            .map_err(|e| CacheError::validation(e.to_string()).into())
+    }
+
+    /// Sets the capacity of the admission window to an absolute size.
+    ///
+    /// The difference is taken from or given back to the protected segment,
+    /// so the total capacity is unchanged. The hill climber keeps adapting
+    /// from the new size unless the window is frozen with `freeze_window`,
+    /// which pins it for setups that need a fixed recency buffer.
+    ///
+    /// # Arguments
+    ///
+    /// * `cap` - Window capacity, at least 1 and below the cache capacity
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` if `cap` is out of range
+    pub fn set_window_capacity(&mut self, cap: usize) -> PyResult<()> {
+        self.policy
+            .set_window_capacity(cap, &mut self.entries)
+            .map_err(|e| CacheError::validation(e.to_string()).into())
+    }
+
+    /// Stops or resumes the hill climber's window adaptation.
+    ///
+    /// A frozen window keeps its current capacity, and protected keeps its
+    /// own, until unfrozen or changed with `set_window_capacity`.
+    ///
+    /// # Arguments
+    ///
+    /// * `frozen` - Whether to pin the window size
+    pub fn freeze_window(&mut self, frozen: bool) {
+        self.policy.set_window_frozen(frozen);
+    }
+
+    /// Records the most recent admission contests for `admission_trace`.
+    ///
+    /// Shows the frequency gap at the eviction boundary of a real workload,
+    /// to judge whether the hashdos threshold or the tie breaker need
+    /// adjusting. Disabled by default, when nothing is recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Number of contests kept, 0 disables and drops the trace
+    pub fn enable_admission_trace(&mut self, capacity: usize) {
+        self.policy.set_admission_trace(capacity);
+    }
+
+    /// Returns the recorded admission contests, oldest first.
+    ///
+    /// # Returns
+    ///
+    /// `(candidate_freq, victim_freq, admitted)` tuples, empty when disabled
+    #[must_use]
+    pub fn admission_trace(&self) -> Vec<(usize, usize, bool)> {
+        self.policy.admission_trace()
+    }
+
+    /// Records the most recent hill climber steps for `climber_trace`.
+    ///
+    /// Replaying a workload with the trace on shows how the window size
+    /// evolved, to diagnose oscillation or slow convergence. Disabled by
+    /// default, when nothing is recorded.
+    ///
+    /// # Arguments
+    ///
+    /// * `capacity` - Number of steps kept, 0 disables and drops the trace
+    pub fn enable_climber_trace(&mut self, capacity: usize) {
+        self.policy.set_climber_trace(capacity);
+    }
+
+    /// Returns the recorded hill climber steps, oldest first.
+    ///
+    /// # Returns
+    ///
+    /// `(sample_hr, delta, next_step, amount)` tuples, where `amount` is the
+    /// window adjustment applied, empty when disabled
+    #[must_use]
+    pub fn climber_trace(&self) -> Vec<(f32, f32, f32, isize)> {
+        self.policy.climber_trace()
+    }
+
+    /// Sets which list eviction takes its first victim from.
+    ///
+    /// `VictimOrder::ProbationFirst` (the default) pits window candidates
+    /// against the least recently used probation key, then protected, then
+    /// window keys. `VictimOrder::WindowFirst` starts at the window tail,
+    /// evicting the coldest recent entry first. The admission contest itself
+    /// is unchanged, and `coldest_key` follows the same order.
+    ///
+    /// # Arguments
+    ///
+    /// * `order` - Where victim selection starts
+    pub fn set_victim_priority(&mut self, order: VictimOrder) {
+        self.policy.set_victim_order(order);
+    }
+
+    /// Lets hot window keys skip probation when they leave the window.
+    ///
+    /// A key leaving the window with an estimated frequency above the
+    /// threshold is placed straight into protected, as long as protected has
+    /// room, instead of competing from probation. Others go to probation as
+    /// usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq_threshold` - Frequency a key must exceed, 0 (the default) disables it
+    pub fn set_hot_window_promotion(&mut self, freq_threshold: usize) {
+        self.policy.set_hot_window_promotion(freq_threshold);
+    }
+
+    /// Keeps hot keys in protected when the hill climber shrinks it.
+    ///
+    /// Shrinking protected normally demotes its least recently used keys to
+    /// probation, where a still-hot key can be evicted before it's read
+    /// again. With a threshold, keys estimated above it are passed over and
+    /// the next colder key is demoted instead. Once only hot keys are left,
+    /// they're demoted from the tail as usual.
+    ///
+    /// # Arguments
+    ///
+    /// * `freq_threshold` - Frequency a key must exceed to be kept, 0 (the default) disables it
+    pub fn set_demotion_repair(&mut self, freq_threshold: usize) {
+        self.policy.set_demotion_repair(freq_threshold);
+    }
+
+    /// Sets which side wins an admission contest between equal frequencies.
+    ///
+    /// By default the incumbent victim wins ties. Recency-favoring workloads
+    /// may prefer the candidate, which is the more recently inserted key.
+    /// Above the hashdos threshold the pseudo-random tiebreak still applies.
+    ///
+    /// # Arguments
+    ///
+    /// * `prefer_candidate` - Whether the candidate wins ties, false by default
+    pub fn set_tie_breaker(&mut self, prefer_candidate: bool) {
+        self.policy.set_prefer_candidate_on_tie(prefer_candidate);
     }
 
     /// Sets or updates a cache entry, handling eviction if necessary.
@@ -57,70 +483,144 @@ impl TlfuCore {
     ///
     /// # Returns
     ///
-    /// `Some(evicted_key)` if an entry was evicted to make room, `None` otherwise
-    fn set_entry(&mut self, key: u64, ttl: u64) -> Option<u64> {
-        // Update existing entry
-        if let Some(entry) = self.entries.get_mut(&key) {
-            entry.expire = self.wheel.clock.expire_ns(ttl);
-            self.wheel.schedule(key, entry);
-            return None;
+    /// Keys evicted to make room, or the key itself if it was rejected. More
+    /// than one key is evicted when the overflow allowance was lowered.
+    fn set_entry(&mut self, key: u64, ttl: u64) -> Vec<u64> {
+        match self.insert_entry(key, ttl) {
+            Insert::Updated => Vec::new(),
+            Insert::Rejected => vec![key],
+            Insert::Inserted => self.evict_to_capacity(),
         }
+    }
 
-        // Create new entry
-        let mut entry = Entry::new();
-        entry.expire = self.wheel.clock.expire_ns(ttl);
-        self.wheel.schedule(key, &mut entry);
-        self.entries.insert(key, entry);
+    /// Sets multiple cache entries in a batch operation.
+    #[pyo3(name = "set")]
+    pub fn py_set(slf: &Bound<'_, Self>, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set(entries))
+    }
 
-        self.policy
-            .set(key, &mut self.entries)
-            .ok()
-            .flatten()
-            .inspect(|&evicted_key| {
-                if let Some(evicted) = self.entries.get_mut(&evicted_key) {
-                    self.wheel.deschedule(evicted);
-                }
-                self.entries.remove(&evicted_key);
-                log::debug!("Evicted key {} for key {}", evicted_key, key);
-            })
+    /// Sets multiple cache entries, making the window recency follow the batch.
+    #[pyo3(name = "set_ordered")]
+    pub fn py_set_ordered(slf: &Bound<'_, Self>, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_ordered(entries))
     }
 
-    /// Sets multiple cache entries in a batch operation.
-    ///
-    /// Entries with TTL of -1 are removed instead of added.
+    /// Sets multiple cache entries, returning the inserted keys that didn't stick.
+    #[pyo3(name = "set_reporting_admission")]
+    pub fn py_set_reporting_admission(slf: &Bound<'_, Self>, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_reporting_admission(entries))
+    }
+
+    /// Sets multiple cache entries, reporting evictions and removals separately.
+    #[pyo3(name = "set_full_report")]
+    pub fn py_set_full_report(
+        slf: &Bound<'_, Self>,
+        entries: Vec<(u64, i64)>,
+    ) -> (Vec<u64>, Vec<u64>) {
+        Self::flushing(slf, |cache| cache.set_full_report(entries))
+    }
+
+    /// Sets multiple cache entries, seeding the frequency of new keys.
+    #[pyo3(name = "set_with_frequency")]
+    pub fn py_set_with_frequency(slf: &Bound<'_, Self>, entries: Vec<(u64, i64, u8)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_with_frequency(entries))
+    }
+
+    /// Sets multiple cache entries with both a TTL and an idle timeout (TTI).
+    #[pyo3(name = "set_with_tti")]
+    pub fn py_set_with_tti(slf: &Bound<'_, Self>, entries: Vec<(u64, i64, i64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_with_tti(entries))
+    }
+
+    /// Sets multiple cache entries tagged with a group, in a batch operation.
+    #[pyo3(name = "set_grouped")]
+    pub fn py_set_grouped(slf: &Bound<'_, Self>, entries: Vec<(u64, i64, u32)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_grouped(entries))
+    }
+
+    /// Removes every key tagged with the given group.
     ///
     /// # Arguments
     ///
-    /// * `entries` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    /// * `group` - The group to invalidate
     ///
     /// # Returns
     ///
-    /// Vector of keys that were evicted to make room for new entries
-    pub fn set(&mut self, entries: Vec<(u64, i64)>) -> Vec<u64> {
-        let mut evicted = HashSet::new();
+    /// Vector of keys that were removed
+    pub fn invalidate_group(&mut self, group: u32) -> Vec<u64> {
+        let keys: Vec<u64> = self
+            .groups
+            .remove(&group)
+            .map(|keys| keys.into_iter().collect())
+            .unwrap_or_default();
 
-        for (key, ttl) in entries {
-            match ttl {
-                -1 => self.remove_internal(key),
-                _ if !evicted.contains(&key) => {
-                    if let Some(evicted_key) = self.set_entry(key, ttl.unsigned_abs()) {
-                        evicted.insert(evicted_key);
-                    }
-                }
-                _ => {}
-            }
+        for &key in &keys {
+            self.remove_internal(key);
         }
+        log::debug!("Invalidated group {}: {} keys", group, keys.len());
 
-        // Clean up evicted entries
-        evicted.retain(|key| self.entries.remove(key).is_some());
+        keys
+    }
 
-        log::debug!(
-            "Set: {} entries evicted, size={}",
-            evicted.len(),
-            self.entries.len()
-        );
+    /// Returns the keys that would be evicted if `new_keys` were inserted.
+    ///
+    /// The insertion and eviction contest run against a clone of the policy
+    /// state and entries, so the cache itself is left untouched. Cloning is
+    /// O(n) in the cache size, so this is intended for small batches and
+    /// diagnostics rather than the hot path.
+    ///
+    /// # Arguments
+    ///
+    /// * `new_keys` - Keys to simulate inserting, in order
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that would be evicted, in eviction order
+    #[must_use]
+    pub fn preview_evictions(&self, new_keys: Vec<u64>) -> Vec<u64> {
+        let mut policy = self.policy.clone();
+        let mut entries = self.entries.clone();
+        let mut evicted = Vec::new();
+
+        // the same insert-then-evict pass as `set`
+        let now = self.wheel.clock.now_ns();
+        for key in new_keys {
+            if entries.contains_key(&key) {
+                continue;
+            }
+            let mut entry = Entry::new();
+            entry.inserted_at = now;
+            entries.insert(key, entry);
+            let admitted = policy
+                .insert_new(
+                    key,
+                    &mut entries,
+                    self.skips_window(0),
+                    self.counts_write(key),
+                )
+                .unwrap_or_else(|e| {
+                    log::error!("preview(key={}): {}", key, e);
+                    true
+                });
+            if !admitted {
+                entries.remove(&key);
+                evicted.push(key);
+            }
+        }
 
-        evicted.into_iter().collect()
+        let victims = policy
+            .evict_graced(&mut entries, self.graced_from())
+            .unwrap_or_else(|e| {
+                log::error!("preview evict: {}", e);
+                Vec::new()
+            });
+        for key in &victims {
+            entries.remove(key);
+        }
+        evicted.extend(victims);
+        let mut seen = HashSet::new();
+        evicted.retain(|key| !entries.contains_key(key) && seen.insert(*key));
+        evicted
     }
 
     /// Removes an entry from all internal structures.
@@ -131,6 +631,7 @@ impl TlfuCore {
                 log::warn!("Failed to remove key {} from policy: {}", key, e);
             });
             self.wheel.deschedule(&mut entry);
+            self.untrack_group(key, entry.group);
             log::debug!("Removed key {}", key);
         }
     }
@@ -144,15 +645,22 @@ impl TlfuCore {
     /// # Returns
     ///
     /// `Some(key)` if the key was found and removed, `None` if not present
-    pub fn remove(&mut self, key: u64) -> Option<u64> {
-        self.entries.remove(&key).map(|mut entry| {
-            let _ = self.policy.remove(&mut entry).map_err(|e| {
-                log::error!("remove(key={}): {}", key, e);
-            });
-            self.wheel.deschedule(&mut entry);
-            log::debug!("Removed key {}", key);
-            key
-        })
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, a policy inconsistency raises `RuntimeError`; the key
+    /// is removed either way.
+    pub fn remove(&mut self, key: u64) -> PyResult<Option<u64>> {
+        let Some(mut entry) = self.entries.remove(&key) else {
+            return Ok(None);
+        };
+        let result = self.policy.remove(&mut entry);
+        self.wheel.deschedule(&mut entry);
+        self.untrack_group(key, entry.group);
+        log::debug!("Removed key {}", key);
+        self.check_policy("remove", key, result)?;
+        self.check_size("remove")?;
+        Ok(Some(key))
     }
 
     /// Marks entries as accessed to update their position in the policy.
@@ -160,213 +668,4830 @@ impl TlfuCore {
     /// # Arguments
     ///
     /// * `keys` - Vector of keys to mark as accessed
-    pub fn access(&mut self, keys: Vec<u64>) {
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, a policy inconsistency raises `RuntimeError` and the
+    /// remaining keys are not accessed.
+    pub fn access(&mut self, keys: Vec<u64>) -> PyResult<()> {
         log::trace!("Accessing {} keys", keys.len());
+        let now = self.wheel.clock.now_ns();
+        let mut refresh = Vec::new();
+        let mut signaled = HashSet::new();
         for key in keys {
-            self.access_entry(key);
+            let result = self
+                .policy
+                .access(key, &self.wheel.clock, &mut self.entries);
+            self.check_policy("access", key, result)?;
+            if self.needs_refresh(key, now) && signaled.insert(key) {
+                refresh.push(key);
+            }
         }
+        self.signal_refresh(&refresh);
+        self.check_size("access")?;
+        Ok(())
     }
 
-    /// Updates policy state for a single accessed entry.
-    #[inline]
-    fn access_entry(&mut self, key: u64) {
-        let _ = self
-            .policy
-            .access(key, &self.wheel.clock, &mut self.entries)
-            .map_err(|e| {
-                log::error!("access(key={}): {}", key, e);
-            });
+    /// Like `set`, but takes raw Python hashes and applies `spread` internally.
+    #[pyo3(name = "set_hashed")]
+    pub fn py_set_hashed(slf: &Bound<'_, Self>, entries: Vec<(i64, i64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.set_hashed(entries))
     }
 
-    /// Processes TTL expirations and removes expired entries from the cache.
+    /// Like `access`, but takes raw Python hashes and applies `spread` internally.
     ///
-    /// This advances the internal timer wheel and returns all keys that expired
-    /// during the advancement.
+    /// # Arguments
     ///
-    /// # Returns
+    /// * `keys` - Vector of Python hashes to mark as accessed
+    pub fn access_hashed(&mut self, keys: Vec<i64>) -> PyResult<()> {
+        self.access(keys.into_iter().map(spread).collect())
+    }
+
+    /// Marks entries as accessed and renews the TTL of those still live.
+    ///
+    /// Each key records a normal access. Keys that are resident and not
+    /// expired additionally get their TTL reset to the given value; missing
+    /// or expired keys are left alone.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl) pairs, ttl in nanoseconds with 0 meaning no expiration
+    pub fn access_extend(&mut self, entries: Vec<(u64, i64)>) {
+        log::trace!("Accessing and extending {} keys", entries.len());
+        for (key, ttl) in entries {
+            self.access_entry(key);
+            let now = self.wheel.clock.now_ns();
+            if let Some(entry) = self.entries.get_mut(&key)
+                && !entry.is_expired(now)
+            {
+                entry.expire = self.wheel.clock.expire_ns(ttl.unsigned_abs());
+                self.wheel.schedule(key, entry);
+            }
+        }
+    }
+
+    /// Resets a live entry's expiration to the TTL it was last set with.
+    ///
+    /// A keep-alive for heartbeat-style refreshes: unlike `access_extend`
+    /// this records no access, so neither the policy lists nor the sketch
+    /// change, and the TTL doesn't have to be passed again.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    ///
+    /// # Returns
+    ///
+    /// false if the key is missing, already expired or was set without a TTL
+    pub fn renew(&mut self, key: u64) -> bool {
+        let now = self.wheel.clock.now_ns();
+        let Some(entry) = self.entries.get_mut(&key) else {
+            return false;
+        };
+        if entry.original_ttl == 0 || entry.is_expired(now) {
+            return false;
+        }
+        entry.expire = now.saturating_add(entry.original_ttl);
+        self.wheel.schedule(key, entry);
+        true
+    }
+
+    /// Moves a live entry into another cache, keeping its metadata.
+    #[pyo3(name = "transfer_to")]
+    pub fn py_transfer_to(&mut self, key: u64, dest: &Bound<'_, Self>) -> PyResult<bool> {
+        // fails for the cache itself, as taking `dest` by reference did
+        drop(dest.try_borrow_mut()?);
+        Ok(Self::flushing(dest, |dest| self.transfer_to(key, dest)))
+    }
+
+    /// Records an access on hit, or inserts the key on miss.
+    #[pyo3(name = "access_or_insert")]
+    pub fn py_access_or_insert(slf: &Bound<'_, Self>, key: u64, ttl: i64) -> bool {
+        Self::flushing(slf, |cache| cache.access_or_insert(key, ttl))
+    }
+
+    /// Records an access and reports how close the entry is to expiring.
+    ///
+    /// Drives serve-stale-while-revalidate: a stale result means the value is
+    /// still usable but a background refresh should be triggered.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    /// * `stale_window_ns` - How long before expiry an entry counts as stale
+    ///
+    /// # Returns
+    ///
+    /// `1` if fresh, `0` if live but expiring within the stale window, `-1`
+    /// if missing or expired. Live entries record a normal access.
+    pub fn access_with_freshness(&mut self, key: u64, stale_window_ns: u64) -> i8 {
+        let now = self.wheel.clock.now_ns();
+        let freshness = match self.entries.get(&key).map(Entry::deadline) {
+            None => return -1,
+            Some(0) => 1,
+            Some(deadline) if deadline <= now => return -1,
+            Some(deadline) if deadline - now <= stale_window_ns => 0,
+            Some(_) => 1,
+        };
+        self.access_entry(key);
+        freshness
+    }
+
+    /// Records an access and reports which segment served the hit.
+    ///
+    /// The segment is the one the key was in before the access, so a
+    /// probation hit reports `2` even though the access promotes it. Lets
+    /// read-time tiering decide on replication without a separate lookup.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    ///
+    /// # Returns
+    ///
+    /// `1` for window, `2` for probation, `3` for protected, `-1` if missing
+    /// or expired. Live entries record a normal access.
+    pub fn access_with_segment(&mut self, key: u64) -> i8 {
+        let now = self.wheel.clock.now_ns();
+        let segment = match self.entries.get(&key) {
+            Some(entry) if !entry.is_expired(now) => entry.policy_list_id as i8,
+            _ => return -1,
+        };
+        self.access_entry(key);
+        segment
+    }
+
+    /// Replays a key stream through the policy and returns the hit rate.
+    #[pyo3(name = "simulate")]
+    pub fn py_simulate(slf: &Bound<'_, Self>, keys: Vec<u64>) -> f64 {
+        Self::flushing(slf, |cache| cache.simulate(keys))
+    }
+
+    /// Returns and clears the keys evicted by `access_or_insert` since the last call.
+    pub fn take_evicted(&mut self) -> Vec<u64> {
+        std::mem::take(&mut self.pending_evicted)
+    }
+
+    /// Updates policy state for a single accessed entry.
+    #[inline]
+    fn access_entry(&mut self, key: u64) {
+        let _ = self
+            .policy
+            .access(key, &self.wheel.clock, &mut self.entries)
+            .map_err(|e| {
+                log::error!("access(key={}): {}", key, e);
+            });
+    }
+
+    /// Sets a callback consulted for each entry about to expire.
+    ///
+    /// Every expiry path asks it: `advance` and its variants, `purge_expired`
+    /// and `remove_if_expired`. The callback is called with the key and may
+    /// return a new TTL in nanoseconds to renew the entry instead of reaping
+    /// it, which turns expiration into a refresh-ahead hook. A TTL of 0 keeps
+    /// the entry with no expiration going forward; `None`, a negative value
+    /// or a raised exception lets it expire. The cache isn't borrowed while
+    /// the callback runs, so it may call back into the cache; a key it
+    /// rewrites or removes itself is left as it is.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - `Callable[[int], Optional[int]]`, or `None` to remove it
+    pub fn set_expiry_interceptor(&mut self, callback: Option<Py<PyAny>>) {
+        self.expiry_interceptor = callback;
+    }
+
+    /// Marks a resident entry as holding writes not yet flushed.
+    ///
+    /// For write-back setups: once a dirty entry is evicted or expires, the
+    /// `set_flush_on_evict` callback gets a chance to persist it. Missing
+    /// keys are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    pub fn mark_dirty(&mut self, key: u64) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.dirty = true;
+        }
+    }
+
+    /// Marks a resident entry as flushed, missing keys are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    pub fn mark_clean(&mut self, key: u64) {
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.dirty = false;
+        }
+    }
+
+    /// Returns the resident keys marked dirty, in no particular order.
+    #[must_use]
+    pub fn dirty_keys(&self) -> Vec<u64> {
+        self.entries
+            .iter()
+            .filter(|(_, entry)| entry.dirty)
+            .map(|(&key, _)| key)
+            .collect()
+    }
+
+    /// Sets a callback called with each dirty key the cache evicts or expires.
+    ///
+    /// The callback runs once per entry, after the call that dropped it has
+    /// released the cache, so unflushed writes can be persisted and the
+    /// callback may use the cache again. Evictions count whether the policy
+    /// or `evict_coldest` picked the victim. Explicit removals such as
+    /// `remove`, a TTL of -1, `invalidate_group` or `clear` don't call it.
+    /// Exceptions are logged and otherwise ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - `Callable[[int], object]`, or `None` to remove it
+    pub fn set_flush_on_evict(&mut self, callback: Option<Py<PyAny>>) {
+        self.flush_on_evict = callback;
+    }
+
+    /// Sets a callback signaled by `access` for hot keys close to expiring.
+    ///
+    /// An accessed key triggers the callback, once per `access` call, when
+    /// its estimated frequency is at least `freq_threshold` and its remaining
+    /// TTL is below `ttl_fraction` of the TTL it was last set with. The
+    /// callback is only a signal, e.g. to start an asynchronous re-fetch;
+    /// the entry isn't modified and the return value is ignored. Entries
+    /// without a TTL never trigger it. The cache stays mutably borrowed while
+    /// the callback runs, so it can't modify the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - `Callable[[int], object]`, or `None` to remove it
+    /// * `freq_threshold` - Minimum estimated frequency of the key
+    /// * `ttl_fraction` - Fraction of the original TTL, from 0.0 to 1.0
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` if `ttl_fraction` is outside 0.0 to 1.0
+    pub fn set_refresh_ahead(
+        &mut self,
+        callback: Option<Py<PyAny>>,
+        freq_threshold: usize,
+        ttl_fraction: f64,
+    ) -> PyResult<()> {
+        if !(0.0..=1.0).contains(&ttl_fraction) {
+            return Err(CacheError::validation(format!(
+                "ttl_fraction must be between 0.0 and 1.0, got {}",
+                ttl_fraction
+            ))
+            .into());
+        }
+        self.refresh_ahead = callback.map(|callback| RefreshAhead {
+            callback,
+            freq_threshold,
+            ttl_fraction,
+        });
+        Ok(())
+    }
+
+    /// Processes TTL expirations and removes expired entries from the cache.
+    ///
+    /// This advances the internal timer wheel and returns all keys that expired
+    /// during the advancement.
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were expired and removed
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, a policy inconsistency raises `RuntimeError` once all
+    /// expired entries have been removed.
+    pub fn advance(slf: &Bound<'_, Self>) -> PyResult<Vec<u64>> {
+        let now = slf.borrow().wheel.clock.now_ns();
+        Self::advance_to(slf, now)
+    }
+
+    /// Like `advance`, but scans at most `max_buckets` timer wheel buckets.
+    ///
+    /// After a long pause a single `advance` sweeps every level of the
+    /// wheel at once. This stops short of the current time once the bucket
+    /// budget would be exceeded, so the backlog can be drained over several
+    /// calls, e.g. `while cache.is_advance_pending()`. Each call moves at
+    /// least one level 0 tick forward, even past the budget.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_buckets` - Maximum number of buckets to scan
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were expired and removed
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, a policy inconsistency raises `RuntimeError` once all
+    /// expired entries have been removed.
+    pub fn advance_incremental(slf: &Bound<'_, Self>, max_buckets: usize) -> PyResult<Vec<u64>> {
+        Self::expire_intercepted(
+            slf,
+            |cache| {
+                let now = cache.wheel.clock.now_ns();
+                let expired = cache
+                    .wheel
+                    .advance_incremental(now, max_buckets, &mut cache.entries);
+                Ok((now, expired))
+            },
+            Self::remove_expired,
+        )
+    }
+
+    /// Like `advance`, then releases the storage of emptied timer wheel buckets.
+    ///
+    /// Buckets keep their allocation after their entries expire, so a burst
+    /// of short-lived entries leaves memory behind in a long-lived cache.
+    /// This trades a pass over the buckets for a lower steady-state
+    /// footprint; buckets still holding entries are left untouched.
+    ///
+    /// # Returns
     ///
     /// Vector of keys that were expired and removed
-    pub fn advance(&mut self) -> Vec<u64> {
-        let expired = self
-            .wheel
-            .advance(self.wheel.clock.now_ns(), &mut self.entries);
+    ///
+    /// # Errors
+    ///
+    /// In strict mode, a policy inconsistency raises `RuntimeError` once all
+    /// expired entries have been removed and the wheel compacted.
+    pub fn advance_and_compact(slf: &Bound<'_, Self>) -> PyResult<Vec<u64>> {
+        let expired = Self::advance(slf);
+        slf.borrow_mut().wheel.compact();
+        expired
+    }
+
+    /// Returns the entry map's excess capacity to the allocator.
+    ///
+    /// The map keeps its peak allocation after a burst of removals or
+    /// expirations. Shrinking rehashes every remaining entry, so this is
+    /// meant for cleanup after a burst, alongside `advance_and_compact`,
+    /// rather than the hot path.
+    pub fn shrink_entries(&mut self) {
+        self.entries.shrink_to_fit();
+    }
+
+    /// Like `advance`, but hands the expired keys to `callback` in one call.
+    ///
+    /// The callback receives the whole list of expired keys once the reap
+    /// has completed, saving a crossing per key for callers that just
+    /// forward the list. It isn't called when nothing expired. The cache is
+    /// no longer borrowed while it runs, so it may call back into the cache.
+    ///
+    /// # Arguments
+    ///
+    /// * `callback` - Callable taking a list of expired keys
+    pub fn advance_notify(slf: &Bound<'_, Self>, callback: &Bound<'_, PyAny>) -> PyResult<()> {
+        let now = slf.borrow().wheel.clock.now_ns();
+        Self::advance_notify_to(slf, now, callback)
+    }
+
+    /// Advances to an explicit time, rejecting time that moves backward.
+    ///
+    /// For tests and simulations driving the cache with synthetic time.
+    /// Unlike `advance`, which ignores a backward jump, this raises so the
+    /// caller can assert its clock is monotonic.
+    ///
+    /// # Arguments
+    ///
+    /// * `now` - Time in nanoseconds on the cache clock, see `clock_now_ns`
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were expired and removed
+    pub fn advance_checked(slf: &Bound<'_, Self>, now: u64) -> PyResult<Vec<u64>> {
+        Self::expire_intercepted(
+            slf,
+            |cache| {
+                let expired = cache
+                    .wheel
+                    .advance_checked(now, &mut cache.entries)
+                    .map_err(|e| CacheError::validation(e.to_string()))?;
+                Ok((now, expired))
+            },
+            Self::remove_expired,
+        )
+    }
+
+    /// Returns whether an `advance` now would have buckets to process.
+    ///
+    /// A cheap check of the timer wheel buckets between the last advance and
+    /// the current time, without touching any entry. Entries in those
+    /// buckets may only get moved to a finer level rather than expired, so
+    /// `true` means expiration work is pending, not that a key will expire.
+    #[must_use]
+    pub fn is_advance_pending(&self) -> bool {
+        self.wheel.is_advance_pending(self.wheel.clock.now_ns())
+    }
+
+    /// Removes every expired entry by scanning all entries directly.
+    ///
+    /// Unlike `advance`, this doesn't depend on wheel bucket granularity: it is
+    /// an O(n) correctness backstop that reaps anything past its deadline.
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were expired and removed
+    pub fn purge_expired(slf: &Bound<'_, Self>) -> PyResult<Vec<u64>> {
+        Self::expire_intercepted(
+            slf,
+            |cache| {
+                let now = cache.wheel.clock.now_ns();
+                let expired = cache
+                    .entries
+                    .iter()
+                    .filter(|(_, entry)| entry.is_expired(now))
+                    .map(|(&key, _)| key)
+                    .collect();
+                Ok((now, expired))
+            },
+            |cache, expired| {
+                cache.drop_expired(&expired);
+                if !expired.is_empty() {
+                    log::debug!("Purge: {} entries expired", expired.len());
+                }
+                Ok(expired)
+            },
+        )
+    }
+
+    /// Removes those of the given keys that are resident and expired.
+    ///
+    /// Live and missing keys are left alone. A targeted alternative to
+    /// `advance` and `purge_expired` for cleaning up a known subset, e.g.
+    /// when reconciling an external index.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Keys to check
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were expired and removed
+    pub fn remove_if_expired(slf: &Bound<'_, Self>, keys: Vec<u64>) -> PyResult<Vec<u64>> {
+        Self::expire_intercepted(
+            slf,
+            |cache| {
+                let now = cache.wheel.clock.now_ns();
+                let mut seen = HashSet::with_capacity(keys.len());
+                let expired = keys
+                    .into_iter()
+                    .filter(|key| {
+                        cache
+                            .entries
+                            .get(key)
+                            .is_some_and(|entry| entry.is_expired(now))
+                            && seen.insert(*key)
+                    })
+                    .collect();
+                Ok((now, expired))
+            },
+            |cache, expired| {
+                cache.drop_expired(&expired);
+                Ok(expired)
+            },
+        )
+    }
+
+    /// Removes a key only if its expiration still matches `expected_expire`.
+    ///
+    /// A compare-and-remove for invalidation protocols: the caller reads the
+    /// expiration via `entry_info`, and a stale invalidation can't remove an
+    /// entry that was rewritten with a new TTL since that read.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key to remove
+    /// * `expected_expire` - The expiration timestamp the caller last observed
+    ///
+    /// # Returns
+    ///
+    /// `true` if the key was resident with a matching expiration and removed
+    pub fn remove_if_expire_eq(&mut self, key: u64, expected_expire: u64) -> bool {
+        if self
+            .entries
+            .get(&key)
+            .is_none_or(|entry| entry.expire != expected_expire)
+        {
+            return false;
+        }
+        self.remove_internal(key);
+        true
+    }
+
+    /// Removes every entry last written before `cutoff_ns`.
+    ///
+    /// Independent of TTLs, this invalidates entries by generation, e.g. all
+    /// keys written before a config reload. Updating a key with `set`
+    /// refreshes its insertion time.
+    ///
+    /// # Arguments
+    ///
+    /// * `cutoff_ns` - Time in nanoseconds on the cache clock, see `clock_now_ns`
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were removed
+    pub fn remove_inserted_before(&mut self, cutoff_ns: u64) -> Vec<u64> {
+        let stale: Vec<u64> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| entry.inserted_at < cutoff_ns)
+            .map(|(&key, _)| key)
+            .collect();
+
+        for &key in &stale {
+            self.remove_internal(key);
+        }
+        if !stale.is_empty() {
+            log::debug!(
+                "Removed {} entries inserted before {}",
+                stale.len(),
+                cutoff_ns
+            );
+        }
+
+        stale
+    }
+
+    /// Returns the key at the front of the eviction order.
+    ///
+    /// An O(1) peek at what capacity eviction considers first: by default
+    /// the least recently used probation key, else the protected one, else
+    /// the window's, see `set_victim_priority`. Whether it is actually
+    /// evicted still depends on the admission contest with the incoming
+    /// candidate.
+    ///
+    /// # Returns
+    ///
+    /// The key, or `None` if the cache is empty
+    #[must_use]
+    pub fn coldest_key(&self) -> Option<u64> {
+        self.policy.coldest_key()
+    }
+
+    /// Removes up to `count` of the coldest entries at the current capacity.
+    #[pyo3(name = "evict_coldest")]
+    pub fn py_evict_coldest(slf: &Bound<'_, Self>, count: usize) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.evict_coldest(count))
+    }
+
+    /// Lets the cache exceed its capacity by `extra` entries before evicting.
+    ///
+    /// Inserts stop evicting until the size passes `capacity + extra`, so a
+    /// burst of writes doesn't pay for eviction on every insert. Call `trim`
+    /// once the burst subsides to evict back down to the hard capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `extra` - Number of entries tolerated above capacity, 0 disables
+    pub fn set_overflow_allowance(&mut self, extra: usize) {
+        self.policy.set_overflow_allowance(extra);
+    }
+
+    /// Makes `access`, `remove` and `advance` raise on policy inconsistencies.
+    ///
+    /// By default these conditions, which indicate a bug, are only logged so
+    /// production traffic keeps flowing. Strict mode turns them into
+    /// `RuntimeError`s, which is useful in CI and staging.
+    ///
+    /// # Arguments
+    ///
+    /// * `strict` - Whether to raise instead of logging, off by default
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Checks the policy's size accounting after every mutating call.
+    ///
+    /// After each `set`, `access`, `remove` and `advance`, the tracked size
+    /// is compared with the segment lengths, which is cheap but not free. A
+    /// mismatch is logged with the operation that caused it; in strict mode
+    /// it raises a `RuntimeError` instead. `set` has no error to raise and
+    /// always logs, `set_with_error` raises. Meant for development and CI.
+    ///
+    /// # Arguments
+    ///
+    /// * `enabled` - Whether to check, off by default
+    pub fn set_debug_invariants(&mut self, enabled: bool) {
+        self.debug_invariants = enabled;
+    }
+
+    /// Spares recently inserted keys as eviction victims.
+    ///
+    /// Keys inserted within the last `grace_ns` are passed over by the
+    /// admission contest while an older key can be evicted instead, so a
+    /// burst of writes doesn't evict its own keys right away. Once only
+    /// graced keys remain, eviction proceeds anyway to respect capacity.
+    ///
+    /// # Arguments
+    ///
+    /// * `grace_ns` - Grace period in nanoseconds, 0 disables
+    pub fn set_insertion_grace(&mut self, grace_ns: u64) {
+        self.insertion_grace = grace_ns;
+    }
+
+    /// Clamps TTLs passed to `set` and `set_entry` to at most `max_ns`.
+    ///
+    /// A guardrail against unit mix-ups: a TTL in seconds passed as
+    /// nanoseconds, or the other way around, can't park an entry in the
+    /// overflow level forever. Larger TTLs are clamped silently, apart from
+    /// a debug log. A TTL of 0 still means no expiration.
+    ///
+    /// # Arguments
+    ///
+    /// * `max_ns` - Largest TTL in nanoseconds, 0 (the default) disables the clamp
+    pub fn set_max_ttl(&mut self, max_ns: u64) {
+        self.max_ttl = max_ns;
+    }
+
+    /// Stops counting writes of keys that keep expiring without being read.
+    ///
+    /// Each expiry of an entry that wasn't accessed since it was last
+    /// written counts against its key, and an expiry after a read clears
+    /// the count. Once a key has expired unread `penalty` times, inserting
+    /// it no longer bumps its frequency, so churny write-only keys can't win
+    /// admission on write volume alone. The history outlives the entries and
+    /// `clear`, and is dropped by `full_reset`.
+    ///
+    /// # Arguments
+    ///
+    /// * `penalty` - Unread expiries before writes stop counting, 0 disables
+    pub fn set_expiry_penalty(&mut self, penalty: usize) {
+        self.expiry_penalty = penalty;
+        if penalty == 0 {
+            self.expired_unread.clear();
+        }
+    }
+
+    /// Evicts down to the hard capacity, ignoring the overflow allowance.
+    #[pyo3(name = "trim")]
+    pub fn py_trim(slf: &Bound<'_, Self>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.trim())
+    }
+
+    /// Returns everything known about every live entry, in eviction order.
+    ///
+    /// A census for offline analysis in one pass instead of a per-key
+    /// `entry_info` round-trip. As in `export_state`, the remaining TTL is an
+    /// unsigned count of nanoseconds with 0 meaning no expiration; idle timeouts aren't
+    /// applied to it. The access count is kept since the entry was created,
+    /// updates don't reset it.
+    ///
+    /// # Returns
+    ///
+    /// `(key, remaining_ttl, policy_list_id, frequency, access_count)`
+    /// tuples, coldest first
+    #[must_use]
+    pub fn dump(&self) -> Vec<(u64, u64, u8, usize, u32)> {
+        let now = self.wheel.clock.now_ns();
+        self.policy
+            .eviction_order()
+            .into_iter()
+            .filter_map(|key| {
+                let entry = self.entries.get(&key)?;
+                if entry.is_expired(now) {
+                    return None;
+                }
+                let ttl = match entry.expire {
+                    0 => 0,
+                    expire => expire - now,
+                };
+                Some((
+                    key,
+                    ttl,
+                    entry.policy_list_id,
+                    self.policy.sketch.estimate(key),
+                    entry.access_count,
+                ))
+            })
+            .collect()
+    }
+
+    /// Exports the policy placement of every live entry in eviction order.
+    ///
+    /// Together with `import_state` this migrates a cache to a differently
+    /// sized instance without losing which segment each key lives in. To stay
+    /// valid across clocks, the third field is the remaining TTL in
+    /// nanoseconds rather than an absolute deadline (0 means no expiration).
+    /// Idle timeouts, groups and sketch frequencies aren't exported.
+    ///
+    /// # Returns
+    ///
+    /// `(key, policy_list_id, remaining_ttl)` tuples, coldest first
+    #[must_use]
+    pub fn export_state(&self) -> Vec<(u64, u8, u64)> {
+        let now = self.wheel.clock.now_ns();
+        self.policy
+            .eviction_order()
+            .into_iter()
+            .filter_map(|key| {
+                let entry = self.entries.get(&key)?;
+                if entry.is_expired(now) {
+                    return None;
+                }
+                let ttl = match entry.expire {
+                    0 => 0,
+                    expire => expire - now,
+                };
+                Some((key, entry.policy_list_id, ttl))
+            })
+            .collect()
+    }
+
+    /// Nudges a key's estimated frequency from an external importance signal.
+    ///
+    /// A positive `delta` counts that many extra accesses in the sketch, which
+    /// biases the admission contest toward the key whether or not it's
+    /// resident. The sketch can't cheaply decrement a single key's counters,
+    /// so a negative `delta` changes nothing and only logs a warning; the
+    /// key's frequency still decays with the sketch's periodic reset.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The key hash to adjust
+    /// * `delta` - Number of extra accesses to count
+    pub fn adjust_frequency(&mut self, key: u64, delta: i8) {
+        if delta < 0 {
+            log::warn!(
+                "adjust_frequency(key={}): negative delta {} is not supported",
+                key,
+                delta
+            );
+            return;
+        }
+        for _ in 0..delta {
+            self.policy.sketch.add(key);
+        }
+    }
+
+    /// Inserts keys straight into probation, bypassing the admission contest.
+    #[pyo3(name = "warm_fill")]
+    pub fn py_warm_fill(slf: &Bound<'_, Self>, keys: Vec<(u64, i64)>) {
+        Self::flushing(slf, |cache| cache.warm_fill(keys));
+    }
+
+    /// Replaces the cache contents with state from `export_state`.
+    #[pyo3(name = "import_state")]
+    pub fn py_import_state(slf: &Bound<'_, Self>, state: Vec<(u64, u8, u64)>) -> Vec<u64> {
+        Self::flushing(slf, |cache| cache.import_state(state))
+    }
+
+    /// Exchanges the contents of two caches in place.
+    ///
+    /// Swaps the policy state, timer wheel and entries (along with group
+    /// membership), so a cache warmed in the background can replace the
+    /// serving one. Per-contents bookkeeping moves along: keys pending for
+    /// `take_evicted`, the unread expiry history and the recent eviction
+    /// counts. Settings made through the setters, including the policy's
+    /// thresholds, window policy and callbacks, stay with each cache.
+    ///
+    /// Each wheel keeps its own clock, so expiration continues against the
+    /// swapped-in wheel's clock base. Rebuild TTLs relative to a consistent
+    /// base if precise timing matters.
+    ///
+    /// # Arguments
+    ///
+    /// * `other` - The cache to exchange contents with
+    pub fn swap(&mut self, other: &mut TlfuCore) {
+        std::mem::swap(&mut self.policy, &mut other.policy);
+        // the policy carries its settings, move them back
+        self.policy.swap_config(&mut other.policy);
+        std::mem::swap(&mut self.wheel, &mut other.wheel);
+        std::mem::swap(&mut self.entries, &mut other.entries);
+        std::mem::swap(&mut self.groups, &mut other.groups);
+        std::mem::swap(&mut self.pending_evicted, &mut other.pending_evicted);
+        std::mem::swap(&mut self.expired_unread, &mut other.expired_unread);
+        std::mem::swap(&mut self.eviction_samples, &mut other.eviction_samples);
+        for cache in [&mut *self, &mut *other] {
+            while cache.eviction_samples.len() > cache.eviction_samples_cap {
+                cache.eviction_samples.pop_front();
+            }
+        }
+    }
+
+    /// Removes all entries from the cache.
+    pub fn clear(&mut self) {
+        self.policy.clear();
+        self.wheel.clear();
+        self.entries.clear();
+        self.groups.clear();
+        self.pending_evicted.clear();
+        log::debug!("Cache cleared");
+    }
+
+    /// Removes all entries and forgets all frequency history.
+    ///
+    /// Unlike `clear`, which keeps the sketch so hotness survives, this also
+    /// zeroes the sketch and puts the window/protected split and the hill
+    /// climber back to their initial state; a `new_small` cache stays a plain
+    /// LRU. Use it after a major workload shift so stale hotness doesn't bias
+    /// the new workload. Settings such as thresholds and the window policy
+    /// are kept.
+    pub fn full_reset(&mut self) {
+        self.clear();
+        self.policy.full_reset();
+        self.eviction_samples.clear();
+        self.expired_unread.clear();
+        log::debug!("Cache fully reset");
+    }
+
+    /// Returns the number of entries currently in the cache.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns the number of entries that haven't expired yet.
+    ///
+    /// `len` also counts entries past their deadline that `advance` hasn't
+    /// reaped yet. This gives the live count without reaping anything; it
+    /// is O(n) in the number of entries.
+    #[must_use]
+    pub fn live_len(&self) -> usize {
+        let now = self.wheel.clock.now_ns();
+        self.entries
+            .values()
+            .filter(|entry| !entry.is_expired(now))
+            .count()
+    }
+
+    /// Returns the number of entries that never expire.
+    ///
+    /// These are entries with neither a TTL nor an idle timeout, which are
+    /// never reaped by `advance` and only leave the cache through eviction.
+    #[must_use]
+    pub fn immortal_count(&self) -> usize {
+        self.entries
+            .values()
+            .filter(|entry| entry.deadline() == 0)
+            .count()
+    }
+
+    /// Returns how many not yet expired entries `advance` moved to another
+    /// timer wheel bucket over the wheel's lifetime.
+    ///
+    /// Entries with long TTLs start in coarse levels and cascade down as
+    /// they approach expiration. A high ratio of reschedules to expirations
+    /// means that cascading dominates `advance` cost, and a different level
+    /// layout (see `with_wheel_levels`) may help.
+    #[must_use]
+    pub fn reschedule_count(&self) -> u64 {
+        self.wheel.reschedule_count()
+    }
+
+    /// Returns the number of entries scheduled for expiration in the timer wheel.
+    ///
+    /// Entries without a TTL aren't scheduled, so this normally equals the
+    /// number of entries with an expiration. A difference signals a
+    /// scheduling bug. It also gauges the expiration work pending on `advance`.
+    #[must_use]
+    pub fn scheduled_count(&self) -> usize {
+        self.wheel.scheduled_count()
+    }
+
+    /// Returns the number of scheduled entries in each timer wheel level.
+    ///
+    /// Levels are ordered from the finest to the overflow level, so this is a
+    /// histogram of remaining TTLs at wheel resolution. Entries clustering in
+    /// the first or the last level suggest a different level layout.
+    ///
+    /// # Returns
+    ///
+    /// One count per wheel level, 5 with the default layout
+    #[must_use]
+    pub fn ttl_distribution(&self) -> Vec<usize> {
+        self.wheel.level_occupancy()
+    }
+
+    /// Counts live entries per band of remaining TTL.
+    ///
+    /// With boundaries `b0 < b1 < ... < bn`, the bands are `[0, b0)`,
+    /// `[b0, b1)`, ..., `[bn, inf)`, followed by one more band for entries
+    /// that never expire. The remaining TTL is measured to the entry's
+    /// deadline, which accounts for idle timeouts. Expired entries not yet
+    /// reaped aren't counted. Scans all entries once.
+    ///
+    /// # Arguments
+    ///
+    /// * `boundaries` - Band boundaries in nanoseconds, strictly increasing
+    ///
+    /// # Returns
+    ///
+    /// `boundaries.len() + 2` counts, the last for entries without expiration
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` if `boundaries` isn't strictly increasing
+    pub fn ttl_histogram(&self, boundaries: Vec<u64>) -> PyResult<Vec<usize>> {
+        if boundaries.windows(2).any(|pair| pair[0] >= pair[1]) {
+            return Err(CacheError::validation("boundaries must be strictly increasing").into());
+        }
+        let now = self.wheel.clock.now_ns();
+        let mut counts = vec![0; boundaries.len() + 2];
+        for entry in self.entries.values() {
+            match entry.deadline() {
+                0 => counts[boundaries.len() + 1] += 1,
+                deadline if deadline > now => {
+                    let remaining = deadline - now;
+                    counts[boundaries.partition_point(|&bound| bound <= remaining)] += 1;
+                }
+                _ => {}
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Checks timer wheel scheduling invariants against the cache entries.
+    ///
+    /// # Returns
+    ///
+    /// Vector of (key, description) pairs, empty when the wheel is consistent
+    #[must_use]
+    pub fn validate_wheel(&self) -> Vec<(u64, String)> {
+        self.wheel.validate_wheel(&self.entries)
+    }
+
+    /// Checks all policy and timer wheel invariants.
+    ///
+    /// Raises a `RuntimeError` listing every violation found, see
+    /// `consistency_check`. Meant for diagnosing suspected corruption, it
+    /// walks every entry and list.
+    pub fn check_consistency(&self) -> PyResult<()> {
+        self.consistency_check()
+            .map_err(|issues| CacheError::corruption(issues.join("; ")).into())
+    }
+
+    /// Returns how many more entries fit before inserts start evicting.
+    ///
+    /// Counts against the configured capacity, not the overflow allowance.
+    #[must_use]
+    pub fn free_slots(&self) -> usize {
+        self.policy.capacity().saturating_sub(self.entries.len())
+    }
+
+    /// Sets a batch only if it fits without eviction, see `try_set`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `ValueError` with the overflow count if the batch doesn't fit;
+    /// the cache is left untouched.
+    pub fn set_if_fits(&mut self, entries: Vec<(u64, i64)>) -> PyResult<Vec<u64>> {
+        self.try_set(entries).map_err(|overflow| {
+            CacheError::validation(format!("batch exceeds free slots by {}", overflow)).into()
+        })
+    }
+
+    /// Returns the current window capacity as a fraction of total capacity.
+    ///
+    /// The window starts at 1% and is adjusted by the hill climber, so this
+    /// shows where the adaptive sizing has settled for the current workload.
+    #[must_use]
+    pub fn current_window_ratio(&self) -> f64 {
+        self.policy.window_ratio()
+    }
+
+    /// Returns hit counts broken down by the segment that served the hit.
+    ///
+    /// Only hits on live entries are counted. Mostly window hits with few
+    /// protected hits suggest the SLRU split doesn't suit the workload.
+    ///
+    /// # Returns
+    ///
+    /// `(window_hits, probation_hits, protected_hits)` since creation
+    #[must_use]
+    pub fn segment_hit_stats(&self) -> (u64, u64, u64) {
+        self.policy.segment_hits()
+    }
+
+    /// Returns the number of sketch additions since the last frequency reset.
+    #[must_use]
+    pub fn sketch_additions(&self) -> usize {
+        self.policy.sketch.additions()
+    }
+
+    /// Returns how many times the frequency sketch has been halved.
+    ///
+    /// Frequent resets relative to traffic suggest the sample size is too
+    /// small for the key population.
+    #[must_use]
+    pub fn sketch_reset_count(&self) -> u64 {
+        self.policy.sketch.reset_count()
+    }
+
+    /// Halves every frequency estimate now, as the sketch's periodic reset does.
+    ///
+    /// Meant for tests and diagnostics that need the aging step without
+    /// pumping `sample_size` additions through the sketch.
+    pub fn decay_sketch(&mut self) {
+        self.policy.sketch.decay();
+    }
+
+    /// Returns the number of 64-bit counter words in the frequency sketch.
+    ///
+    /// The table holds `capacity.next_power_of_two()` words (at least 64),
+    /// so its memory footprint is eight bytes per word.
+    #[must_use]
+    pub fn sketch_table_len(&self) -> usize {
+        self.policy.sketch.table_size()
+    }
+
+    /// Returns the number of additions after which the sketch is halved.
+    #[must_use]
+    pub fn sketch_sample_size(&self) -> usize {
+        self.policy.sketch.sample_size
+    }
+
+    /// Caps the number of additions after which the sketch is halved.
+    ///
+    /// By default the sample size is ten times the counter count, bounded by
+    /// 2^30. Lowering it makes very large sketches age their counters sooner.
+    ///
+    /// # Arguments
+    ///
+    /// * `max` - Largest sample size, at least 1
+    pub fn set_sketch_max_sample_size(&mut self, max: usize) -> PyResult<()> {
+        if max < 1 {
+            return Err(CacheError::validation("sketch sample size must be at least 1").into());
+        }
+        self.policy.sketch.cap_sample_size(max);
+        Ok(())
+    }
+
+    /// Returns the time in nanoseconds the timer wheel was last advanced to.
+    ///
+    /// Compare with `clock_now_ns` to observe how far the wheel lags behind
+    /// the clock between `advance` calls.
+    #[must_use]
+    pub fn wheel_nanos(&self) -> u64 {
+        self.wheel.nanos()
+    }
+
+    /// Returns the cache clock's current time in nanoseconds since creation.
+    #[must_use]
+    pub fn clock_now_ns(&self) -> u64 {
+        self.wheel.clock.now_ns()
+    }
+
+    /// Returns the average number of evictions over the most recent batches.
+    ///
+    /// Every `set`, `set_with_tti` and `set_grouped` batch records how many
+    /// keys it evicted. A rising rate means the working set is outgrowing the
+    /// cache, usually before the hit rate visibly drops.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Number of most recent batches to average over
+    ///
+    /// # Returns
+    ///
+    /// Average evictions per batch, 0.0 if nothing was recorded yet
+    #[must_use]
+    pub fn recent_eviction_rate(&self, window: usize) -> f64 {
+        let window = window.min(self.eviction_samples.len());
+        if window == 0 {
+            return 0.0;
+        }
+        let total: usize = self.eviction_samples.iter().rev().take(window).sum();
+        total as f64 / window as f64
+    }
+
+    /// Returns a 0-100 health score for monitoring.
+    ///
+    /// Sums the weights of the `health_details` checks that pass. The two
+    /// accounting checks weigh 35 each and the two load checks 15 each, so a
+    /// score below 70 means a bookkeeping bug rather than just load.
+    #[must_use]
+    pub fn health_score(&self) -> u8 {
+        self.health_checks()
+            .iter()
+            .filter(|&&(_, _, passed)| passed)
+            .map(|&(_, weight, _)| weight)
+            .sum()
+    }
+
+    /// Lists the checks behind `health_score` and whether each passed.
+    ///
+    /// - `size_matches_segments`: the policy's size equals its segment lengths
+    /// - `wheel_matches_entries`: the wheel schedules exactly the entries
+    ///   with an expiration
+    /// - `sketch_not_saturated`: at most 10% of sketch counters are maxed out
+    /// - `eviction_pressure_low`: recent batches evicted at most 10% of
+    ///   capacity on average
+    ///
+    /// The sketch check scans the counter table and the wheel check all
+    /// entries, so this is meant for a monitoring endpoint, not every call.
+    #[must_use]
+    pub fn health_details(&self) -> Vec<(String, bool)> {
+        self.health_checks()
+            .into_iter()
+            .map(|(name, _, passed)| (name.to_string(), passed))
+            .collect()
+    }
+
+    /// Sets how many per-batch eviction counts are kept, 64 by default.
+    ///
+    /// # Arguments
+    ///
+    /// * `samples` - Ring buffer size, at least 1
+    pub fn set_eviction_sample_size(&mut self, samples: usize) -> PyResult<()> {
+        if samples < 1 {
+            return Err(CacheError::validation("eviction sample size must be at least 1").into());
+        }
+        self.eviction_samples_cap = samples;
+        while self.eviction_samples.len() > samples {
+            self.eviction_samples.pop_front();
+        }
+        Ok(())
+    }
+
+    /// Returns the effective cache geometry.
+    ///
+    /// `new` silently clamps a size of 0 to 1 and derives the segment sizes,
+    /// so this reports what was actually built: `capacity`, the `window`,
+    /// `probation` and `protected` segment capacities (adapted over time by
+    /// the hill climber) and the `sketch` table size in counters.
+    #[must_use]
+    pub fn geometry(&self) -> HashMap<String, usize> {
+        self.policy.geometry()
+    }
+
+    /// Returns debugging information about the cache state.
+    #[must_use]
+    pub fn debug_info(&self) -> DebugInfo {
+        self.policy.debug_info()
+    }
+
+    /// Returns everything known about a single key.
+    ///
+    /// Includes its policy segment, expiration, wheel position and estimated
+    /// frequency, or `None` if the key is not resident.
+    #[must_use]
+    pub fn entry_info(&self, key: u64) -> Option<EntryInfo> {
+        self.entries
+            .get(&key)
+            .map(|entry| self.policy.entry_info(key, entry))
+    }
+
+    /// Returns the timer wheel level a key is scheduled in.
+    ///
+    /// A single-key view of `ttl_distribution`, for checking that a key's TTL
+    /// landed in the level its duration calls for.
+    ///
+    /// # Returns
+    ///
+    /// The level, or `None` if the key isn't cached or never expires
+    #[must_use]
+    pub fn wheel_level_of(&self, key: u64) -> Option<u8> {
+        self.entries
+            .get(&key)
+            .filter(|entry| entry.wheel_list_index.is_some())
+            .map(|entry| entry.wheel_index.0)
+    }
+
+    /// Moves an entry to a new key, keeping its policy position and TTL.
+    ///
+    /// The key is replaced in place in its policy segment and timer wheel
+    /// bucket, and the new key inherits the old key's estimated frequency.
+    ///
+    /// # Returns
+    ///
+    /// `true` if renamed, `false` if `old_key` isn't cached or `new_key` is
+    pub fn rename(&mut self, old_key: u64, new_key: u64) -> bool {
+        if old_key == new_key || self.entries.contains_key(&new_key) {
+            return false;
+        }
+        let Some(entry) = self.entries.remove(&old_key) else {
+            return false;
+        };
+        if let Err(e) = self.policy.rename(old_key, new_key, &entry) {
+            log::error!("rename(key={}): {}", old_key, e);
+        }
+        if entry.wheel_list_index.is_some() && !self.wheel.rename(new_key, &entry) {
+            log::error!("rename(key={}): not found in timer wheel", old_key);
+        }
+        self.untrack_group(old_key, entry.group);
+        if let Some(group) = entry.group {
+            self.groups.entry(group).or_default().insert(new_key);
+        }
+        self.entries.insert(new_key, entry);
+        log::debug!("Renamed key {} to {}", old_key, new_key);
+        true
+    }
+
+    /// Returns whether a key sits in the protected segment.
+    ///
+    /// # Returns
+    ///
+    /// `Some(true)` if protected, `Some(false)` if in the window or
+    /// probation, `None` if the key isn't cached
+    #[must_use]
+    pub fn is_protected(&self, key: u64) -> Option<bool> {
+        self.entries
+            .get(&key)
+            .map(|entry| entry.policy_list_id == 3)
+    }
+
+    /// Returns the `n` resident keys with the highest estimated frequency.
+    ///
+    /// Keeps a bounded min-heap of size `n` rather than sorting every entry.
+    ///
+    /// # Returns
+    ///
+    /// `(key, estimate)` pairs sorted by estimate descending
+    #[must_use]
+    pub fn top_keys(&self, n: usize) -> Vec<(u64, usize)> {
+        if n == 0 {
+            return Vec::new();
+        }
+
+        let mut heap = BinaryHeap::with_capacity(n + 1);
+        for &key in self.entries.keys() {
+            heap.push(Reverse((self.policy.sketch.estimate(key), key)));
+            if heap.len() > n {
+                heap.pop();
+            }
+        }
+
+        heap.into_sorted_vec()
+            .into_iter()
+            .map(|Reverse((estimate, key))| (key, estimate))
+            .collect()
+    }
+
+    /// Returns all keys currently stored in the cache.
+    #[must_use]
+    pub fn keys(&self) -> Vec<u64> {
+        self.entries.keys().copied().collect()
+    }
+
+    /// Returns one page of the key set.
+    ///
+    /// Lets a monitoring loop pull the keys of a large cache in bounded chunks,
+    /// releasing the external lock between calls. The snapshot isn't
+    /// transactional across chunks: keys written or removed between pages may
+    /// be missed or show up twice.
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Number of keys to skip
+    /// * `limit` - Maximum number of keys to return
+    #[must_use]
+    pub fn snapshot_keys_chunked(&self, offset: usize, limit: usize) -> Vec<u64> {
+        self.entries
+            .keys()
+            .skip(offset)
+            .take(limit)
+            .copied()
+            .collect()
+    }
+
+    /// Returns a cursor over a snapshot of the key set, read in batches.
+    ///
+    /// Unlike `snapshot_keys_chunked`, the key set is copied once up front,
+    /// so batches are consistent with each other no matter how the cache
+    /// changes in between. Python only holds one batch at a time.
+    #[must_use]
+    pub fn key_cursor(&self) -> KeyCursor {
+        KeyCursor {
+            keys: self.keys(),
+            position: 0,
+        }
+    }
+
+    /// Returns all keys in policy order instead of hash order.
+    ///
+    /// Window keys come first, then probation, then protected, each segment
+    /// most recently used first. The order is deterministic for a given
+    /// sequence of operations, which makes snapshots easy to diff.
+    #[must_use]
+    pub fn keys_ordered(&self) -> Vec<u64> {
+        self.policy.ordered_keys()
+    }
+
+    /// Sets multiple entries with panic safety for Python FFI.
+    #[pyo3(name = "set_with_error")]
+    pub fn py_set_with_error(
+        slf: &Bound<'_, Self>,
+        entries: Vec<(u64, i64)>,
+    ) -> PyResult<Vec<u64>> {
+        Self::flushing(slf, |cache| cache.set_with_error(entries))
+    }
+
+    /// Marks entries as accessed with panic safety for Python FFI.
+    pub fn access_with_error(&mut self, keys: Vec<u64>) -> PyResult<()> {
+        use std::panic::AssertUnwindSafe;
+        catch_panic(AssertUnwindSafe(|| self.access(keys)), "access")?
+    }
+
+    /// Advances the timer wheel with panic safety for Python FFI.
+    pub fn advance_with_error(slf: &Bound<'_, Self>) -> PyResult<Vec<u64>> {
+        use std::panic::AssertUnwindSafe;
+        catch_panic(AssertUnwindSafe(|| Self::advance(slf)), "advance")?
+    }
+}
+
+impl TlfuCore {
+    /// Sets multiple cache entries in a batch operation.
+    ///
+    /// Entries with TTL of -1 are removed instead of added. A key repeated
+    /// within the batch is inserted once and later occurrences only update
+    /// its TTL, so the last TTL wins.
+    ///
+    /// New keys enter the window front in batch order, so the last new key
+    /// ends up most recently used. Updated keys keep their position; use
+    /// `set_ordered` when the batch is an access sequence.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were evicted to make room for new entries, or
+    /// rejected by the minimum admission frequency
+    pub fn set(&mut self, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        self.set_full_report(entries).0
+    }
+
+    /// Sets multiple cache entries, making the window recency follow the batch.
+    ///
+    /// Behaves like `set`, then moves the batch keys still in the window to
+    /// the window front in batch order, updated keys included, so the last
+    /// key of the batch is the most recently used. Keys placed in the main
+    /// segments, e.g. by `set_promote_long_ttl_threshold`, keep their position there.
+    /// The reordering doesn't count as an access.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Same as `set`
+    pub fn set_ordered(&mut self, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        let keys: Vec<u64> = entries
+            .iter()
+            .filter(|&&(_, ttl)| ttl != -1)
+            .map(|&(key, _)| key)
+            .collect();
+        let evicted = self.set(entries);
+        for key in keys {
+            self.policy.touch_window(key, &self.entries);
+        }
+        evicted
+    }
+
+    /// Sets multiple cache entries, returning the inserted keys that didn't stick.
+    ///
+    /// Behaves like `set`, but instead of every evicted key it returns the
+    /// keys of this batch that aren't resident afterwards, either rejected by
+    /// the minimum admission frequency or evicted again within the batch.
+    /// Read-through callers can use it to tell whether a fetched value
+    /// actually landed. Resident keys evicted to make room aren't reported,
+    /// so callers that mirror the cache contents should use `set` instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Keys whose last entry in the batch was an insert or update but that
+    /// aren't cached afterwards, in batch order
+    pub fn set_reporting_admission(&mut self, entries: Vec<(u64, i64)>) -> Vec<u64> {
+        let mut last_ttl = HashMap::with_capacity(entries.len());
+        let mut order = Vec::with_capacity(entries.len());
+        for &(key, ttl) in &entries {
+            if last_ttl.insert(key, ttl).is_none() {
+                order.push(key);
+            }
+        }
+
+        self.set(entries);
+        order
+            .into_iter()
+            .filter(|key| last_ttl[key] != -1 && !self.entries.contains_key(key))
+            .collect()
+    }
+
+    /// Sets multiple cache entries, reporting evictions and removals separately.
+    ///
+    /// Behaves like `set`, but also returns the resident keys that were
+    /// explicitly removed by a TTL of -1, so callers can tell the two kinds of
+    /// departure apart.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// `(evicted, removed)` where `evicted` is what `set` would return and
+    /// `removed` holds the keys dropped by ttl=-1 that were present
+    pub fn set_full_report(&mut self, entries: Vec<(u64, i64)>) -> (Vec<u64>, Vec<u64>) {
+        let mut evicted = Vec::new();
+        let mut removed = Vec::new();
+
+        // Eviction is deferred until the whole batch is in, so a bulk insert
+        // runs a single eviction pass instead of one contest per key.
+        for (key, ttl) in entries {
+            match ttl {
+                -1 => {
+                    if self.entries.contains_key(&key) {
+                        removed.push(key);
+                    }
+                    self.remove_internal(key);
+                }
+                _ => {
+                    if let Insert::Rejected = self.insert_entry(key, ttl.unsigned_abs()) {
+                        evicted.push(key);
+                    }
+                }
+            }
+        }
+
+        let evicted = self.evict_batch(evicted);
+        if let Err(e) = self.check_size("set") {
+            // no error to return here, set_with_error raises it in strict mode
+            log::error!("{}", e);
+        }
+        log::debug!(
+            "Set: {} entries evicted, {} removed, size={}",
+            evicted.len(),
+            removed.len(),
+            self.entries.len()
+        );
+
+        (evicted, removed)
+    }
+
+    /// Sets multiple cache entries, seeding the frequency of new keys.
+    ///
+    /// Before a new key is inserted its sketch counter is bumped `frequency`
+    /// times (capped at the counter maximum, 15 or 255 for 8-bit counters),
+    /// so keys restored from a snapshot compete for admission with their
+    /// historical popularity rather than starting cold. Existing keys only
+    /// get their TTL updated.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl, frequency) tuples where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were evicted to make room for new entries
+    pub fn set_with_frequency(&mut self, entries: Vec<(u64, i64, u8)>) -> Vec<u64> {
+        let entries = entries
+            .into_iter()
+            .map(|(key, ttl, frequency)| {
+                if ttl != -1 && !self.entries.contains_key(&key) {
+                    for _ in 0..usize::from(frequency).min(self.policy.sketch.max_count()) {
+                        self.policy.sketch.add(key);
+                    }
+                }
+                (key, ttl)
+            })
+            .collect();
+        self.set(entries)
+    }
+
+    /// Sets multiple cache entries with both a TTL and an idle timeout (TTI).
+    ///
+    /// An entry expires once its TTL elapses, or once it hasn't been accessed
+    /// for `tti` nanoseconds, whichever comes first. A TTI of 0 disables idle
+    /// expiration. Entries with TTL of -1 are removed instead of added.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl, tti) tuples where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were evicted to make room for new entries
+    pub fn set_with_tti(&mut self, entries: Vec<(u64, i64, i64)>) -> Vec<u64> {
+        let mut evicted = Vec::new();
+
+        for (key, ttl, tti) in entries {
+            match ttl {
+                -1 => self.remove_internal(key),
+                _ => {
+                    if let Insert::Rejected = self.insert_entry(key, ttl.unsigned_abs()) {
+                        evicted.push(key);
+                    } else if let Some(entry) = self.entries.get_mut(&key) {
+                        entry.tti = tti.unsigned_abs();
+                        entry.last_access = self.wheel.clock.now_ns();
+                        self.wheel.schedule(key, entry);
+                    }
+                }
+            }
+        }
+
+        let evicted = self.evict_batch(evicted);
+        if let Err(e) = self.check_size("set_with_tti") {
+            log::error!("{}", e);
+        }
+        evicted
+    }
+
+    /// Sets multiple cache entries tagged with a group, in a batch operation.
+    ///
+    /// Behaves like `set`, additionally tagging every inserted or updated key
+    /// with its group so the whole group can later be dropped with
+    /// `invalidate_group`. Updating a key with a different group moves it.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (key, ttl, group) tuples where ttl=-1 means remove
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were evicted to make room for new entries
+    pub fn set_grouped(&mut self, entries: Vec<(u64, i64, u32)>) -> Vec<u64> {
+        let mut evicted = Vec::new();
+
+        for (key, ttl, group) in entries {
+            match ttl {
+                -1 => self.remove_internal(key),
+                _ => {
+                    if let Insert::Rejected = self.insert_entry(key, ttl.unsigned_abs()) {
+                        evicted.push(key);
+                    } else {
+                        self.track_group(key, group);
+                    }
+                }
+            }
+        }
+
+        let evicted = self.evict_batch(evicted);
+        if let Err(e) = self.check_size("set_grouped") {
+            log::error!("{}", e);
+        }
+        evicted
+    }
+
+    /// Like `set`, but takes raw Python hashes and applies `spread` internally.
+    ///
+    /// Saves a separate `spread` call per key across the FFI boundary. The
+    /// returned evicted keys are spread hashes, as with `set`.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Vector of (python_hash, ttl) pairs where ttl=-1 means remove
+    pub fn set_hashed(&mut self, entries: Vec<(i64, i64)>) -> Vec<u64> {
+        self.set(
+            entries
+                .into_iter()
+                .map(|(hash, ttl)| (spread(hash), ttl))
+                .collect(),
+        )
+    }
+
+    /// Moves a live entry into another cache, keeping its metadata.
+    ///
+    /// For two-tier setups, where promoting a key with `remove` and `set`
+    /// would lose its history. The entry keeps its remaining TTL, measured
+    /// against `dest`'s clock, its idle timeout, group and dirty flag, and
+    /// `dest`'s sketch is raised to the source's frequency estimate. The key then goes
+    /// through `dest`'s normal admission; keys evicted from `dest` to make
+    /// room, possibly the transferred key itself, are kept until drained with
+    /// `take_evicted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    /// * `dest` - The cache to move the entry into, must not be this cache
+    ///
+    /// # Returns
+    ///
+    /// false if the key is missing or expired here, or already in `dest`
+    pub fn transfer_to(&mut self, key: u64, dest: &mut TlfuCore) -> bool {
+        let now = self.wheel.clock.now_ns();
+        let Some(entry) = self.entries.get(&key) else {
+            return false;
+        };
+        if entry.is_expired(now) || dest.entries.contains_key(&key) {
+            return false;
+        }
+        let ttl = match entry.expire {
+            0 => 0,
+            expire => expire - now,
+        };
+        let (tti, idle, group, dirty) = (
+            entry.tti,
+            now.saturating_sub(entry.last_access),
+            entry.group,
+            entry.dirty,
+        );
+        let frequency = self.policy.sketch.estimate(key);
+        self.remove_internal(key);
+
+        for _ in dest.policy.sketch.estimate(key)..frequency {
+            dest.policy.sketch.add(key);
+        }
+        let evicted = dest.set_entry(key, ttl);
+        dest.pending_evicted.extend(evicted);
+        let dest_now = dest.wheel.clock.now_ns();
+        if let Some(entry) = dest.entries.get_mut(&key) {
+            entry.dirty = dirty;
+            if tti > 0 {
+                entry.tti = tti;
+                entry.last_access = dest_now.saturating_sub(idle);
+                dest.wheel.schedule(key, entry);
+            }
+            if let Some(group) = group {
+                dest.track_group(key, group);
+            }
+        }
+        log::debug!("Transferred key {} with ttl {}", key, ttl);
+        true
+    }
+
+    /// Records an access on hit, or inserts the key on miss.
+    ///
+    /// A resident, unexpired key counts as a hit and records a normal access.
+    /// Otherwise the key is inserted (or its expired entry renewed) with the
+    /// given TTL. Keys evicted by the insertion are kept until drained with
+    /// `take_evicted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key
+    /// * `ttl` - Time-to-live in nanoseconds applied on miss, 0 means no expiration
+    ///
+    /// # Returns
+    ///
+    /// `true` on hit, `false` if the key was inserted
+    pub fn access_or_insert(&mut self, key: u64, ttl: i64) -> bool {
+        let now = self.wheel.clock.now_ns();
+        if self
+            .entries
+            .get(&key)
+            .is_some_and(|entry| !entry.is_expired(now))
+        {
+            self.access_entry(key);
+            return true;
+        }
+
+        let evicted = self.set_entry(key, ttl.unsigned_abs());
+        self.pending_evicted.extend(evicted);
+        false
+    }
+
+    /// Replays a key stream through the policy and returns the hit rate.
+    ///
+    /// Each key goes through `access_or_insert` without a TTL, so a cache of
+    /// the configured size can be shadow-tested against real traffic before
+    /// deploying it. The cache keeps the resulting state; evictions caused by
+    /// the replay aren't queued for `take_evicted`.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - The key stream to replay
+    ///
+    /// # Returns
+    ///
+    /// Fraction of keys that were hits, 0.0 for an empty stream
+    pub fn simulate(&mut self, keys: Vec<u64>) -> f64 {
+        if keys.is_empty() {
+            return 0.0;
+        }
+        let pending = self.pending_evicted.len();
+        let total = keys.len();
+        let hits = keys
+            .into_iter()
+            .filter(|&key| self.access_or_insert(key, 0))
+            .count();
+        self.pending_evicted.truncate(pending);
+        hits as f64 / total as f64
+    }
+
+    /// Removes up to `count` of the coldest entries at the current capacity.
+    ///
+    /// Entries are ranked by their sketch estimate, lowest first, with ties
+    /// broken by eviction order (probation, protected, then window, least
+    /// recently used first). This is a one-shot trim, capacity is unchanged.
+    /// Removed entries count as evicted for `set_flush_on_evict`.
+    ///
+    /// # Arguments
+    ///
+    /// * `count` - Maximum number of entries to remove
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were removed, coldest first
+    pub fn evict_coldest(&mut self, count: usize) -> Vec<u64> {
+        let mut order = self.policy.eviction_order();
+        // stable sort keeps eviction order among equal estimates
+        order.sort_by_key(|&key| self.policy.sketch.estimate(key));
+        order.truncate(count);
+
+        for &key in &order {
+            self.queue_flush(key);
+            self.remove_internal(key);
+        }
+        if !order.is_empty() {
+            log::debug!("Evict coldest: {} entries removed", order.len());
+        }
+
+        order
+    }
+
+    /// Evicts down to the hard capacity, ignoring the overflow allowance.
+    ///
+    /// # Returns
+    ///
+    /// Vector of keys that were evicted
+    pub fn trim(&mut self) -> Vec<u64> {
+        let evicted = self.policy.trim(&mut self.entries).unwrap_or_else(|e| {
+            log::error!("trim: {}", e);
+            Vec::new()
+        });
+        self.discard_evicted(&evicted);
+        evicted
+    }
+
+    /// Inserts keys straight into probation, bypassing the admission contest.
+    ///
+    /// Meant for restoring a working set into a cold cache, where `set`
+    /// would reject most keys because the sketch knows nothing about them.
+    /// New keys still prime the sketch as a `set` would, unless
+    /// `set_weight_writes` turned that off. They only fill the free room and
+    /// nothing resident is evicted; ordering matters, as later keys win when
+    /// there are more new keys than room. TTLs are handled like with `set`:
+    /// they're capped by `set_max_ttl`, resident keys get theirs updated, and
+    /// with `set_promote_long_ttl_threshold` new keys below the threshold go
+    /// to the window instead of probation, which spills into probation once
+    /// the window is full.
+    ///
+    /// # Arguments
+    ///
+    /// * `keys` - Vector of (key, ttl) pairs where ttl=-1 means remove
+    pub fn warm_fill(&mut self, keys: Vec<(u64, i64)>) {
+        // a later occurrence of a key takes its place
+        let mut seen = HashSet::with_capacity(keys.len());
+        let mut latest: Vec<(u64, i64)> = keys
+            .into_iter()
+            .rev()
+            .filter(|&(key, _)| seen.insert(key))
+            .collect();
+        latest.reverse();
+
+        let mut fresh = Vec::new();
+        for (key, ttl) in latest {
+            if ttl == -1 {
+                self.remove_internal(key);
+                continue;
+            }
+            let ttl = ttl.unsigned_abs();
+            if self.entries.contains_key(&key) {
+                self.insert_entry(key, ttl);
+                continue;
+            }
+            if self.policy.weight_writes() && self.counts_write(key) {
+                self.policy.sketch.add(key);
+            }
+            fresh.push((key, self.clamp_ttl(key, ttl)));
+        }
+
+        let room = self.policy.capacity().saturating_sub(self.entries.len());
+        let skipped = fresh.len().saturating_sub(room);
+        for &(key, ttl) in &fresh[skipped..] {
+            let mut entry = Entry::new();
+            entry.expire = self.wheel.clock.expire_ns(ttl);
+            entry.original_ttl = ttl;
+            entry.inserted_at = self.wheel.clock.now_ns();
+            self.wheel.schedule_new(key, &mut entry);
+            // with a long TTL threshold, short lived keys go to the window as with `set`
+            let list = if self.promote_long_ttl_threshold == 0 || self.skips_window(ttl) {
+                2
+            } else {
+                1
+            };
+            self.policy.restore(key, list, &mut entry);
+            self.entries.insert(key, entry);
+        }
+        // the window may be over its capacity now, nothing is over the total
+        let evicted = self
+            .policy
+            .rebalance(&mut self.entries)
+            .unwrap_or_else(|e| {
+                log::error!("warm_fill: {}", e);
+                Vec::new()
+            });
+        self.discard_evicted(&evicted);
+
+        log::debug!(
+            "Warm fill: {} keys inserted, {} skipped for lack of room",
+            fresh.len() - skipped,
+            skipped
+        );
+    }
+
+    /// Replaces the cache contents with state from `export_state`.
+    ///
+    /// Entries are placed back into their window, probation or protected
+    /// segment in the same recency order. If the state holds more entries
+    /// than this cache's capacity, the coldest ones are dropped; segments
+    /// that exceed their own capacity spill over the way they normally would.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - `(key, policy_list_id, remaining_ttl)` tuples, coldest first
+    ///
+    /// # Returns
+    ///
+    /// Keys that didn't fit and were dropped
+    pub fn import_state(&mut self, state: Vec<(u64, u8, u64)>) -> Vec<u64> {
+        self.clear();
+
+        let overflow = state.len().saturating_sub(self.policy.capacity());
+        let mut dropped: Vec<u64> = state[..overflow].iter().map(|&(key, _, _)| key).collect();
+        for &(key, policy_list_id, ttl) in &state[overflow..] {
+            if self.entries.contains_key(&key) {
+                continue;
+            }
+            let mut entry = Entry::new();
+            entry.expire = self.wheel.clock.expire_ns(ttl);
+            entry.original_ttl = ttl;
+            entry.inserted_at = self.wheel.clock.now_ns();
+            self.wheel.schedule_new(key, &mut entry);
+            self.policy.restore(key, policy_list_id, &mut entry);
+            self.entries.insert(key, entry);
+        }
+
+        let evicted = self
+            .policy
+            .rebalance(&mut self.entries)
+            .unwrap_or_else(|e| {
+                log::error!("import_state: {}", e);
+                Vec::new()
+            });
+        self.discard_evicted(&evicted);
+        dropped.extend(evicted);
+
+        log::debug!(
+            "Import: {} entries restored, {} dropped",
+            self.entries.len(),
+            dropped.len()
+        );
+        dropped
+    }
+
+    /// Sets multiple entries with panic safety for Python FFI.
+    ///
+    /// Unlike `set`, raises a size accounting mismatch found by
+    /// `set_debug_invariants` as a `RuntimeError` in strict mode.
+    pub fn set_with_error(&mut self, entries: Vec<(u64, i64)>) -> PyResult<Vec<u64>> {
+        use std::panic::AssertUnwindSafe;
+        let evicted = catch_panic(AssertUnwindSafe(|| self.set(entries)), "set")?;
+        if self.strict {
+            self.check_size("set")?;
+        }
+        Ok(evicted)
+    }
+
+    /// Advances to `now` and passes the expired keys to `callback` after
+    /// releasing the borrow on the cache.
+    fn advance_notify_to(
+        slf: &Bound<'_, Self>,
+        now: u64,
+        callback: &Bound<'_, PyAny>,
+    ) -> PyResult<()> {
+        let expired = Self::advance_to(slf, now)?;
+        if !expired.is_empty() {
+            callback.call1((expired,))?;
+        }
+        Ok(())
+    }
+
+    /// Advances the timer wheel to `now` and removes expired entries.
+    fn advance_to(slf: &Bound<'_, Self>, now: u64) -> PyResult<Vec<u64>> {
+        Self::expire_intercepted(
+            slf,
+            |cache| Ok((now, cache.wheel.advance(now, &mut cache.entries))),
+            |cache, expired| {
+                let expired = cache.remove_expired(expired)?;
+                cache.check_size("advance")?;
+                Ok(expired)
+            },
+        )
+    }
+
+    /// Runs an expiry pass with the expiry interceptor consulted in between.
+    ///
+    /// `reap` finds the expired keys and the time they expired at. The cache
+    /// borrow is released while the interceptor runs, so it may call back
+    /// into the cache; `finish` then drops the keys it didn't renew, and the
+    /// dirty ones among them go to the flush callback.
+    fn expire_intercepted(
+        slf: &Bound<'_, Self>,
+        reap: impl FnOnce(&mut Self) -> PyResult<(u64, Vec<u64>)>,
+        finish: impl FnOnce(&mut Self, Vec<u64>) -> PyResult<Vec<u64>>,
+    ) -> PyResult<Vec<u64>> {
+        let (now, mut expired, interceptor) = {
+            let mut cache = slf.borrow_mut();
+            let (now, expired) = reap(&mut cache)?;
+            let interceptor = cache
+                .expiry_interceptor
+                .as_ref()
+                .filter(|_| !expired.is_empty())
+                .map(|interceptor| interceptor.clone_ref(slf.py()));
+            (now, expired, interceptor)
+        };
+
+        let renewals = interceptor
+            .map(|interceptor| Self::ask_interceptor(interceptor.bind(slf.py()), &expired));
+        Self::flushing(slf, |cache| {
+            if let Some(renewals) = renewals {
+                cache.renew_intercepted(now, &mut expired, &renewals);
+            }
+            finish(cache, expired)
+        })
+    }
+
+    /// Drops keys the wheel expired from entries and the policy.
+    ///
+    /// In strict mode the first policy error is returned once every expired
+    /// key has been removed.
+    fn remove_expired(&mut self, expired: Vec<u64>) -> PyResult<Vec<u64>> {
+        let expired_count = expired.len();
+        let mut first_error = None;
+
+        for &key in &expired {
+            self.queue_flush(key);
+            if let Some(mut entry) = self.entries.remove(&key) {
+                let result = self.policy.remove(&mut entry);
+                if let Err(e) = self.check_policy("advance", key, result) {
+                    first_error.get_or_insert(e);
+                }
+                self.untrack_group(key, entry.group);
+                self.track_unread_expiry(key, entry.accessed);
+                log::trace!("Expired key {}", key);
+            }
+        }
+
+        if expired_count > 0 {
+            log::debug!("Advance: {} entries expired", expired_count);
+        }
+
+        match first_error {
+            Some(e) => Err(e),
+            None => Ok(expired),
+        }
+    }
+
+    /// With `set_debug_invariants` on, reports drift between the policy's
+    /// size and its segment lengths after `op`.
+    ///
+    /// Logs the mismatch, or in strict mode returns it as a corruption error.
+    fn check_size(&self, op: &str) -> Result<(), CacheError> {
+        if !self.debug_invariants {
+            return Ok(());
+        }
+        let Some((size, listed)) = self.policy.size_drift() else {
+            return Ok(());
+        };
+        let message = format!(
+            "{}: size {} doesn't match segment lengths totalling {}",
+            op, size, listed
+        );
+        if self.strict {
+            return Err(CacheError::corruption(message));
+        }
+        log::error!("{}", message);
+        Ok(())
+    }
+
+    /// Logs a policy error, or in strict mode turns it into a `RuntimeError`.
+    fn check_policy(&self, op: &str, key: u64, result: anyhow::Result<()>) -> PyResult<()> {
+        let Err(e) = result else {
+            return Ok(());
+        };
+        if self.strict {
+            return Err(CacheError::policy(key, format!("{}: {}", op, e)).into());
+        }
+        log::error!("{}(key={}): {}", op, key, e);
+        Ok(())
+    }
+
+    /// Insertion time from which keys are spared by eviction, see
+    /// `set_insertion_grace`; 0 when there's no grace period.
+    fn graced_from(&self) -> u64 {
+        if self.insertion_grace > 0 {
+            let now = self.wheel.clock.now_ns();
+            now.saturating_sub(self.insertion_grace).max(1)
+        } else {
+            0
+        }
+    }
+
+    /// Whether a new entry with `ttl` skips the window, see
+    /// `set_promote_long_ttl_threshold`.
+    fn skips_window(&self, ttl: u64) -> bool {
+        let threshold = self.promote_long_ttl_threshold;
+        threshold > 0 && (ttl == 0 || ttl > threshold)
+    }
+
+    /// Caps `ttl` at `max_ttl`, if one is set.
+    fn clamp_ttl(&self, key: u64, ttl: u64) -> u64 {
+        if self.max_ttl > 0 && ttl > self.max_ttl {
+            log::debug!("Clamped TTL {} of key {} to {}", ttl, key, self.max_ttl);
+            return self.max_ttl;
+        }
+        ttl
+    }
+
+    /// Whether a set of the new `key` counts towards its frequency, see
+    /// `set_expiry_penalty`.
+    fn counts_write(&self, key: u64) -> bool {
+        self.expiry_penalty == 0
+            || self
+                .expired_unread
+                .get(&key)
+                .is_none_or(|&unread| unread < self.expiry_penalty)
+    }
+
+    /// Creates or updates an entry without running eviction.
+    ///
+    /// After `Insert::Inserted` the policy may be over capacity. A rejected
+    /// key is dropped again right away.
+    fn insert_entry(&mut self, key: u64, ttl: u64) -> Insert {
+        let ttl = self.clamp_ttl(key, ttl);
+
+        // Update existing entry
+        if let Some(entry) = self.entries.get_mut(&key) {
+            entry.expire = self.wheel.clock.expire_ns(ttl);
+            entry.original_ttl = ttl;
+            entry.inserted_at = self.wheel.clock.now_ns();
+            entry.accessed = false;
+            self.wheel.schedule(key, entry);
+            return Insert::Updated;
+        }
+
+        // Create new entry
+        let mut entry = Entry::new();
+        entry.expire = self.wheel.clock.expire_ns(ttl);
+        entry.original_ttl = ttl;
+        entry.inserted_at = self.wheel.clock.now_ns();
+        self.wheel.schedule_new(key, &mut entry);
+        self.entries.insert(key, entry);
+
+        let skip_window = self.skips_window(ttl);
+        let count_write = self.counts_write(key);
+        match self
+            .policy
+            .insert_new(key, &mut self.entries, skip_window, count_write)
+        {
+            Ok(false) => {
+                if let Some(mut entry) = self.entries.remove(&key) {
+                    self.wheel.deschedule(&mut entry);
+                }
+                log::trace!("Rejected key {} below minimum admission frequency", key);
+                Insert::Rejected
+            }
+            Ok(true) => Insert::Inserted,
+            Err(e) => {
+                log::error!("set(key={}): {}", key, e);
+                Insert::Inserted
+            }
+        }
+    }
+
+    /// Evicts down to capacity and drops the evicted entries.
+    fn evict_to_capacity(&mut self) -> Vec<u64> {
+        let graced_from = self.graced_from();
+        let evicted = self
+            .policy
+            .evict_graced(&mut self.entries, graced_from)
+            .unwrap_or_else(|e| {
+                log::error!("evict: {}", e);
+                Vec::new()
+            });
+        self.discard_evicted(&evicted);
+        evicted
+    }
+
+    /// Verifies the policy lists and the timer wheel against the entries.
+    ///
+    /// Covers `TinyLfu::consistency_issues` and `TimerWheel::validate_wheel`.
+    ///
+    /// # Returns
+    ///
+    /// `Err` with one message per violated invariant
+    pub fn consistency_check(&self) -> Result<(), Vec<String>> {
+        let mut issues = self.policy.consistency_issues(&self.entries);
+        issues.extend(
+            self.validate_wheel()
+                .into_iter()
+                .map(|(key, issue)| format!("key {}: {}", key, issue)),
+        );
+        if issues.is_empty() {
+            Ok(())
+        } else {
+            Err(issues)
+        }
+    }
+
+    /// Sets a batch all or nothing: only if it fits without eviction.
+    ///
+    /// Since `set` defers eviction to the end of the batch, only the size
+    /// after the whole batch counts, so removals by ttl=-1 make room for new
+    /// keys. Updates of resident keys take no room.
+    ///
+    /// # Returns
+    ///
+    /// What `set` returns, or `Err` with the number of entries the batch
+    /// exceeds the free slots by, in which case nothing was applied
+    pub fn try_set(&mut self, entries: Vec<(u64, i64)>) -> Result<Vec<u64>, usize> {
+        let mut last_ttl = HashMap::with_capacity(entries.len());
+        for &(key, ttl) in &entries {
+            last_ttl.insert(key, ttl);
+        }
+        let (mut added, mut removed) = (0, 0);
+        for (key, ttl) in last_ttl {
+            match (self.entries.contains_key(&key), ttl == -1) {
+                (false, false) => added += 1,
+                (true, true) => removed += 1,
+                _ => {}
+            }
+        }
+        let overflow = (self.entries.len() + added)
+            .saturating_sub(removed)
+            .saturating_sub(self.policy.capacity());
+        if overflow > 0 {
+            return Err(overflow);
+        }
+        Ok(self.set(entries))
+    }
+
+    /// Runs the `health_details` checks as `(name, weight, passed)`.
+    fn health_checks(&self) -> [(&'static str, u8, bool); 4] {
+        let with_deadline = self
+            .entries
+            .values()
+            .filter(|entry| entry.deadline() != 0)
+            .count();
+        let eviction_limit = self.policy.capacity() as f64 * HEALTH_EVICTION_PRESSURE;
+        [
+            (
+                "size_matches_segments",
+                35,
+                self.policy.size_drift().is_none(),
+            ),
+            (
+                "wheel_matches_entries",
+                35,
+                self.wheel.scheduled_count() == with_deadline,
+            ),
+            (
+                "sketch_not_saturated",
+                15,
+                self.policy.sketch.saturation() <= HEALTH_SKETCH_SATURATION,
+            ),
+            (
+                "eviction_pressure_low",
+                15,
+                self.recent_eviction_rate(self.eviction_samples_cap) <= eviction_limit,
+            ),
+        ]
+    }
+
+    /// Appends a batch's eviction count to the ring buffer.
+    fn record_evictions(&mut self, count: usize) {
+        if self.eviction_samples.len() >= self.eviction_samples_cap {
+            self.eviction_samples.pop_front();
+        }
+        self.eviction_samples.push_back(count);
+    }
+
+    /// Drops entries the policy has already evicted.
+    fn discard_evicted(&mut self, evicted: &[u64]) {
+        for &key in evicted {
+            self.queue_flush(key);
+            if let Some(mut entry) = self.entries.remove(&key) {
+                self.wheel.deschedule(&mut entry);
+                self.untrack_group(key, entry.group);
+            }
+            log::debug!("Evicted key {}", key);
+        }
+    }
+
+    /// Runs the single eviction pass that ends a batch insert.
+    ///
+    /// `rejected` holds the keys turned away during the batch. A key rejected
+    /// early may be admitted by a later write, so only keys that are gone are
+    /// reported, and each of them once.
+    fn evict_batch(&mut self, mut rejected: Vec<u64>) -> Vec<u64> {
+        rejected.extend(self.evict_to_capacity());
+        let mut seen = HashSet::new();
+        rejected.retain(|key| !self.entries.contains_key(key) && seen.insert(*key));
+        self.record_evictions(rejected.len());
+        rejected
+    }
+
+    /// Queues a dirty entry's key for the flush callback before it is dropped.
+    fn queue_flush(&mut self, key: u64) {
+        if self.flush_on_evict.is_some() && self.entries.get(&key).is_some_and(|entry| entry.dirty)
+        {
+            self.pending_flush.push(key);
+        }
+    }
+
+    /// Runs `op`, then hands the dirty keys it dropped to the flush callback.
+    ///
+    /// The cache borrow is released before the callback runs, so it may call
+    /// back into the cache.
+    fn flushing<R>(slf: &Bound<'_, Self>, op: impl FnOnce(&mut Self) -> R) -> R {
+        let (result, pending, callback) = {
+            let mut cache = slf.borrow_mut();
+            let result = op(&mut cache);
+            let pending = std::mem::take(&mut cache.pending_flush);
+            let callback = cache
+                .flush_on_evict
+                .as_ref()
+                .filter(|_| !pending.is_empty())
+                .map(|callback| callback.clone_ref(slf.py()));
+            (result, pending, callback)
+        };
+
+        if let Some(callback) = callback {
+            for key in pending {
+                if let Err(e) = callback.call1(slf.py(), (key,)) {
+                    log::warn!("flush on evict(key={}): {}", key, e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Asks the expiry interceptor for a new TTL for each expired key.
+    fn ask_interceptor(interceptor: &Bound<'_, PyAny>, expired: &[u64]) -> HashMap<u64, u64> {
+        expired
+            .iter()
+            .filter_map(|&key| {
+                let ttl = interceptor
+                    .call1((key,))
+                    .and_then(|ttl| ttl.extract::<Option<i64>>())
+                    .map_err(|e| log::warn!("expiry interceptor(key={}): {}", key, e))
+                    .ok()
+                    .flatten()?;
+                u64::try_from(ttl).ok().map(|ttl| (key, ttl))
+            })
+            .collect()
+    }
+
+    /// Renews the entries the expiry interceptor kept and drops them from
+    /// `expired`, along with keys the interceptor rewrote or removed itself.
+    fn renew_intercepted(
+        &mut self,
+        now: u64,
+        expired: &mut Vec<u64>,
+        renewals: &HashMap<u64, u64>,
+    ) {
+        for (&key, &ttl) in renewals {
+            if let Some(entry) = self.entries.get_mut(&key) {
+                if ttl == 0 {
+                    entry.expire = 0;
+                    entry.tti = 0;
+                } else {
+                    entry.expire = now.saturating_add(ttl);
+                    entry.last_access = now;
+                }
+                self.wheel.schedule(key, entry);
+                log::trace!("Renewed key {}", key);
+            }
+        }
+        expired.retain(|key| {
+            !renewals.contains_key(key)
+                && self
+                    .entries
+                    .get(key)
+                    .is_some_and(|entry| entry.is_expired(now))
+        });
+    }
+
+    /// Whether an accessed key is hot and close enough to expiring to be
+    /// signaled to the refresh-ahead callback.
+    fn needs_refresh(&self, key: u64, now: u64) -> bool {
+        let Some(refresh) = &self.refresh_ahead else {
+            return false;
+        };
+        let Some(entry) = self.entries.get(&key) else {
+            return false;
+        };
+        if entry.expire == 0 || entry.expire <= now {
+            return false;
+        }
+        // expire moves on renew or extension while inserted_at stays, so
+        // measure against the TTL the entry was set with
+        let remaining = entry.expire - now;
+        (remaining as f64) < entry.original_ttl as f64 * refresh.ttl_fraction
+            && self.policy.sketch.estimate(key) >= refresh.freq_threshold
+    }
+
+    /// Calls the refresh-ahead callback for each key.
+    fn signal_refresh(&self, keys: &[u64]) {
+        let Some(refresh) = &self.refresh_ahead else {
+            return;
+        };
+        if keys.is_empty() {
+            return;
+        }
+        Python::attach(|py| {
+            for &key in keys {
+                if let Err(e) = refresh.callback.call1(py, (key,)) {
+                    log::warn!("refresh ahead(key={}): {}", key, e);
+                }
+            }
+        });
+    }
+
+    /// Tags a resident key with a group, moving it out of its previous group.
+    fn track_group(&mut self, key: u64, group: u32) {
+        let previous = match self.entries.get_mut(&key) {
+            Some(entry) if entry.group != Some(group) => entry.group.replace(group),
+            _ => return,
+        };
+        self.untrack_group(key, previous);
+        self.groups.entry(group).or_default().insert(key);
+    }
+
+    /// Removes expired keys found by a scan rather than the timer wheel.
+    fn drop_expired(&mut self, expired: &[u64]) {
+        for &key in expired {
+            if let Some(entry) = self.entries.get(&key) {
+                self.track_unread_expiry(key, entry.accessed);
+            }
+            self.queue_flush(key);
+            self.remove_internal(key);
+        }
+    }
+
+    /// Updates the unread expiry history used by `set_expiry_penalty`.
+    fn track_unread_expiry(&mut self, key: u64, accessed: bool) {
+        if self.expiry_penalty == 0 {
+            return;
+        }
+        if accessed {
+            self.expired_unread.remove(&key);
+        } else {
+            *self.expired_unread.entry(key).or_default() += 1;
+        }
+    }
+
+    /// Drops a key from its group index, pruning the group once empty.
+    fn untrack_group(&mut self, key: u64, group: Option<u32>) {
+        if let Some(group) = group
+            && let Some(keys) = self.groups.get_mut(&group)
+        {
+            keys.remove(&key);
+            if keys.is_empty() {
+                self.groups.remove(&group);
+            }
+        }
+    }
+}
+
+/// Batched iteration over a snapshot of a cache's keys.
+///
+/// Created by `TlfuCore::key_cursor`. The snapshot is independent of the
+/// cache, so it stays valid while the cache is modified.
+#[pyclass]
+pub struct KeyCursor {
+    keys: Vec<u64>,
+    position: usize,
+}
+
+#[pymethods]
+impl KeyCursor {
+    /// Returns the next batch of up to `n` keys, empty once exhausted.
+    ///
+    /// # Arguments
+    ///
+    /// * `n` - Maximum number of keys to return
+    pub fn next_batch(&mut self, n: usize) -> Vec<u64> {
+        let end = self.position.saturating_add(n).min(self.keys.len());
+        let batch = self.keys[self.position..end].to_vec();
+        self.position = end;
+        batch
+    }
+
+    /// Returns the number of keys not yet returned.
+    #[must_use]
+    pub fn remaining(&self) -> usize {
+        self.keys.len() - self.position
+    }
+}
+
+/// Supplemental hash function for Python hash values.
+///
+/// Python's hash function returns `i64` which can be negative or weakly distributed.
+/// This function applies the MurmurHash3 finalizer to improve distribution and prevent hash DoS.
+///
+/// # Arguments
+///
+/// * `h` - Python's hash value (an i64)
+///
+/// # Returns
+///
+/// A well-distributed u64 hash value
+///
+/// # Examples
+///
+/// ```ignore
+/// let python_hash = 12345i64;
+/// let improved_hash = spread(python_hash);
+/// ```
+#[pyfunction]
+#[must_use]
+pub fn spread(h: i64) -> u64 {
+    // Convert i64 to u64 preserving bit pattern
+    let mut z = u64::from_ne_bytes(h.to_ne_bytes());
+
+    // Apply MurmurHash3 finalizer for better distribution
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^= z >> 31;
+
+    z
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+    use std::time::Duration;
+
+    /// Runs `f` with the cache moved into a Python object, as the expiry
+    /// methods take one, and moves it back afterwards.
+    fn bound<R>(cache: &mut TlfuCore, f: impl FnOnce(&Bound<'_, TlfuCore>) -> R) -> R {
+        Python::initialize();
+        Python::attach(|py| {
+            let obj = Bound::new(py, std::mem::replace(cache, TlfuCore::new(1))).unwrap();
+            let result = f(&obj);
+            *cache = std::mem::replace(&mut *obj.borrow_mut(), TlfuCore::new(1));
+            result
+        })
+    }
+
+    #[test]
+    fn test_set_operations() {
+        let mut cache = TlfuCore::new(1000);
+
+        // Add entries
+        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
+        let mut keys: Vec<_> = cache.entries.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 2, 3]);
+
+        // Remove entry 3, add entry 4, re-add entry 3
+        cache.set(vec![(3, -1), (4, 0), (3, 0)]);
+        keys = cache.entries.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 2, 3, 4]);
+
+        // Remove entry 3, keep entry 4
+        cache.set(vec![(3, -1), (4, 0)]);
+        keys = cache.entries.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 2, 4]);
+    }
+
+    #[test]
+    fn test_remove_operation() {
+        let mut cache = TlfuCore::new(1000);
+        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
+        cache.remove(2).unwrap();
+
+        let mut keys: Vec<_> = cache.entries.keys().copied().collect();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 3]);
+    }
+
+    #[test]
+    fn test_bounded_cache_size() {
+        for size in [1, 2, 3] {
+            let mut cache = TlfuCore::new(size);
+            cache.set(vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
+            assert_eq!(cache.len(), size);
+
+            cache.access(vec![1]).unwrap();
+            cache.set(vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
+            assert_eq!(cache.len(), size);
+        }
+    }
+
+    #[test]
+    fn test_spread_hash_function() {
+        let mut rng = rand::rng();
+
+        for _ in 0..100_000 {
+            let k = rng.random_range(-i64::MAX..i64::MAX);
+            let _hashed = spread(k);
+            // Just verify it doesn't panic
+        }
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
+        assert!(cache.len() > 0);
+
+        cache.clear();
+        assert_eq!(cache.len(), 0);
+    }
+
+    #[test]
+    fn test_keys() {
+        let mut cache = TlfuCore::new(100);
+        let entries = vec![(1, 0), (2, 0), (3, 0)];
+        cache.set(entries.clone());
+
+        let mut keys = cache.keys();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 2, 3]);
+    }
+
+    fn scan_hit_rate(cache: &mut TlfuCore, keys: &[u64]) -> f64 {
+        let mut hits = 0;
+        for &key in keys {
+            if cache.entries.contains_key(&key) {
+                hits += 1;
+                cache.access(vec![key]).unwrap();
+            } else {
+                cache.set(vec![(key, 0)]);
+            }
+        }
+        hits as f64 / keys.len() as f64
+    }
+
+    #[test]
+    fn test_window_policy_scan_workload() {
+        // skewed hot keys interleaved with a long scan of one-hit wonders
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut keys = Vec::new();
+        for scan in 0..50_000u64 {
+            let r: f64 = rng.random();
+            keys.push((r * r * r * 2000.0) as u64);
+            keys.push(1_000_000 + scan);
+        }
+
+        let mut lru = TlfuCore::with_window_policy(200, WindowPolicy::Lru);
+        let mut fifo = TlfuCore::with_window_policy(200, WindowPolicy::Fifo);
+        let lru_hr = scan_hit_rate(&mut lru, &keys);
+        let fifo_hr = scan_hit_rate(&mut fifo, &keys);
+        // second chance keeps the hot set at least as well as LRU does
+        assert!(lru_hr > 0.15);
+        assert!(fifo_hr >= lru_hr);
+    }
+
+    #[test]
+    fn test_large_window_scan_hit_rate() {
+        // hot keys are read right after they're written, then come back
+        // after a scan longer than the window but shorter than two passes
+        // over it
+        let mut keys = Vec::new();
+        let mut scan = 1_000_000u64;
+        for _ in 0..500 {
+            for hot in 0..8u64 {
+                keys.extend([hot, hot]);
+                keys.extend(scan..scan + 10);
+                scan += 10;
+            }
+        }
+
+        // main only holds two keys, so the window policy decides what survives
+        let hit_rate = |policy| {
+            let mut cache = TlfuCore::with_window_policy(66, policy);
+            cache.set_window_capacity(64).unwrap();
+            cache.freeze_window(true);
+            scan_hit_rate(&mut cache, &keys)
+        };
+        let lru_hr = hit_rate(WindowPolicy::Lru);
+        let fifo_hr = hit_rate(WindowPolicy::Fifo);
+        // LRU flushes the hot keys with the scan, while their reference bit
+        // gets them a second pass through the FIFO window
+        assert!(fifo_hr > lru_hr + 0.03);
+    }
+
+    #[test]
+    fn test_preview_evictions() {
+        let mut cache = TlfuCore::new(10);
+        cache.set((0..10).map(|k| (k, 0)).collect());
+        cache.access((0..10).collect()).unwrap();
+        let mut keys_before = cache.keys();
+        keys_before.sort_unstable();
+
+        let new_keys: Vec<u64> = (100..105).collect();
+        let mut preview = cache.preview_evictions(new_keys.clone());
+        assert_eq!(preview.len(), 5);
+
+        // preview must not mutate the cache
+        let mut keys_after = cache.keys();
+        keys_after.sort_unstable();
+        assert_eq!(keys_before, keys_after);
+        assert_eq!(cache.policy.len(), 10);
+
+        let mut evicted = cache.set(new_keys.into_iter().map(|k| (k, 0)).collect());
+        preview.sort_unstable();
+        evicted.sort_unstable();
+        assert_eq!(preview, evicted);
+    }
+
+    #[test]
+    fn test_set_hashdos_threshold() {
+        let mut cache = TlfuCore::new(100);
+        assert!(cache.set_hashdos_threshold(0).is_err());
+        assert!(cache.set_hashdos_threshold(1).is_ok());
+        assert!(cache.set_hashdos_threshold(20).is_ok());
+    }
+
+    fn grouped_keys(cache: &TlfuCore, group: u32) -> Vec<u64> {
+        let mut keys: Vec<u64> = cache
+            .groups
+            .get(&group)
+            .map(|keys| keys.iter().copied().collect())
+            .unwrap_or_default();
+        keys.sort_unstable();
+        keys
+    }
+
+    #[test]
+    fn test_invalidate_group() {
+        let mut cache = TlfuCore::new(100);
+        cache.set_grouped(vec![(1, 0, 7), (2, 0, 7), (3, 0, 8)]);
+        cache.set(vec![(4, 0)]);
+
+        // moving a key to another group
+        cache.set_grouped(vec![(2, 0, 8)]);
+        assert_eq!(grouped_keys(&cache, 7), vec![1]);
+        assert_eq!(grouped_keys(&cache, 8), vec![2, 3]);
+
+        let mut removed = cache.invalidate_group(8);
+        removed.sort_unstable();
+        assert_eq!(removed, vec![2, 3]);
+        let mut keys = cache.keys();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 4]);
+        assert!(!cache.groups.contains_key(&8));
+        assert!(cache.invalidate_group(8).is_empty());
+
+        cache.remove(1).unwrap();
+        assert!(cache.groups.is_empty());
+    }
+
+    #[test]
+    fn test_group_index_eviction_cleanup() {
+        let mut cache = TlfuCore::new(5);
+        let evicted = cache.set_grouped((0..50).map(|k| (k, 0, 1)).collect());
+        assert!(!evicted.is_empty());
+
+        let mut resident = cache.keys();
+        resident.sort_unstable();
+        assert_eq!(grouped_keys(&cache, 1), resident);
+        for key in evicted {
+            assert!(!cache.groups[&1].contains(&key));
+        }
+    }
+
+    #[test]
+    fn test_group_index_expiration_cleanup() {
+        let mut cache = TlfuCore::new(100);
+        let ttl = Duration::from_secs(1).as_nanos() as i64;
+        cache.set_grouped(vec![(1, ttl, 3), (2, ttl, 3), (3, 0, 3)]);
+
+        let now = cache.wheel.clock.now_ns();
+        let mut expired = bound(&mut cache, |c| {
+            TlfuCore::advance_to(c, now + Duration::from_secs(5).as_nanos() as u64)
+        })
+        .unwrap();
+        expired.sort_unstable();
+        assert_eq!(expired, vec![1, 2]);
+        assert_eq!(grouped_keys(&cache, 3), vec![3]);
+    }
+
+    #[test]
+    fn test_tti_idle_reap() {
+        let mut cache = TlfuCore::new(100);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let tti = 2 * second as i64;
+        cache.set_with_tti(vec![(1, 0, tti), (2, 0, tti), (3, 60 * tti, 0)]);
+
+        // keep key 1 alive by accessing it every second
+        let start = cache.wheel.clock.now_ns();
+        let mut expired = Vec::new();
+        for step in 1..=6 {
+            let now = start + step * second;
+            cache.entries.get_mut(&1).unwrap().last_access = now;
+            expired.extend(bound(&mut cache, |c| TlfuCore::advance_to(c, now)).unwrap());
+        }
+        assert_eq!(expired, vec![2]);
+        let mut keys = cache.keys();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![1, 3]);
+
+        // once accesses stop, key 1 idles out
+        let expired = bound(&mut cache, |c| TlfuCore::advance_to(c, start + 10 * second)).unwrap();
+        assert_eq!(expired, vec![1]);
+    }
+
+    #[test]
+    fn test_tti_bounded_by_ttl() {
+        let mut cache = TlfuCore::new(100);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        cache.set_with_tti(vec![(1, 2 * second as i64, 60 * second as i64)]);
+
+        let entry = &cache.entries[&1];
+        assert_eq!(entry.deadline(), entry.expire);
+
+        let start = cache.wheel.clock.now_ns();
+        cache.entries.get_mut(&1).unwrap().last_access = start + second;
+        let expired = bound(&mut cache, |c| TlfuCore::advance_to(c, start + 4 * second)).unwrap();
+        assert_eq!(expired, vec![1]);
+    }
+
+    #[test]
+    fn test_immortal_count() {
+        let mut cache = TlfuCore::new(100);
+        assert_eq!(cache.immortal_count(), 0);
+
+        let ttl = Duration::from_secs(60).as_nanos() as i64;
+        cache.set(vec![(1, 0), (2, 0), (3, ttl)]);
+        cache.set_with_tti(vec![(4, 0, ttl)]);
+        assert_eq!(cache.immortal_count(), 2);
+
+        cache.set(vec![(1, ttl)]);
+        assert_eq!(cache.immortal_count(), 1);
+    }
+
+    #[test]
+    fn test_access_extend() {
+        let mut cache = TlfuCore::new(100);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let hour = 3600 * second;
+        cache.set(vec![(1, 10 * second as i64), (3, 10 * second as i64)]);
+        // key 3 expired but not reaped yet
+        cache.entries.get_mut(&3).unwrap().expire = 1;
+        let expire_3 = cache.entries[&3].expire;
+
+        let before = cache.wheel.clock.now_ns();
+        cache.access_extend(vec![(1, hour as i64), (2, hour as i64), (3, hour as i64)]);
+
+        let expire_1 = cache.entries[&1].expire;
+        assert!(expire_1 >= before + hour);
+        assert!(expire_1 <= cache.wheel.clock.now_ns() + hour);
+        assert_eq!(cache.entries[&1].wheel_index.0, 1);
+        assert_eq!(cache.entries[&3].expire, expire_3);
+        assert!(!cache.entries.contains_key(&2));
+        assert!(cache.policy.sketch.estimate(1) > 1);
+    }
+
+    #[test]
+    fn test_renew() {
+        let mut cache = TlfuCore::new(100);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        cache.set(vec![(1, 10 * second as i64), (2, 0), (3, second as i64)]);
+        cache.entries.get_mut(&1).unwrap().expire = cache.wheel.clock.now_ns() + second;
+        // key 3 expired but not reaped yet
+        cache.entries.get_mut(&3).unwrap().expire = 1;
+        let order = cache.keys_ordered();
+        let frequency = cache.policy.sketch.estimate(1);
+
+        let before = cache.wheel.clock.now_ns();
+        assert!(cache.renew(1));
+        assert!(!cache.renew(2));
+        assert!(!cache.renew(3));
+        assert!(!cache.renew(4));
+
+        let expire = cache.entries[&1].expire;
+        assert!(expire >= before + 10 * second);
+        assert!(expire <= cache.wheel.clock.now_ns() + 10 * second);
+        assert_eq!(cache.entries[&2].expire, 0);
+        assert_eq!(cache.entries[&3].expire, 1);
+        assert_eq!(cache.keys_ordered(), order);
+        assert_eq!(cache.policy.sketch.estimate(1), frequency);
+        assert!(cache.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_transfer_to() {
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut cold = TlfuCore::new(100);
+        let mut hot = TlfuCore::new(100);
+        cold.set_grouped(vec![(1, 3600 * second as i64, 7)]);
+        cold.set(vec![(2, 0), (3, 10 * second as i64)]);
+        for _ in 0..5 {
+            cold.access(vec![1]).unwrap();
+        }
+        let frequency = cold.policy.sketch.estimate(1);
+        hot.set(vec![(3, 0)]);
+
+        assert!(cold.transfer_to(1, &mut hot));
+        assert!(!cold.entries.contains_key(&1));
+        assert!(!cold.transfer_to(1, &mut hot));
+        assert!(!cold.transfer_to(3, &mut hot));
+        assert!(!cold.transfer_to(4, &mut hot));
+
+        let moved = &hot.entries[&1];
+        let remaining = moved.expire - hot.wheel.clock.now_ns();
+        assert!(remaining <= 3600 * second && remaining > 3500 * second);
+        assert_eq!(moved.group, Some(7));
+        // seeded up to the source estimate, plus the insert itself
+        assert_eq!(hot.policy.sketch.estimate(1), frequency + 1);
+        assert!(hot.take_evicted().is_empty());
+
+        assert!(cold.transfer_to(2, &mut hot));
+        assert_eq!(hot.entries[&2].expire, 0);
+        assert!(hot.check_consistency().is_ok());
+        assert!(cold.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_validate_wheel_after_operations() {
+        let mut cache = TlfuCore::new(50);
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..2000 {
+            let ttl = Duration::from_secs(rng.random_range(0..100_000)).as_nanos() as i64;
+            cache.set(vec![(rng.random_range(0..200), ttl)]);
+            cache.access(vec![rng.random_range(0..200)]).unwrap();
+        }
+        assert!(cache.validate_wheel().is_empty());
+
+        let now = cache.wheel.clock.now_ns();
+        bound(&mut cache, |c| {
+            TlfuCore::advance_to(c, now + Duration::from_secs(5000).as_nanos() as u64)
+        })
+        .unwrap();
+        assert!(cache.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_access_or_insert() {
+        let mut cache = TlfuCore::new(2);
+        let ttl = Duration::from_secs(60).as_nanos() as i64;
+
+        assert!(!cache.access_or_insert(1, ttl));
+        assert!(cache.entries[&1].expire > 0);
+        assert!(cache.access_or_insert(1, ttl));
+        assert!(!cache.access_or_insert(2, 0));
+        assert!(cache.take_evicted().is_empty());
+
+        // expired entries count as a miss and are renewed
+        cache.entries.get_mut(&1).unwrap().expire = 1;
+        assert!(!cache.access_or_insert(1, ttl));
+        assert!(!cache.entries[&1].is_expired(cache.wheel.clock.now_ns()));
+
+        for key in 10..20 {
+            cache.access_or_insert(key, 0);
+        }
+        let evicted = cache.take_evicted();
+        assert_eq!(evicted.len(), 10);
+        for key in &evicted {
+            assert!(!cache.entries.contains_key(key));
+        }
+        assert!(cache.take_evicted().is_empty());
+    }
+
+    #[test]
+    fn test_entry_info() {
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0)]);
+        assert!(cache.entry_info(1).is_some());
+        assert!(cache.entry_info(2).is_none());
+    }
+
+    #[test]
+    fn test_purge_expired() {
+        let mut cache = TlfuCore::new(100);
+        let ttl = Duration::from_secs(60).as_nanos() as i64;
+        cache.set(vec![(1, ttl), (2, ttl), (3, ttl), (4, 0)]);
+        for key in [1, 3] {
+            cache.entries.get_mut(&key).unwrap().expire = 1;
+        }
+
+        let mut purged = bound(&mut cache, TlfuCore::purge_expired).unwrap();
+        purged.sort_unstable();
+        assert_eq!(purged, vec![1, 3]);
+        let mut keys = cache.keys();
+        keys.sort_unstable();
+        assert_eq!(keys, vec![2, 4]);
+        assert_eq!(cache.policy.len(), 2);
+        assert!(cache.validate_wheel().is_empty());
+        assert!(
+            bound(&mut cache, TlfuCore::purge_expired)
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_with_wheel_levels() {
+        assert!(TlfuCore::with_wheel_levels(100, vec![64, 3, 1]).is_err());
+
+        let mut cache = TlfuCore::with_wheel_levels(100, vec![64, 64, 32, 4, 4, 1]).unwrap();
+        let day = Duration::from_secs(24 * 60 * 60).as_nanos() as i64;
+        cache.set(vec![(1, 10 * day), (2, 20 * day)]);
+        assert_eq!(cache.entries[&1].wheel_index.0, 4);
+        assert_eq!(cache.entries[&2].wheel_index.0, 4);
+        assert_ne!(cache.entries[&1].wheel_index, cache.entries[&2].wheel_index);
+        assert!(cache.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_promote_long_ttl_threshold() {
+        let mut cache = TlfuCore::new(100);
+        let hour = Duration::from_secs(60 * 60).as_nanos() as u64;
+        cache.set_promote_long_ttl_threshold(hour);
+
+        cache.set(vec![(1, 60), (2, 2 * hour as i64), (3, 0)]);
+        assert_eq!(cache.entries[&1].policy_list_id, 1);
+        assert_eq!(cache.entries[&2].policy_list_id, 2);
+        assert_eq!(cache.entries[&3].policy_list_id, 2);
+
+        // updating an existing key doesn't move it
+        cache.set(vec![(1, 2 * hour as i64)]);
+        assert_eq!(cache.entries[&1].policy_list_id, 1);
+    }
+
+    #[test]
+    fn test_swap() {
+        let mut serving = TlfuCore::new(100);
+        let mut warmed = TlfuCore::new(100);
+        serving.set(vec![(1, 0), (2, 0)]);
+        warmed.set(vec![(3, 0), (4, 0), (5, 0)]);
+
+        serving.swap(&mut warmed);
+
+        let mut keys = serving.keys();
+        keys.sort();
+        assert_eq!(keys, vec![3, 4, 5]);
+        assert_eq!(serving.len(), 3);
+        let mut keys = warmed.keys();
+        keys.sort();
+        assert_eq!(keys, vec![1, 2]);
+        assert_eq!(warmed.len(), 2);
+
+        serving.access(vec![4]).unwrap();
+        serving.remove(5).unwrap();
+        assert_eq!(serving.len(), 2);
+        assert!(serving.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_swap_keeps_settings() {
+        let mut serving = TlfuCore::new(10);
+        let mut warmed = TlfuCore::new(10);
+        serving.set_overflow_allowance(5);
+        serving.set((1..=10).map(|key| (key, 0)).collect());
+        warmed.set((11..=20).map(|key| (key, 0)).collect());
+        assert_eq!(warmed.set(vec![(21, 0)]).len(), 1);
+        assert!(!warmed.access_or_insert(22, 0));
+
+        serving.swap(&mut warmed);
+
+        // the keys evicted from the warmed contents travel with them
+        assert_eq!(serving.take_evicted().len(), 1);
+        assert!(warmed.take_evicted().is_empty());
+        assert_eq!(serving.recent_eviction_rate(64), 0.5);
+
+        // the allowance stays with the serving cache
+        assert!(
+            serving
+                .set((23..=26).map(|key| (key, 0)).collect())
+                .is_empty()
+        );
+        assert_eq!(serving.len(), 14);
+        assert_eq!(warmed.set(vec![(30, 0)]).len(), 1);
+        assert!(serving.validate_wheel().is_empty());
+        assert!(warmed.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_expiry_interceptor() {
+        Python::initialize();
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, second), (2, second), (3, second), (4, second)]);
+
+        Python::attach(|py| {
+            let callback = py
+                .eval(
+                    c"lambda k: 10**12 if k == 1 else 0 if k == 2 else 1 // 0 if k == 4 else None",
+                    None,
+                    None,
+                )
+                .unwrap();
+            cache.set_expiry_interceptor(Some(callback.unbind()));
+        });
+
+        let now = cache.wheel.clock.now_ns() + 3 * second as u64;
+        let mut expired = bound(&mut cache, |c| TlfuCore::advance_to(c, now)).unwrap();
+        expired.sort();
+        assert_eq!(expired, vec![3, 4]);
+        assert_eq!(cache.entries[&1].expire, now + 1_000_000_000_000);
+        assert_eq!(cache.entries[&2].expire, 0);
+        assert!(cache.validate_wheel().is_empty());
+
+        cache.set_expiry_interceptor(None);
+        assert_eq!(
+            bound(&mut cache, |c| TlfuCore::advance_to(
+                c,
+                now + 2000 * second as u64
+            ))
+            .unwrap(),
+            vec![1]
+        );
+        assert_eq!(cache.keys(), vec![2]);
+    }
+
+    #[test]
+    fn test_expiry_interceptor_scans() {
+        use pyo3::types::PyDict;
+
+        Python::initialize();
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        Python::attach(|py| {
+            let mut core = TlfuCore::new(100);
+            core.set((1..=6).map(|k| (k, second)).collect());
+            let cache = Bound::new(py, core).unwrap();
+
+            // odd keys are renewed, 2 is rewritten through the cache itself
+            let globals = PyDict::new(py);
+            globals.set_item("cache", &cache).unwrap();
+            let callback = py
+                .eval(
+                    c"lambda k: 10**12 if k % 2 else cache.set([(k, 0)]) if k == 2 else None",
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            cache
+                .borrow_mut()
+                .set_expiry_interceptor(Some(callback.unbind()));
+            for entry in cache.borrow_mut().entries.values_mut() {
+                entry.expire = 1;
+            }
+
+            assert_eq!(
+                TlfuCore::remove_if_expired(&cache, vec![5, 6]).unwrap(),
+                vec![6]
+            );
+            let mut purged = TlfuCore::purge_expired(&cache).unwrap();
+            purged.sort();
+            assert_eq!(purged, vec![4]);
+
+            let cache = cache.borrow();
+            let mut keys = cache.keys();
+            keys.sort();
+            assert_eq!(keys, vec![1, 2, 3, 5]);
+            assert_eq!(cache.entries[&2].expire, 0);
+            assert!(cache.entries[&5].expire > 1);
+            assert!(cache.validate_wheel().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_clear_resets_policy() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((0..200).map(|k| (k, 0)).collect());
+        cache.access((0..200).collect()).unwrap();
+        cache.clear();
+
+        assert_eq!(cache.len(), 0);
+        let info = cache.debug_info();
+        assert_eq!(info.len, 0);
+        assert_eq!(info.window_len, 0);
+        assert_eq!(info.probation_len, 0);
+        assert_eq!(info.protected_len, 0);
+
+        cache.set(vec![(1, 0)]);
+        assert_eq!(cache.debug_info().len, 1);
+    }
+
+    #[test]
+    fn test_top_keys() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=10).map(|k| (k, 0)).collect());
+        for k in 1..=10 {
+            cache.access(vec![k; k as usize]).unwrap();
+        }
+
+        let top = cache.top_keys(3);
+        assert_eq!(
+            top.iter().map(|(k, _)| *k).collect::<Vec<_>>(),
+            vec![10, 9, 8]
+        );
+        assert!(top.windows(2).all(|w| w[0].1 >= w[1].1));
+        assert!(cache.top_keys(0).is_empty());
+        assert_eq!(cache.top_keys(50).len(), 10);
+    }
+
+    #[test]
+    fn test_batch_set_matches_per_key() {
+        let run = |batched: bool, hot_new: bool| {
+            let mut cache = TlfuCore::new(100);
+            for k in 0..100 {
+                cache.set(vec![(k, 0)]);
+            }
+            // warm either the residents or the incoming keys
+            let warm: Vec<u64> = if hot_new {
+                (1000..1050).flat_map(|k| [k, k, k]).collect()
+            } else {
+                (0..100).flat_map(|k| [k, k, k]).collect()
+            };
+            cache.access(warm).unwrap();
+
+            let batch: Vec<(u64, i64)> = (1000..1050).map(|k| (k, 0)).collect();
+            let mut evicted = if batched {
+                cache.set(batch)
+            } else {
+                batch
+                    .into_iter()
+                    .flat_map(|entry| cache.set(vec![entry]))
+                    .collect()
+            };
+            evicted.sort();
+            let mut keys = cache.keys();
+            keys.sort();
+            assert_eq!(keys.len(), 100);
+            assert_eq!(cache.debug_info().len, 100);
+            (keys, evicted)
+        };
+
+        for hot_new in [false, true] {
+            let (batched_keys, batched_evicted) = run(true, hot_new);
+            let (per_key_keys, per_key_evicted) = run(false, hot_new);
+            assert_eq!(batched_keys, per_key_keys);
+            assert_eq!(batched_evicted, per_key_evicted);
+            assert_eq!(batched_evicted.len(), 50);
+        }
+    }
+
+    #[test]
+    fn test_batch_variants_match_set() {
+        let fill = || {
+            let mut cache = TlfuCore::new(100);
+            cache.set((0..100).map(|k| (k, 0)).collect());
+            cache
+                .access((0..100).flat_map(|k| [k, k]).collect())
+                .unwrap();
+            cache
+        };
+        let sorted = |mut keys: Vec<u64>| {
+            keys.sort_unstable();
+            keys
+        };
+
+        let mut plain = fill();
+        let mut grouped = fill();
+        let mut tti = fill();
+        let expected = sorted(plain.set((1000..1050).map(|k| (k, 0)).collect()));
+        assert!(!expected.is_empty());
+        let grouped_evicted = grouped.set_grouped((1000..1050).map(|k| (k, 0, 3)).collect());
+        assert_eq!(sorted(grouped_evicted), expected);
+        let tti_evicted = tti.set_with_tti((1000..1050).map(|k| (k, 0, 0)).collect());
+        assert_eq!(sorted(tti_evicted), expected);
+        assert_eq!(sorted(grouped.keys()), sorted(plain.keys()));
+        assert_eq!(sorted(tti.keys()), sorted(plain.keys()));
+        assert!(grouped.validate_wheel().is_empty());
+        assert!(tti.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_is_protected() {
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
+        // 1 and 2 were pushed to probation by the 1-slot window
+        cache.access(vec![1]).unwrap();
+
+        assert_eq!(cache.is_protected(1), Some(true));
+        assert_eq!(cache.is_protected(2), Some(false));
+        assert_eq!(cache.is_protected(3), Some(false));
+        assert_eq!(cache.is_protected(4), None);
+    }
+
+    #[test]
+    fn test_min_admission_frequency() {
+        let mut cache = TlfuCore::new(1000);
+        cache.set_min_admission_frequency(2);
+
+        // a scan of single-access keys never becomes resident
+        let scan: Vec<(u64, i64)> = (0..100).map(|k| (k, 0)).collect();
+        let mut rejected = cache.set(scan);
+        rejected.sort();
+        assert_eq!(rejected, (0..100).collect::<Vec<u64>>());
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.debug_info().len, 0);
+        assert!(cache.validate_wheel().is_empty());
+
+        // a key seen before is admitted on its next set
+        cache.access(vec![1000]).unwrap();
+        assert!(cache.set(vec![(1000, 0), (1001, 0)]).contains(&1001));
+        assert_eq!(cache.keys(), vec![1000]);
+    }
+
+    #[test]
+    fn test_set_full_report() {
+        let mut cache = TlfuCore::new(10);
+        cache.set((0..10).map(|k| (k, 0)).collect());
+
+        let (evicted, removed) = cache.set_full_report(vec![
+            (3, -1),
+            (100, 0),
+            (42, -1),
+            (5, -1),
+            (101, 0),
+            (102, 0),
+        ]);
+        assert_eq!(removed, vec![3, 5]);
+        assert_eq!(evicted.len(), 1);
+        assert!(!removed.contains(&evicted[0]));
+        assert_eq!(cache.len(), 10);
+    }
+
+    #[test]
+    fn test_keys_ordered() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=5).map(|k| (k, 0)).collect());
+        cache.access(vec![2]).unwrap();
+
+        let ordered = cache.keys_ordered();
+        assert_eq!(ordered, vec![5, 4, 3, 1, 2]);
+        let mut keys = cache.keys();
+        keys.sort();
+        let mut sorted = ordered.clone();
+        sorted.sort();
+        assert_eq!(keys, sorted);
+    }
+
+    #[test]
+    fn test_snapshot_keys_chunked() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((0..50).map(|k| (k, 0)).collect());
+
+        let mut keys = Vec::new();
+        let mut offset = 0;
+        loop {
+            let chunk = cache.snapshot_keys_chunked(offset, 16);
+            assert!(chunk.len() <= 16);
+            if chunk.is_empty() {
+                break;
+            }
+            offset += chunk.len();
+            keys.extend(chunk);
+        }
+        keys.sort();
+        assert_eq!(keys, (0..50).collect::<Vec<u64>>());
+        assert!(cache.snapshot_keys_chunked(100, 16).is_empty());
+    }
+
+    #[test]
+    fn test_set_duplicate_keys() {
+        let mut cache = TlfuCore::new(100);
+        let hour = Duration::from_secs(60 * 60).as_nanos() as u64;
+        let before = cache.wheel.clock.now_ns();
+        cache.set(vec![(5, hour as i64), (5, 2 * hour as i64)]);
+
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.debug_info().len, 1);
+        // counted as a single insertion
+        assert_eq!(cache.policy.sketch.estimate(5), 1);
+        assert_eq!(cache.sketch_additions(), 1);
+        let expire = cache.entries[&5].expire;
+        assert!(expire >= before + 2 * hour);
+        assert!(expire <= cache.wheel.clock.now_ns() + 2 * hour);
+        assert!(cache.validate_wheel().is_empty());
+
+        // a later removal in the same batch wins as well
+        assert!(cache.set(vec![(6, 0), (6, -1)]).is_empty());
+        assert!(!cache.entries.contains_key(&6));
+        assert_eq!(cache.debug_info().len, 1);
+    }
+
+    #[test]
+    fn test_set_duplicate_keys_admission_gate() {
+        let mut cache = TlfuCore::new(3);
+        cache.set_min_admission_frequency(2);
+
+        // the first write is rejected, the second is admitted
+        assert!(cache.set(vec![(1, 0), (1, 0), (1, 0)]).is_empty());
+        assert_eq!(cache.keys(), vec![1]);
+
+        // a key rejected on every write is reported once
+        cache.set_min_admission_frequency(10);
+        assert_eq!(cache.set(vec![(2, 0), (2, 0), (2, 0)]), vec![2]);
+        assert_eq!(cache.keys(), vec![1]);
+        assert!(cache.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_evict_coldest() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=10).map(|k| (k, 0)).collect());
+        for k in 1..=10 {
+            cache.access(vec![k; k as usize]).unwrap();
+        }
+
+        let mut removed = cache.evict_coldest(3);
+        removed.sort();
+        assert_eq!(removed, vec![1, 2, 3]);
+        assert_eq!(cache.len(), 7);
+        assert_eq!(cache.debug_info().len, 7);
+        assert!(cache.validate_wheel().is_empty());
+
+        assert_eq!(cache.evict_coldest(100).len(), 7);
+        assert_eq!(cache.len(), 0);
+        assert_eq!(cache.debug_info().len, 0);
+    }
+
+    #[test]
+    fn test_weight_writes() {
+        let run = |weight_writes: bool| {
+            let mut cache = TlfuCore::new(10);
+            cache.set_weight_writes(weight_writes);
+            let reads: Vec<u64> = (1..=10).collect();
+            cache.set(reads.iter().map(|&k| (k, 0)).collect());
+            cache
+                .access(reads.iter().flat_map(|&k| [k, k]).collect())
+                .unwrap();
+
+            // keys rewritten over and over but never read
+            for _ in 0..5 {
+                cache.set((100..105).map(|k| (k, 0)).collect());
+                cache.set((100..105).map(|k| (k, -1)).collect());
+            }
+            cache.set((100..105).map(|k| (k, 0)).collect());
+            cache.keys().into_iter().filter(|&k| k < 100).count()
+        };
+
+        // write volume alone pushes out read-hot keys
+        assert!(run(true) < 8);
+        // only the window slot goes to a write-only key
+        assert_eq!(run(false), 9);
+    }
+
+    #[test]
+    fn test_wheel_nanos() {
+        let mut cache = TlfuCore::new(100);
+        assert!(cache.wheel_nanos() <= cache.clock_now_ns());
+
+        let before = cache.clock_now_ns();
+        bound(&mut cache, TlfuCore::advance).unwrap();
+        let after = cache.clock_now_ns();
+        assert!(cache.wheel_nanos() >= before && cache.wheel_nanos() <= after);
+
+        bound(&mut cache, |c| {
+            TlfuCore::advance_to(c, after + 5_000_000_000)
+        })
+        .unwrap();
+        assert_eq!(cache.wheel_nanos(), after + 5_000_000_000);
+    }
+
+    #[test]
+    fn test_overflow_allowance() {
+        let mut cache = TlfuCore::new(100);
+        cache.set_overflow_allowance(10);
+
+        for k in 0..110 {
+            assert!(cache.set(vec![(k, 0)]).is_empty());
+        }
+        assert_eq!(cache.len(), 110);
+        assert_eq!(cache.debug_info().len, 110);
+
+        // past the allowance eviction resumes, back to the soft limit
+        assert_eq!(cache.set(vec![(110, 0)]).len(), 1);
+        assert_eq!(cache.len(), 110);
+
+        let trimmed = cache.trim();
+        assert_eq!(trimmed.len(), 10);
+        assert_eq!(cache.len(), 100);
+        assert_eq!(cache.debug_info().len, 100);
+        assert!(trimmed.iter().all(|k| !cache.entries.contains_key(k)));
+        assert!(cache.trim().is_empty());
+    }
+
+    #[test]
+    fn test_lowered_allowance_reports_all_evictions() {
+        let mut cache = TlfuCore::new(10);
+        cache.set_overflow_allowance(10);
+        cache.set((1..=20).map(|key| (key, 0)).collect());
+        assert_eq!(cache.len(), 20);
+
+        cache.set_overflow_allowance(0);
+        let evicted = cache.set_grouped(vec![(21, 0, 7)]);
+        assert_eq!(evicted.len(), 11);
+        assert_eq!(cache.len(), 10);
+        assert!(evicted.iter().all(|key| !cache.entries.contains_key(key)));
+        assert!(cache.validate_wheel().is_empty());
+    }
+
+    #[test]
+    fn test_preview_evictions_over_capacity() {
+        let mut cache = TlfuCore::new(10);
+        cache.set_overflow_allowance(10);
+        cache.set((1..=20).map(|key| (key, 0)).collect());
+        assert_eq!(cache.len(), 20);
+
+        // one insert now evicts everything above the lowered allowance
+        cache.set_overflow_allowance(0);
+        let mut preview = cache.preview_evictions(vec![21]);
+        assert_eq!(preview.len(), 11);
+        assert_eq!(cache.len(), 20);
+
+        let mut evicted = cache.set(vec![(21, 0)]);
+        preview.sort_unstable();
+        evicted.sort_unstable();
+        assert_eq!(preview, evicted);
+    }
+
+    #[test]
+    fn test_hashed_variants() {
+        let mut hashed = TlfuCore::new(100);
+        let mut spread_first = TlfuCore::new(100);
+        let raw: Vec<i64> = vec![-7, 0, 42, i64::MAX, i64::MIN];
+
+        hashed.set_hashed(raw.iter().map(|&h| (h, 0)).collect());
+        spread_first.set(raw.iter().map(|&h| (spread(h), 0)).collect());
+        hashed.access_hashed(vec![42, 42]).unwrap();
+        spread_first.access(vec![spread(42), spread(42)]).unwrap();
+
+        assert_eq!(hashed.keys_ordered(), spread_first.keys_ordered());
+        assert_eq!(hashed.is_protected(spread(42)), Some(true));
+
+        hashed.set_hashed(vec![(42, -1)]);
+        assert_eq!(hashed.is_protected(spread(42)), None);
+    }
+
+    #[test]
+    fn test_export_import_state() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        let mut source = TlfuCore::new(100);
+        source.set(
+            (1..=50)
+                .map(|k| (k, if k % 2 == 0 { hour } else { 0 }))
+                .collect(),
+        );
+        source.access((1..=20).collect()).unwrap();
+
+        let state = source.export_state();
+        assert_eq!(state.len(), 50);
+        assert!(state.iter().all(|&(k, _, ttl)| (k % 2 == 0) == (ttl > 0)));
+
+        // same capacity round-trips placement and order
+        let mut same = TlfuCore::new(100);
+        assert!(same.import_state(state.clone()).is_empty());
+        assert_eq!(same.keys_ordered(), source.keys_ordered());
+        assert_eq!(
+            same.export_state()
+                .iter()
+                .map(|s| (s.0, s.1))
+                .collect::<Vec<_>>(),
+            state.iter().map(|s| (s.0, s.1)).collect::<Vec<_>>()
+        );
+        assert!(same.validate_wheel().is_empty());
+        assert_eq!(same.debug_info().len, 50);
+
+        // a smaller cache keeps the hottest end
+        let mut small = TlfuCore::new(10);
+        let mut dropped = small.import_state(state.clone());
+        assert_eq!(small.len(), 10);
+        assert_eq!(small.debug_info().len, 10);
+        let mut kept = small.keys();
+        kept.sort();
+        let mut expected: Vec<u64> = state[40..].iter().map(|s| s.0).collect();
+        expected.sort();
+        assert_eq!(kept, expected);
+        dropped.sort();
+        let mut expected: Vec<u64> = state[..40].iter().map(|s| s.0).collect();
+        expected.sort();
+        assert_eq!(dropped, expected);
+    }
+
+    #[test]
+    fn test_large_capacity_sketch() {
+        let capacity = 1 << 21;
+        for (mut cache, sample_size) in [
+            (TlfuCore::new(capacity), capacity * 10),
+            (
+                TlfuCore::with_max_sketch_size(capacity, 1 << 16),
+                (1 << 16) * 10,
+            ),
+        ] {
+            assert_eq!(cache.policy.sketch.sample_size, sample_size);
+
+            let keys: Vec<u64> = (0..10_000).map(|k| spread(k as i64)).collect();
+            cache.set(keys.iter().map(|&k| (k, 0)).collect());
+            for _ in 0..3 {
+                cache.access(keys[..100].to_vec()).unwrap();
+            }
+
+            assert_eq!(cache.len(), 10_000);
+            let hot = keys[..100]
+                .iter()
+                .filter(|&&k| cache.policy.sketch.estimate(k) >= 4)
+                .count();
+            assert_eq!(hot, 100);
+            let cold = keys[100..]
+                .iter()
+                .filter(|&&k| cache.policy.sketch.estimate(k) <= 2)
+                .count();
+            assert!(cold > 9_800);
+        }
+    }
+
+    #[test]
+    fn test_recent_eviction_rate() {
+        let mut cache = TlfuCore::new(10);
+        assert_eq!(cache.recent_eviction_rate(8), 0.0);
+        assert!(cache.set_eviction_sample_size(0).is_err());
+        cache.set_eviction_sample_size(4).unwrap();
+
+        cache.set((0..10).map(|k| (k, 0)).collect());
+        assert_eq!(cache.recent_eviction_rate(8), 0.0);
+
+        // a burst of new keys, 5 evictions per batch
+        for batch in 1..=3 {
+            let start = batch * 100;
+            assert_eq!(
+                cache
+                    .set((start..start + 5).map(|k| (k, 0)).collect())
+                    .len(),
+                5
+            );
+        }
+        assert_eq!(cache.recent_eviction_rate(1), 5.0);
+        assert_eq!(cache.recent_eviction_rate(4), 15.0 / 4.0);
+        // only 4 samples are kept
+        assert_eq!(cache.recent_eviction_rate(100), 15.0 / 4.0);
+
+        cache.set(vec![(1000, 0)]);
+        assert_eq!(cache.recent_eviction_rate(4), 16.0 / 4.0);
+    }
+
+    #[test]
+    fn test_set_with_frequency() {
+        let mut cache = TlfuCore::new(10);
+        cache.set_with_frequency((0..10).map(|k| (k, 0, 1)).collect());
+        assert_eq!(cache.len(), 10);
+
+        // a hot restored key enters the 1-slot window
+        let evicted = cache.set_with_frequency(vec![(100, 0, 10)]);
+        assert_eq!(evicted.len(), 1);
+        assert_ne!(evicted[0], 100);
+
+        // pushed out of the window, it wins its first contest against a low-seed victim
+        let evicted = cache.set_with_frequency(vec![(200, 0, 0)]);
+        assert_eq!(evicted.len(), 1);
+        assert_ne!(evicted[0], 100);
+        assert_eq!(cache.entries[&100].policy_list_id, 2);
+
+        // while an unseeded key loses its first contest
+        assert_eq!(cache.set_with_frequency(vec![(300, 0, 0)]), vec![200]);
+    }
+
+    #[test]
+    fn test_set_with_frequency_wide_counters() {
+        let mut narrow = TlfuCore::new(1000);
+        narrow.set_with_frequency(vec![(1, 0, 100)]);
+        assert_eq!(narrow.policy.sketch.estimate(1), 15);
+
+        // 8-bit counters keep seeds above 15
+        let mut wide = TlfuCore::with_counter_width(1000, CounterWidth::Eight);
+        wide.set_with_frequency(vec![(1, 0, 100), (2, 0, 255)]);
+        assert_eq!(wide.policy.sketch.estimate(1), 101);
+        assert_eq!(wide.policy.sketch.estimate(2), 255);
+    }
+
+    #[test]
+    fn test_simulate() {
+        let mut cache = TlfuCore::new(100);
+        assert_eq!(cache.simulate(vec![]), 0.0);
+
+        // a working set that fits: only the first pass misses
+        let keys: Vec<u64> = (0..50).cycle().take(500).collect();
+        assert!((cache.simulate(keys) - 0.9).abs() < 1e-9);
+        assert!(cache.take_evicted().is_empty());
+
+        // a scan much larger than the cache almost never hits
+        let mut small = TlfuCore::new(10);
+        let scan: Vec<u64> = (0..1000).cycle().take(5000).collect();
+        assert!(small.simulate(scan) < 0.05);
+        assert!(small.take_evicted().is_empty());
+        assert_eq!(small.len(), 10);
+    }
+
+    #[test]
+    fn test_geometry() {
+        let geometry = TlfuCore::new(1000).geometry();
+        assert_eq!(geometry["capacity"], 1000);
+        assert_eq!(geometry["window"], 10);
+        assert_eq!(geometry["protected"], 792);
+        assert_eq!(geometry["probation"], 198);
+        assert_eq!(geometry["sketch"], 1024);
+
+        // a size of 0 is clamped to a degenerate single slot window
+        let geometry = TlfuCore::new(0).geometry();
+        assert_eq!(geometry["capacity"], 1);
+        assert_eq!(geometry["window"], 1);
+        assert_eq!(geometry["probation"], 0);
+    }
+
+    #[test]
+    fn test_remove_inserted_before() {
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
+        std::thread::sleep(Duration::from_millis(1));
+        let cutoff = cache.clock_now_ns();
+        std::thread::sleep(Duration::from_millis(1));
+
+        // rewriting a key moves it past the cutoff
+        cache.set(vec![(2, 0), (4, 0)]);
+
+        let mut removed = cache.remove_inserted_before(cutoff);
+        removed.sort();
+        assert_eq!(removed, vec![1, 3]);
+        let mut keys = cache.keys();
+        keys.sort();
+        assert_eq!(keys, vec![2, 4]);
+        assert_eq!(cache.debug_info().len, 2);
+        assert!(cache.remove_inserted_before(cutoff).is_empty());
+    }
+
+    #[test]
+    fn test_consistency_check() {
+        let build = || {
+            let mut cache = TlfuCore::new(100);
+            cache.set((1..=10).map(|k| (k, 0)).collect());
+            cache.access(vec![1, 2]).unwrap();
+            assert_eq!(cache.consistency_check(), Ok(()));
+            cache
+        };
+        let issues = |cache: &TlfuCore| cache.consistency_check().unwrap_err().join("\n");
+
+        // index resolving to another key
+        let mut cache = build();
+        let other = cache.entries[&4].policy_list_index;
+        cache.entries.get_mut(&3).unwrap().policy_list_index = other;
+        assert!(
+            issues(&cache).contains("key 3: policy_list_index doesn't resolve to it in probation")
+        );
+
+        // entry claiming the wrong list
+        let mut cache = build();
+        cache.entries.get_mut(&1).unwrap().policy_list_id = 2;
+        let found = issues(&cache);
+        assert!(found.contains("key 1: policy_list_index doesn't resolve to it in probation"));
+        assert!(found.contains("key 1: in protected but its entry names list 2"));
+
+        // missing index and invalid list id
+        let mut cache = build();
+        cache.entries.get_mut(&5).unwrap().policy_list_index = None;
+        cache.entries.get_mut(&6).unwrap().policy_list_id = 9;
+        let found = issues(&cache);
+        assert!(found.contains("key 5: list 2 but no policy_list_index"));
+        assert!(found.contains("key 6: invalid policy_list_id 9"));
+
+        // listed key missing from entries
+        let mut cache = build();
+        cache.entries.remove(&7);
+        assert!(issues(&cache).contains("key 7: in probation but not in entries"));
+
+        // a stale index left on an entry outside the policy
+        let mut cache = build();
+        cache.entries.get_mut(&8).unwrap().policy_list_id = 0;
+        assert!(issues(&cache).contains("key 8: not in policy but has a policy_list_index"));
+    }
+
+    #[test]
+    fn test_access_with_freshness() {
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![
+            (1, 0),
+            (2, 60 * second as i64),
+            (3, 60 * second as i64),
+        ]);
+        cache.entries.get_mut(&3).unwrap().expire = 1;
+
+        assert_eq!(cache.access_with_freshness(1, 10 * second), 1);
+        assert_eq!(cache.access_with_freshness(2, 10 * second), 1);
+        assert_eq!(cache.access_with_freshness(2, 120 * second), 0);
+        assert_eq!(cache.access_with_freshness(3, 10 * second), -1);
+        assert_eq!(cache.access_with_freshness(4, 10 * second), -1);
+
+        // live results record an access, 2 is promoted out of probation
+        assert_eq!(cache.is_protected(2), Some(true));
+        assert_eq!(cache.is_protected(3), Some(false));
+    }
+
+    #[test]
+    fn test_access_with_segment() {
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0), (3, 60_000_000_000), (4, 0)]);
+        cache.entries.get_mut(&3).unwrap().expire = 1;
+
+        assert_eq!(cache.access_with_segment(4), 1);
+        // the segment before the promotion is reported
+        assert_eq!(cache.access_with_segment(2), 2);
+        assert_eq!(cache.access_with_segment(2), 3);
+        assert_eq!(cache.access_with_segment(3), -1);
+        assert_eq!(cache.access_with_segment(5), -1);
+        assert_eq!(cache.is_protected(1), Some(false));
+    }
+
+    #[test]
+    fn test_shrink_entries() {
+        let mut cache = TlfuCore::new(10_000);
+        cache.set((0..10_000).map(|key| (key, 0)).collect());
+        cache.set((10..10_000).map(|key| (key, -1)).collect());
+        let before = cache.entries.capacity();
+
+        cache.shrink_entries();
+        assert!(cache.entries.capacity() < before);
+        assert_eq!(cache.len(), 10);
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_max_ttl() {
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set_max_ttl(10 * second);
+        cache.set(vec![
+            (1, 3600 * second as i64),
+            (2, 5 * second as i64),
+            (3, 0),
+        ]);
+        cache.set_entry(4, u64::MAX / 2);
+
+        for key in [1, 4] {
+            let entry = &cache.entries[&key];
+            assert_eq!(entry.original_ttl, 10 * second);
+            assert!(entry.expire <= cache.wheel.clock.now_ns() + 10 * second);
+            assert_eq!(cache.wheel_level_of(key), Some(0));
+        }
+        assert_eq!(cache.entries[&2].original_ttl, 5 * second);
+        assert_eq!(cache.entries[&3].expire, 0);
+
+        cache.set_max_ttl(0);
+        cache.set(vec![(1, 3600 * second as i64)]);
+        assert_eq!(cache.entries[&1].original_ttl, 3600 * second);
+    }
+
+    #[test]
+    fn test_dump() {
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 60 * second), (3, 60 * second)]);
+        cache.entries.get_mut(&3).unwrap().expire = 1;
+        cache.access(vec![1, 1, 1]).unwrap();
+        cache.set(vec![(1, 0)]);
+
+        let dump = cache.dump();
+        assert_eq!(dump.len(), 2);
+        let (key, ttl, segment, frequency, accesses) = dump[1];
+        assert_eq!((key, ttl, segment, accesses), (1, 0, 3, 3));
+        assert!(frequency >= 4);
+        let (key, ttl, segment, _, accesses) = dump[0];
+        assert_eq!((key, segment, accesses), (2, 2, 0));
+        assert!(ttl > 0 && ttl <= 60 * second as u64);
+    }
+
+    #[test]
+    fn test_debug_invariants() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=10).map(|key| (key, 0)).collect());
+        cache.set_strict(true);
+        cache.policy.force_size(11);
+        // off by default
+        assert!(cache.access(vec![1]).is_ok());
+
+        cache.set_debug_invariants(true);
+        assert!(cache.access(vec![1]).is_err());
+        assert!(cache.remove(2).is_err());
+        assert!(bound(&mut cache, TlfuCore::advance).is_err());
+        // set has no error to raise and only logs, set_with_error raises
+        assert!(cache.set(vec![(20, 0)]).is_empty());
+        assert!(cache.entries.contains_key(&20));
+        assert!(cache.set_with_error(vec![(21, 0)]).is_err());
+        assert!(cache.entries.contains_key(&21));
+        // neither do the other set variants, which run the same check
+        assert!(cache.set_with_tti(vec![(22, 0, 0)]).is_empty());
+        assert!(cache.set_grouped(vec![(23, 0, 1)]).is_empty());
+        assert!(cache.entries.contains_key(&22) && cache.entries.contains_key(&23));
+
+        // outside strict mode the drift is only logged
+        cache.set_strict(false);
+        assert!(cache.access(vec![1]).is_ok());
+        cache.policy.force_size(cache.len());
+        cache.set_strict(true);
+        assert!(cache.access(vec![1]).is_ok());
+    }
+
+    #[test]
+    fn test_health_score() {
+        let failed = |cache: &TlfuCore| -> Vec<String> {
+            cache
+                .health_details()
+                .into_iter()
+                .filter(|(_, passed)| !passed)
+                .map(|(name, _)| name)
+                .collect()
+        };
+        let mut cache = TlfuCore::new(10);
+        cache.set(vec![(1, 0), (2, 60_000_000_000)]);
+        assert_eq!(cache.health_score(), 100);
+        assert_eq!(cache.health_details().len(), 4);
+
+        // a batch turning over the whole cache
+        cache.set((10..100).map(|key| (key, 0)).collect());
+        assert_eq!(failed(&cache), vec!["eviction_pressure_low"]);
+        assert_eq!(cache.health_score(), 85);
+
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0)]);
+        cache.entries.get_mut(&1).unwrap().expire = u64::MAX;
+        cache.policy.force_size(3);
+        assert_eq!(
+            failed(&cache),
+            vec!["size_matches_segments", "wheel_matches_entries"]
+        );
+        assert_eq!(cache.health_score(), 30);
+    }
+
+    #[test]
+    fn test_advance_checked() {
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 5 * second as i64)]);
+        let now = cache.clock_now_ns();
+
+        bound(&mut cache, |c| {
+            assert!(
+                TlfuCore::advance_checked(c, now + second)
+                    .unwrap()
+                    .is_empty()
+            );
+            assert!(TlfuCore::advance_checked(c, now).is_err());
+            // real time advance is behind the synthetic time, and is a no-op
+            assert!(TlfuCore::advance(c).unwrap().is_empty());
+            assert_eq!(c.borrow().wheel_nanos(), now + second);
+            assert_eq!(
+                TlfuCore::advance_checked(c, now + 10 * second).unwrap(),
+                vec![1]
+            );
+        });
+    }
+
+    #[test]
+    fn test_remove_if_expired() {
+        let mut cache = TlfuCore::new(100);
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        cache.set(vec![(1, hour), (2, 1), (3, 0), (4, 1)]);
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(
+            bound(&mut cache, |c| TlfuCore::remove_if_expired(
+                c,
+                vec![1, 2, 2, 3, 5]
+            ))
+            .unwrap(),
+            vec![2]
+        );
+        let mut keys = cache.keys();
+        keys.sort();
+        assert_eq!(keys, vec![1, 3, 4]);
+        assert!(
+            bound(&mut cache, |c| TlfuCore::remove_if_expired(c, vec![2]))
+                .unwrap()
+                .is_empty()
+        );
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_remove_if_expire_eq() {
+        let mut cache = TlfuCore::new(100);
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        cache.set(vec![(1, hour), (2, 0)]);
+        let observed = cache.entries[&1].expire;
+
+        // refreshed after the read, so the stale expiration doesn't match
+        std::thread::sleep(Duration::from_millis(1));
+        cache.set(vec![(1, hour)]);
+        assert!(!cache.remove_if_expire_eq(1, observed));
+        assert!(cache.entries.contains_key(&1));
+
+        let current = cache.entries[&1].expire;
+        assert!(cache.remove_if_expire_eq(1, current));
+        assert!(!cache.entries.contains_key(&1));
+        assert!(!cache.remove_if_expire_eq(1, current));
+
+        assert!(!cache.remove_if_expire_eq(2, 1));
+        assert!(cache.remove_if_expire_eq(2, 0));
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_set_ordered() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        let batch = || vec![(3, hour), (6, hour), (1, hour)];
+        let prefill = |cache: &mut TlfuCore| cache.set((1..=5).map(|key| (key, hour)).collect());
+
+        // plain set leaves the updated keys where they were
+        let mut plain = TlfuCore::new(1000);
+        prefill(&mut plain);
+        assert_eq!(plain.keys_ordered(), vec![5, 4, 3, 2, 1]);
+        plain.set(batch());
+        assert_eq!(plain.keys_ordered(), vec![6, 5, 4, 3, 2, 1]);
+
+        let mut cache = TlfuCore::new(1000);
+        prefill(&mut cache);
+        assert!(cache.set_ordered(batch()).is_empty());
+        assert_eq!(cache.keys_ordered(), vec![1, 6, 3, 5, 4, 2]);
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_try_set() {
+        let mut cache = TlfuCore::new(10);
+        cache.set((1..=7).map(|key| (key, 0)).collect());
+        assert_eq!(cache.free_slots(), 3);
+
+        // four new keys don't fit, and nothing is applied
+        let batch: Vec<(u64, i64)> = vec![(1, 0), (8, 0), (9, 0), (10, 0), (11, 0)];
+        assert_eq!(cache.try_set(batch.clone()), Err(1));
+        assert!(cache.set_if_fits(batch.clone()).is_err());
+        assert_eq!(cache.len(), 7);
+        assert!(!cache.entries.contains_key(&8));
+
+        // a removal in the same batch makes room
+        let mut batch = batch;
+        batch.push((2, -1));
+        assert_eq!(cache.try_set(batch), Ok(vec![]));
+        assert_eq!(cache.len(), 10);
+        assert_eq!(cache.free_slots(), 0);
+        assert_eq!(cache.try_set(vec![(3, 0)]), Ok(vec![]));
+        assert_eq!(cache.try_set(vec![(12, 0)]), Err(1));
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_wheel_level_of() {
+        let mut cache = TlfuCore::new(100);
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        cache.set(vec![(1, 2 * second), (2, 3600 * second), (3, 0)]);
+        assert_eq!(cache.wheel_level_of(1), Some(0));
+        assert_eq!(cache.wheel_level_of(2), Some(1));
+        assert_eq!(cache.wheel_level_of(3), None);
+        assert_eq!(cache.wheel_level_of(4), None);
+    }
+
+    #[test]
+    fn test_sketch_dimensions() {
+        let cache = TlfuCore::new(1000);
+        assert_eq!(cache.sketch_table_len(), 1024);
+        assert_eq!(cache.sketch_sample_size(), 10240);
+
+        // small caches keep the minimum table
+        let cache = TlfuCore::new(10);
+        assert_eq!(cache.sketch_table_len(), 64);
+        assert_eq!(cache.sketch_sample_size(), 640);
+
+        let mut cache = TlfuCore::new(1000);
+        assert!(cache.set_sketch_max_sample_size(0).is_err());
+        cache.set_sketch_max_sample_size(100).unwrap();
+        assert_eq!(cache.sketch_sample_size(), 100);
+
+        let cache = TlfuCore::with_max_sketch_size(100_000, 1000);
+        assert_eq!(cache.sketch_table_len(), 1024);
+    }
+
+    #[test]
+    fn test_strict_mode() {
+        Python::initialize();
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 0), (2, 0), (3, second as i64)]);
+        for key in [1, 2, 3] {
+            cache.entries.get_mut(&key).unwrap().policy_list_id = 9;
+        }
+
+        // lenient by default, errors are only logged
+        assert!(cache.access(vec![1]).is_ok());
+        assert_eq!(cache.remove(1).unwrap(), Some(1));
+
+        cache.set_strict(true);
+        assert!(cache.access(vec![2]).is_err());
+        // the key is still removed before the error is raised
+        assert!(cache.remove(2).is_err());
+        assert!(!cache.entries.contains_key(&2));
+        assert!(cache.remove(2).unwrap().is_none());
 
-        let expired_count = expired.len();
+        let now = cache.clock_now_ns();
+        assert!(bound(&mut cache, |c| TlfuCore::advance_to(c, now + 10 * second)).is_err());
+        assert_eq!(cache.len(), 0);
+    }
 
-        for &key in &expired {
-            if let Some(mut entry) = self.entries.remove(&key) {
-                let _ = self.policy.remove(&mut entry).map_err(|e| {
-                    log::error!("advance(key={}): {}", key, e);
-                });
-                log::trace!("Expired key {}", key);
-            }
-        }
+    #[test]
+    fn test_with_counter_width() {
+        let cache = TlfuCore::with_counter_width(1000, CounterWidth::Eight);
+        assert_eq!(cache.sketch_table_len(), 2048);
+        assert_eq!(cache.sketch_sample_size(), 10240);
 
-        if expired_count > 0 {
-            log::debug!("Advance: {} entries expired", expired_count);
-        }
+        let mut cache = TlfuCore::with_counter_width(100, CounterWidth::Eight);
+        cache.access(vec![7; 40]).unwrap();
+        assert_eq!(cache.policy.sketch.estimate(7), 40);
+    }
 
-        expired
+    #[test]
+    fn test_rename() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, hour as i64), (2, 0), (3, 0)]);
+        // move key 1 out of the window into protected
+        cache.set((4..100).map(|k| (k, 0)).collect());
+        cache.access(vec![1, 1]).unwrap();
+        assert_eq!(cache.is_protected(1), Some(true));
+        let before = cache.entries[&1].clone();
+
+        assert!(!cache.rename(1, 2));
+        assert!(!cache.rename(500, 501));
+        assert!(cache.rename(1, 500));
+
+        assert!(!cache.entries.contains_key(&1));
+        let after = &cache.entries[&500];
+        assert_eq!(after.expire, before.expire);
+        assert_eq!(cache.is_protected(500), Some(true));
+        assert_eq!(after.wheel_index, before.wheel_index);
+        assert!(cache.policy.sketch.estimate(500) >= cache.policy.sketch.estimate(1));
+        assert!(cache.check_consistency().is_ok());
+        assert!(cache.validate_wheel().is_empty());
+
+        let now = cache.clock_now_ns();
+        assert_eq!(
+            bound(&mut cache, |c| TlfuCore::advance_to(c, now + 2 * hour)).unwrap(),
+            vec![500]
+        );
     }
 
-    /// Removes all entries from the cache.
-    pub fn clear(&mut self) {
-        self.wheel.clear();
-        self.entries.clear();
-        log::debug!("Cache cleared");
+    #[test]
+    fn test_advance_notify() {
+        use pyo3::types::PyDict;
+
+        Python::initialize();
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        Python::attach(|py| {
+            let mut core = TlfuCore::new(100);
+            core.set(vec![(1, second), (2, second), (3, 0)]);
+            let now = core.clock_now_ns();
+            let cache = Bound::new(py, core).unwrap();
+
+            let globals = PyDict::new(py);
+            globals.set_item("cache", &cache).unwrap();
+            globals.set_item("seen", Vec::<u64>::new()).unwrap();
+            let callback = py
+                .eval(
+                    c"lambda keys: seen.append((sorted(keys), cache.len()))",
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+
+            TlfuCore::advance_notify_to(&cache, now, &callback).unwrap();
+            TlfuCore::advance_notify_to(&cache, now + 3 * second as u64, &callback).unwrap();
+
+            // called once with the whole batch, after the keys were removed
+            let seen: Vec<(Vec<u64>, usize)> = globals
+                .get_item("seen")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap();
+            assert_eq!(seen, vec![(vec![1, 2], 1)]);
+
+            // errors raised by the callback propagate
+            let failing = py.eval(c"lambda keys: 1 // 0", None, None).unwrap();
+            cache.borrow_mut().set(vec![(4, 5 * second)]);
+            assert!(
+                TlfuCore::advance_notify_to(&cache, now + 10 * second as u64, &failing).is_err()
+            );
+            assert_eq!(cache.borrow().keys(), vec![3]);
+        });
     }
 
-    /// Returns the number of entries currently in the cache.
-    #[must_use]
-    pub fn len(&self) -> usize {
-        self.entries.len()
+    #[test]
+    fn test_insertion_grace() {
+        let burst: Vec<(u64, i64)> = (100..105).map(|k| (k, 0)).collect();
+
+        // without a grace period the burst loses the admission contest
+        let mut cache = TlfuCore::new(20);
+        cache.set((1..=20).map(|k| (k, 0)).collect());
+        let evicted = cache.set(burst.clone());
+        assert!(evicted.iter().any(|k| *k >= 100));
+
+        let mut cache = TlfuCore::new(20);
+        cache.set((1..=20).map(|k| (k, 0)).collect());
+        std::thread::sleep(Duration::from_millis(20));
+        cache.set_insertion_grace(Duration::from_millis(10).as_nanos() as u64);
+        // preview spares the same keys
+        let mut preview = cache.preview_evictions((100..105).collect());
+        let mut evicted = cache.set(burst.clone());
+        preview.sort_unstable();
+        evicted.sort_unstable();
+        assert_eq!(preview, evicted);
+        assert_eq!(evicted.len(), 5);
+        assert!(evicted.iter().all(|k| *k <= 20));
+        assert!((100..105).all(|k| cache.entries.contains_key(&k)));
+        assert!(cache.check_consistency().is_ok());
+
+        // with every key graced, eviction still respects capacity
+        cache.set_insertion_grace(Duration::from_secs(60 * 60).as_nanos() as u64);
+        let evicted = cache.set((200..210).map(|k| (k, 0)).collect());
+        assert_eq!(evicted.len(), 10);
+        assert_eq!(cache.len(), 20);
+        assert!(cache.check_consistency().is_ok());
     }
 
-    /// Returns debugging information about the cache state.
-    #[must_use]
-    pub fn debug_info(&self) -> DebugInfo {
-        self.policy.debug_info()
+    #[test]
+    fn test_segment_hit_stats() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=50).map(|k| (k, 0)).collect());
+        assert_eq!(cache.segment_hit_stats(), (0, 0, 0));
+
+        // 50 is the window's only key, 1 sits in probation until promoted
+        cache.access(vec![50, 1, 1, 1, 999]).unwrap();
+        assert_eq!(cache.segment_hit_stats(), (1, 1, 2));
     }
 
-    /// Returns all keys currently stored in the cache.
-    #[must_use]
-    pub fn keys(&self) -> Vec<u64> {
-        self.entries.keys().copied().collect()
+    #[test]
+    fn test_warm_fill() {
+        let keys: Vec<(u64, i64)> = (1..=150).map(|k| (k, 0)).collect();
+
+        let mut cold = TlfuCore::new(100);
+        cold.set(keys.clone());
+        let resident = (51..=150).filter(|k| cold.entries.contains_key(k)).count();
+        assert!(resident < 100);
+
+        let mut warm = TlfuCore::new(100);
+        warm.warm_fill(keys);
+        assert_eq!(warm.len(), 100);
+        assert!((51..=150).all(|k| warm.entries[&k].policy_list_id == 2));
+        assert_eq!(warm.policy.sketch.estimate(1), 1);
+        assert!(warm.check_consistency().is_ok());
+
+        // resident keys are updated, and a full cache takes no new keys
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        warm.warm_fill(vec![(200, 0), (150, hour), (51, -1)]);
+        assert!(warm.entries[&150].expire > 0);
+        assert!(!warm.entries.contains_key(&51));
+        assert!(warm.entries.contains_key(&200));
+        assert_eq!(warm.len(), 100);
+        assert!(warm.check_consistency().is_ok());
     }
 
-    /// Sets multiple entries with panic safety for Python FFI.
-    pub fn set_with_error(&mut self, entries: Vec<(u64, i64)>) -> PyResult<Vec<u64>> {
-        use std::panic::AssertUnwindSafe;
-        catch_panic(AssertUnwindSafe(|| self.set(entries)), "set")
+    #[test]
+    fn test_warm_fill_ttl_like_set() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as u64;
+        let mut cache = TlfuCore::new(100);
+        cache.set_max_ttl(hour);
+        cache.set_promote_long_ttl_threshold(hour / 2);
+        cache.warm_fill(vec![(1, 2 * hour as i64), (2, 60), (3, -5), (4, -1)]);
+
+        // capped by max_ttl, and long lived so it skips the window
+        assert_eq!(cache.entries[&1].original_ttl, hour);
+        assert_eq!(cache.entries[&1].policy_list_id, 2);
+        // short lived keys share the 1-slot window, the older one spills over
+        assert_eq!(cache.entries[&2].policy_list_id, 2);
+        assert_eq!(cache.entries[&3].policy_list_id, 1);
+        // only -1 removes, other negative TTLs are taken as their magnitude
+        assert_eq!(cache.entries[&3].original_ttl, 5);
+        assert!(!cache.entries.contains_key(&4));
+        assert_eq!(cache.policy.sketch.estimate(4), 0);
+        assert!(cache.check_consistency().is_ok());
+
+        cache.warm_fill(vec![(1, -1)]);
+        assert!(!cache.entries.contains_key(&1));
+        assert_eq!(cache.policy.sketch.estimate(1), 1);
     }
 
-    /// Marks entries as accessed with panic safety for Python FFI.
-    pub fn access_with_error(&mut self, keys: Vec<u64>) -> PyResult<()> {
-        use std::panic::AssertUnwindSafe;
-        catch_panic(AssertUnwindSafe(|| self.access(keys)), "access")
+    #[test]
+    fn test_warm_fill_window_capacity() {
+        let mut cache = TlfuCore::new(100);
+        cache.set_promote_long_ttl_threshold(Duration::from_secs(60 * 60).as_nanos() as u64);
+        cache.warm_fill((1..=50).map(|k| (k, 60)).collect());
+
+        // short lived keys spill from the 1-slot window into probation
+        let info = cache.debug_info();
+        assert_eq!(cache.len(), 50);
+        assert!(info.window_len <= cache.geometry()["window"]);
+        assert_eq!(info.probation_len, 49);
+        assert!(cache.check_consistency().is_ok());
     }
 
-    /// Advances the timer wheel with panic safety for Python FFI.
-    pub fn advance_with_error(&mut self) -> PyResult<Vec<u64>> {
-        use std::panic::AssertUnwindSafe;
-        catch_panic(AssertUnwindSafe(|| self.advance()), "advance")
+    #[test]
+    fn test_warm_fill_weight_writes() {
+        let mut cache = TlfuCore::new(100);
+        cache.set_weight_writes(false);
+        cache.warm_fill(vec![(1, 0)]);
+        assert!(cache.entries.contains_key(&1));
+        assert_eq!(cache.policy.sketch.estimate(1), 0);
+
+        // like set, an update doesn't count as a write either way
+        cache.set_weight_writes(true);
+        cache.warm_fill(vec![(1, 0), (2, 0)]);
+        assert_eq!(cache.policy.sketch.estimate(1), 0);
+        assert_eq!(cache.policy.sketch.estimate(2), 1);
     }
-}
 
-/// Supplemental hash function for Python hash values.
-///
-/// Python's hash function returns `i64` which can be negative or weakly distributed.
-/// This function applies the MurmurHash3 finalizer to improve distribution and prevent hash DoS.
-///
-/// # Arguments
-///
-/// * `h` - Python's hash value (an i64)
-///
-/// # Returns
-///
-/// A well-distributed u64 hash value
-///
-/// # Examples
-///
-/// ```ignore
-/// let python_hash = 12345i64;
-/// let improved_hash = spread(python_hash);
-/// ```
-#[pyfunction]
-#[must_use]
-pub fn spread(h: i64) -> u64 {
-    // Convert i64 to u64 preserving bit pattern
-    let mut z = u64::from_ne_bytes(h.to_ne_bytes());
+    #[test]
+    fn test_scheduled_count() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, hour), (2, hour), (3, 0), (4, 0)]);
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.scheduled_count(), 2);
 
-    // Apply MurmurHash3 finalizer for better distribution
-    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
-    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
-    z ^= z >> 31;
+        // immortal to expiring and back
+        cache.set(vec![(3, hour), (1, 0)]);
+        assert_eq!(cache.scheduled_count(), 2);
+        cache.remove(2).unwrap();
+        assert_eq!(cache.scheduled_count(), 1);
+    }
 
-    z
-}
+    #[test]
+    fn test_ttl_distribution() {
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        assert_eq!(cache.ttl_distribution(), vec![0; 5]);
+        cache.set(vec![
+            (1, second),
+            (2, 2 * second),
+            (3, 120 * second),
+            (4, 5000 * second),
+            (5, 0),
+        ]);
+        assert_eq!(cache.ttl_distribution(), vec![2, 1, 1, 0, 0]);
+        assert_eq!(
+            cache.ttl_distribution().iter().sum::<usize>(),
+            cache.scheduled_count()
+        );
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use rand::Rng;
+        let levels = TlfuCore::with_wheel_levels(100, vec![64, 64, 32, 4, 4, 1]).unwrap();
+        assert_eq!(levels.ttl_distribution().len(), 6);
+    }
 
     #[test]
-    fn test_set_operations() {
-        let mut cache = TlfuCore::new(1000);
+    fn test_ttl_histogram() {
+        let second = Duration::from_secs(1).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![
+            (1, 5 * second),
+            (2, 30 * second),
+            (3, 90 * second),
+            (4, 4000 * second),
+            (5, 0),
+            (6, 0),
+            (7, 1),
+        ]);
+        std::thread::sleep(Duration::from_millis(1));
+        let minute = 60 * second as u64;
+        assert_eq!(
+            cache
+                .ttl_histogram(vec![10 * second as u64, minute, 3600 * second as u64])
+                .unwrap(),
+            vec![1, 1, 1, 1, 2]
+        );
+        assert_eq!(cache.ttl_histogram(vec![]).unwrap(), vec![4, 2]);
+        assert!(cache.ttl_histogram(vec![minute, minute]).is_err());
+        assert!(cache.ttl_histogram(vec![minute, 1]).is_err());
+    }
 
-        // Add entries
-        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
-        let mut keys: Vec<_> = cache.entries.keys().copied().collect();
-        keys.sort_unstable();
-        assert_eq!(keys, vec![1, 2, 3]);
+    #[test]
+    fn test_tie_breaker() {
+        for prefer_candidate in [false, true] {
+            let mut cache = TlfuCore::new(100);
+            cache.set_tie_breaker(prefer_candidate);
+            cache.set((1..=100).map(|k| (k, 0)).collect());
+            // the window's key becomes the candidate once 1000 pushes it out
+            let candidate = cache.keys_ordered()[0];
+            let victim = cache.policy.eviction_order()[0];
+            assert_eq!(
+                cache.policy.sketch.estimate(candidate),
+                cache.policy.sketch.estimate(victim)
+            );
 
-        // Remove entry 3, add entry 4, re-add entry 3
-        cache.set(vec![(3, -1), (4, 0), (3, 0)]);
-        keys = cache.entries.keys().copied().collect();
-        keys.sort_unstable();
-        assert_eq!(keys, vec![1, 2, 3, 4]);
+            let evicted = cache.set(vec![(1000, 0)]);
+            let loser = if prefer_candidate { victim } else { candidate };
+            assert_eq!(evicted, vec![loser]);
+            assert!(!cache.entries.contains_key(&loser));
+        }
+    }
 
-        // Remove entry 3, keep entry 4
-        cache.set(vec![(3, -1), (4, 0)]);
-        keys = cache.entries.keys().copied().collect();
-        keys.sort_unstable();
-        assert_eq!(keys, vec![1, 2, 4]);
+    #[test]
+    fn test_adjust_frequency() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=100).map(|k| (k, 0)).collect());
+        let candidate = cache.keys_ordered()[0];
+        let victim = cache.policy.eviction_order()[0];
+
+        // a negative delta doesn't touch the sketch
+        cache.adjust_frequency(candidate, -5);
+        assert_eq!(
+            cache.policy.sketch.estimate(candidate),
+            cache.policy.sketch.estimate(victim)
+        );
+
+        // the candidate would lose the tie, the boost lets it win
+        cache.adjust_frequency(candidate, 3);
+        assert_eq!(cache.set(vec![(1000, 0)]), vec![victim]);
+        assert!(cache.entries.contains_key(&candidate));
     }
 
     #[test]
-    fn test_remove_operation() {
-        let mut cache = TlfuCore::new(1000);
-        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
-        cache.remove(2);
+    fn test_set_protected_capacity() {
+        Python::initialize();
+        let mut cache = TlfuCore::new(100);
+        assert_eq!(cache.protected_capacity(), 79);
+        cache.set((1..=100).map(|k| (k, 0)).collect());
+        cache.access((1..=100).collect()).unwrap();
+        // promotions overflow protected until the next insert demotes
+        cache.set(vec![(1000, 0)]);
+        assert_eq!(cache.debug_info().protected_len, 79);
 
-        let mut keys: Vec<_> = cache.entries.keys().copied().collect();
-        keys.sort_unstable();
-        assert_eq!(keys, vec![1, 3]);
+        cache.set_protected_capacity(10).unwrap();
+        assert_eq!(cache.protected_capacity(), 10);
+        assert_eq!(cache.debug_info().protected_len, 10);
+
+        cache.access((1..=100).rev().collect()).unwrap();
+        cache.set(vec![(1001, 0)]);
+        assert_eq!(cache.debug_info().protected_len, 10);
+        assert_eq!(cache.protected_capacity(), 10);
+        assert_eq!(cache.len(), 100);
+        assert!(cache.check_consistency().is_ok());
+
+        assert!(cache.set_protected_capacity(100).is_err());
+        assert!(cache.set_protected_capacity(99).is_ok());
     }
 
     #[test]
-    fn test_bounded_cache_size() {
-        for size in [1, 2, 3] {
-            let mut cache = TlfuCore::new(size);
-            cache.set(vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
-            assert_eq!(cache.len(), size);
+    fn test_set_window_capacity() {
+        Python::initialize();
+        let mut cache = TlfuCore::new(100);
+        assert!(cache.set_window_capacity(0).is_err());
+        assert!(cache.set_window_capacity(100).is_err());
 
-            cache.access(vec![1]);
-            cache.set(vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 0)]);
-            assert_eq!(cache.len(), size);
+        cache.set((1..=100).map(|k| (k, 0)).collect());
+        cache.set_window_capacity(30).unwrap();
+        assert_eq!(cache.protected_capacity(), 50);
+        // a growing window fills up with new keys that win admission
+        cache
+            .access((101..=140).chain(101..=140).collect())
+            .unwrap();
+        cache.set((101..=140).map(|k| (k, 0)).collect());
+        assert_eq!(cache.debug_info().window_len, 30);
+        cache.set_window_capacity(10).unwrap();
+        assert_eq!(cache.debug_info().window_len, 10);
+        assert_eq!(cache.protected_capacity(), 70);
+        assert_eq!(cache.len(), 100);
+
+        cache.freeze_window(true);
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..20000 {
+            let key = rng.random_range(0..500);
+            cache.set(vec![(key, 0)]);
+            cache.access(vec![rng.random_range(0..50)]).unwrap();
+            assert_eq!(cache.geometry()["window"], 10);
         }
+        assert!(cache.debug_info().window_len <= 10);
+        assert!(cache.check_consistency().is_ok());
     }
 
     #[test]
-    fn test_spread_hash_function() {
-        let mut rng = rand::rng();
+    fn test_key_cursor() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=25).map(|k| (k, 0)).collect());
+        let mut cursor = cache.key_cursor();
+        assert_eq!(cursor.remaining(), 25);
 
-        for _ in 0..100_000 {
-            let k = rng.random_range(-i64::MAX..i64::MAX);
-            let _hashed = spread(k);
-            // Just verify it doesn't panic
+        // the snapshot is unaffected by later changes
+        cache.set(vec![(100, 0)]);
+        cache.remove(1).unwrap();
+
+        let mut keys = Vec::new();
+        loop {
+            let batch = cursor.next_batch(10);
+            if batch.is_empty() {
+                break;
+            }
+            assert!(batch.len() <= 10);
+            keys.extend(batch);
         }
+        keys.sort();
+        assert_eq!(keys, (1..=25).collect::<Vec<u64>>());
+        assert_eq!(cursor.remaining(), 0);
+        assert!(cursor.next_batch(0).is_empty());
     }
 
     #[test]
-    fn test_clear() {
-        let mut cache = TlfuCore::new(100);
-        cache.set(vec![(1, 0), (2, 0), (3, 0)]);
-        assert!(cache.len() > 0);
+    fn test_full_reset() {
+        let mut cache = TlfuCore::new(1000);
+        cache.set((1..=1000).map(|k| (k, 0)).collect());
+        cache.access(vec![7; 20]).unwrap();
+        cache.set_protected_capacity(10).unwrap();
 
         cache.clear();
+        assert!(cache.policy.sketch.estimate(7) > 0);
+
+        cache.full_reset();
         assert_eq!(cache.len(), 0);
+        assert_eq!(cache.policy.sketch.estimate(7), 0);
+        assert_eq!(cache.sketch_additions(), 0);
+        assert_eq!(cache.geometry(), TlfuCore::new(1000).geometry());
+        assert_eq!(cache.current_window_ratio(), 0.01);
+
+        cache.set(vec![(1, 0)]);
+        assert_eq!(cache.len(), 1);
+        assert!(cache.check_consistency().is_ok());
     }
 
     #[test]
-    fn test_keys() {
+    fn test_set_reporting_admission() {
+        // rejected by the frequency gate
         let mut cache = TlfuCore::new(100);
-        let entries = vec![(1, 0), (2, 0), (3, 0)];
-        cache.set(entries.clone());
+        cache.set_min_admission_frequency(2);
+        cache.access(vec![2]).unwrap();
+        assert_eq!(cache.set_reporting_admission(vec![(1, 0), (2, 0)]), vec![1]);
+        assert_eq!(cache.keys(), vec![2]);
+
+        // evicted again within the same batch, while a removal isn't reported
+        let mut cache = TlfuCore::new(10);
+        cache.set((1..=10).map(|k| (k, 0)).collect());
+        cache.access((1..=10).collect()).unwrap();
+        let batch: Vec<(u64, i64)> = (100..105).map(|k| (k, 0)).collect();
+        let mut dropped =
+            cache.set_reporting_admission(batch.into_iter().chain([(3, -1)]).collect());
+        dropped.sort();
+        assert!(!dropped.is_empty());
+        assert!(dropped.iter().all(|k| (100..105).contains(k)));
+        assert!(dropped.iter().all(|k| !cache.entries.contains_key(k)));
+        assert!(!cache.entries.contains_key(&3));
+    }
+
+    #[test]
+    fn test_with_sketch_block_size() {
+        Python::initialize();
+        assert!(TlfuCore::with_sketch_block_size(1000, 12).is_err());
+        let mut cache = TlfuCore::with_sketch_block_size(1000, 16).unwrap();
+        assert_eq!(cache.sketch_table_len(), 1024);
+        cache.access(vec![7; 5]).unwrap();
+        assert_eq!(cache.policy.sketch.estimate(7), 5);
+    }
+
+    #[test]
+    fn test_live_len() {
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, 1), (2, 1), (3, hour), (4, 0)]);
+        std::thread::sleep(Duration::from_millis(1));
+
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.live_len(), 2);
+        assert_eq!(bound(&mut cache, TlfuCore::purge_expired).unwrap().len(), 2);
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.live_len(), 2);
+    }
+
+    #[test]
+    fn test_refresh_ahead() {
+        use pyo3::types::PyDict;
+
+        Python::initialize();
+        let ttl = Duration::from_millis(200).as_nanos() as i64;
+        let hour = Duration::from_secs(60 * 60).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, ttl), (2, ttl), (3, hour), (4, 0)]);
+        cache.access(vec![1, 1, 1, 3, 3, 3, 4, 4, 4]).unwrap();
+
+        let globals = Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals.set_item("seen", Vec::<u64>::new()).unwrap();
+            let callback = py
+                .eval(c"lambda key: seen.append(key)", Some(&globals), None)
+                .unwrap();
+            assert!(
+                cache
+                    .set_refresh_ahead(Some(callback.clone().unbind()), 4, 1.5)
+                    .is_err()
+            );
+            cache
+                .set_refresh_ahead(Some(callback.unbind()), 4, 0.5)
+                .unwrap();
+            globals.unbind()
+        });
+
+        std::thread::sleep(Duration::from_millis(120));
+        let expire = cache.entries[&1].expire;
+        // 1 is hot and near expiry, 2 is cold, 3 has most of its TTL left
+        // and 4 never expires
+        cache.access(vec![1, 1, 2, 3, 4]).unwrap();
+        assert_eq!(cache.entries[&1].expire, expire);
+
+        let seen: Vec<u64> = Python::attach(|py| {
+            globals
+                .bind(py)
+                .get_item("seen")
+                .unwrap()
+                .unwrap()
+                .extract()
+                .unwrap()
+        });
+        assert_eq!(seen, vec![1]);
+    }
+
+    #[test]
+    fn test_refresh_ahead_after_extend() {
+        use pyo3::types::PyDict;
+
+        Python::initialize();
+        let ttl = Duration::from_millis(400).as_nanos() as i64;
+        let mut cache = TlfuCore::new(100);
+        cache.set(vec![(1, ttl)]);
+        cache.access(vec![1, 1, 1]).unwrap();
+
+        let globals = Python::attach(|py| {
+            let globals = PyDict::new(py);
+            globals.set_item("seen", Vec::<u64>::new()).unwrap();
+            let callback = py
+                .eval(c"lambda key: seen.append(key)", Some(&globals), None)
+                .unwrap();
+            cache
+                .set_refresh_ahead(Some(callback.unbind()), 1, 0.3)
+                .unwrap();
+            globals.unbind()
+        });
+        let seen = |globals: &Py<PyDict>| -> Vec<u64> {
+            Python::attach(|py| {
+                globals
+                    .bind(py)
+                    .get_item("seen")
+                    .unwrap()
+                    .unwrap()
+                    .extract()
+                    .unwrap()
+            })
+        };
+
+        std::thread::sleep(Duration::from_millis(200));
+        cache.access_extend(vec![(1, ttl)]);
+        // the entry is now older than the TTL it has left, which alone
+        // doesn't bring it near expiry
+        std::thread::sleep(Duration::from_millis(240));
+        cache.access(vec![1]).unwrap();
+        assert!(seen(&globals).is_empty());
+
+        std::thread::sleep(Duration::from_millis(60));
+        cache.access(vec![1]).unwrap();
+        assert_eq!(seen(&globals), vec![1]);
+    }
+
+    #[test]
+    fn test_new_small() {
+        let mut cache = TlfuCore::new_small(5);
+        assert_eq!(cache.sketch_table_len(), 0);
+        assert_eq!(cache.current_window_ratio(), 1.0);
 
+        cache.set((1..=5).map(|k| (k, 0)).collect());
+        cache.access(vec![1, 2]).unwrap();
+        // plain LRU, no admission contest
+        assert_eq!(cache.set(vec![(6, 0), (7, 0)]), vec![3, 4]);
+        assert_eq!(cache.set(vec![(8, 0)]), vec![5]);
         let mut keys = cache.keys();
-        keys.sort_unstable();
-        assert_eq!(keys, vec![1, 2, 3]);
+        keys.sort();
+        assert_eq!(keys, vec![1, 2, 6, 7, 8]);
+
+        assert_eq!(cache.remove(1).unwrap(), Some(1));
+        cache.set(vec![(9, 1)]);
+        std::thread::sleep(Duration::from_millis(1));
+        assert_eq!(bound(&mut cache, TlfuCore::purge_expired).unwrap(), vec![9]);
+        assert_eq!(cache.len(), 4);
+        assert!(cache.check_consistency().is_ok());
+
+        // larger sizes get a regular cache
+        assert_eq!(TlfuCore::new_small(1000).sketch_table_len(), 1024);
+    }
+
+    #[test]
+    fn test_full_reset_small() {
+        let mut cache = TlfuCore::new_small(10);
+        let geometry = cache.geometry();
+        cache.set((1..=10).map(|k| (k, 0)).collect());
+
+        cache.full_reset();
+        assert_eq!(cache.geometry(), geometry);
+        // still a plain LRU: the least recently used key goes
+        cache.set((1..=10).map(|k| (k, 0)).collect());
+        cache.access(vec![1]).unwrap();
+        assert_eq!(cache.set(vec![(11, 0)]), vec![2]);
+        assert!(cache.check_consistency().is_ok());
+    }
+
+    #[test]
+    fn test_expiry_penalty() {
+        for penalty in [0, 2] {
+            let mut cache = TlfuCore::new(100);
+            cache.set_expiry_penalty(penalty);
+            // key 1 keeps being written and expiring unread
+            for _ in 0..8 {
+                cache.set(vec![(1, 1)]);
+                assert_eq!(bound(&mut cache, TlfuCore::purge_expired).unwrap(), vec![1]);
+                assert!(!cache.entries.contains_key(&1));
+            }
+            // key 2 is written once and read
+            cache.set(vec![(2, 0)]);
+            cache.access(vec![2, 2, 2]).unwrap();
+            let (churny, read) = (
+                cache.policy.sketch.estimate(1),
+                cache.policy.sketch.estimate(2),
+            );
+            if penalty == 0 {
+                assert_eq!(churny, 8);
+                assert!(churny > read);
+            } else {
+                assert_eq!(churny, 2);
+                assert_eq!(cache.expired_unread[&1], 8);
+                assert!(churny < read);
+            }
+        }
+
+        // an expiry after a read clears the history
+        let mut cache = TlfuCore::new(100);
+        cache.set_expiry_penalty(1);
+        cache.expired_unread.insert(1, 5);
+        cache.set(vec![(1, 0)]);
+        cache.access(vec![1]).unwrap();
+        cache.entries.get_mut(&1).unwrap().expire = 1;
+        assert_eq!(bound(&mut cache, TlfuCore::purge_expired).unwrap(), vec![1]);
+        assert!(!cache.expired_unread.contains_key(&1));
+    }
+
+    #[test]
+    fn test_new_seeded() {
+        let mut seeded = TlfuCore::new_seeded(100, 42);
+        let mut plain = TlfuCore::new(100);
+        for cache in [&mut seeded, &mut plain] {
+            cache.set((1..=200).map(|k| (k, 0)).collect());
+            cache.access((1..=50).collect()).unwrap();
+            assert_eq!(cache.policy.sketch.estimate(7), 2);
+            assert!(cache.check_consistency().is_ok());
+        }
+        assert_eq!(seeded.sketch_table_len(), plain.sketch_table_len());
+    }
+
+    #[test]
+    fn test_coldest_key() {
+        let mut cache = TlfuCore::new(100);
+        assert_eq!(cache.coldest_key(), None);
+        // only the window holds keys
+        cache.set(vec![(1, 0)]);
+        assert_eq!(cache.coldest_key(), Some(1));
+
+        cache.set((2..=100).map(|k| (k, 0)).collect());
+        cache.access((1..=100).collect()).unwrap();
+        cache.set((101..=150).map(|k| (k, 0)).collect());
+        for _ in 0..3 {
+            assert_eq!(
+                cache.coldest_key(),
+                cache.policy.eviction_order().first().copied()
+            );
+            let coldest = cache.coldest_key().unwrap();
+            cache.remove(coldest).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_admission_trace() {
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=100).map(|k| (k, 0)).collect());
+        cache.set(vec![(101, 0)]);
+        assert!(cache.admission_trace().is_empty());
+
+        cache.enable_admission_trace(3);
+        cache.access(vec![102, 102]).unwrap();
+        cache.set(vec![(102, 0)]);
+        // the window's previous key loses the tie, the hot key wins
+        assert_eq!(cache.admission_trace(), vec![(1, 1, false)]);
+        cache.set((103..=110).map(|k| (k, 0)).collect());
+        let trace = cache.admission_trace();
+        assert_eq!(trace.len(), 3);
+        assert!(trace.iter().all(|&(c, v, admitted)| admitted == (c > v)));
+
+        cache.enable_admission_trace(0);
+        assert!(cache.admission_trace().is_empty());
+        cache.set(vec![(111, 0)]);
+        assert!(cache.admission_trace().is_empty());
+    }
+
+    #[test]
+    fn test_flush_on_evict() {
+        use pyo3::types::{PyDict, PyList};
+
+        Python::initialize();
+        let mut cache = TlfuCore::new(100);
+        cache.set((1..=100).map(|k| (k, 0)).collect());
+        let victim = cache.coldest_key().unwrap();
+        cache.mark_dirty(victim);
+        cache.mark_dirty(50);
+        cache.mark_clean(50);
+        cache.mark_dirty(1000);
+        assert_eq!(cache.dirty_keys(), vec![victim]);
+        // the window's key wins against the dirty victim
+        cache.adjust_frequency(cache.keys_ordered()[0], 3);
+
+        Python::attach(|py| {
+            let cache = Bound::new(py, cache).unwrap();
+            let globals = PyDict::new(py);
+            let flushed = PyList::empty(py);
+            globals.set_item("flushed", &flushed).unwrap();
+            globals.set_item("cache", &cache).unwrap();
+            // the cache is released by the time the callback runs
+            let callback = py
+                .eval(
+                    c"lambda k: flushed.append((k, cache.len()))",
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            cache
+                .borrow_mut()
+                .set_flush_on_evict(Some(callback.unbind()));
+            let flushed_keys = || -> Vec<u64> {
+                let flushed: Vec<(u64, usize)> = flushed.extract().unwrap();
+                flushed.into_iter().map(|(key, _)| key).collect()
+            };
+
+            TlfuCore::py_set(&cache, vec![(500, 0)]);
+            assert!(!cache.borrow().entries.contains_key(&victim));
+            TlfuCore::py_set(&cache, (501..=520).map(|k| (k, 0)).collect());
+            assert_eq!(
+                flushed.extract::<Vec<(u64, usize)>>().unwrap(),
+                vec![(victim, 100)]
+            );
+
+            // expiring a dirty key flushes it too
+            TlfuCore::py_set(&cache, vec![(600, 1)]);
+            cache.borrow_mut().mark_dirty(600);
+            std::thread::sleep(Duration::from_millis(1));
+            assert_eq!(TlfuCore::purge_expired(&cache).unwrap(), vec![600]);
+            assert_eq!(flushed_keys(), vec![victim, 600]);
+
+            // evict_coldest evicts, explicit removals don't flush
+            let keys = cache.borrow().keys_ordered();
+            for key in keys {
+                cache.borrow_mut().mark_dirty(key);
+            }
+            let coldest = TlfuCore::py_evict_coldest(&cache, 2);
+            assert_eq!(flushed_keys()[2..], coldest);
+            let keys = cache.borrow().keys_ordered();
+            assert!(cache.borrow_mut().remove(keys[0]).unwrap().is_some());
+            TlfuCore::py_set(&cache, vec![(keys[1], -1)]);
+            assert_eq!(flushed_keys().len(), 4);
+            assert_eq!(cache.borrow().dirty_keys().len(), cache.borrow().len());
+        });
+    }
+
+    #[test]
+    fn test_victim_priority() {
+        let mut residents = Vec::new();
+        for order in [VictimOrder::ProbationFirst, VictimOrder::WindowFirst] {
+            let mut cache = TlfuCore::new(100);
+            cache.set_victim_priority(order);
+            cache.set((1..=100).map(|k| (k, 0)).collect());
+            for _ in 0..3 {
+                cache.access((1..=50).collect()).unwrap();
+            }
+            // a scan of keys seen twice, so some of them win admission
+            cache.access((1000..1200).collect()).unwrap();
+            cache.set((1000..1200).map(|k| (k, 0)).collect());
+            assert_eq!(cache.len(), 100);
+            assert!(cache.check_consistency().is_ok());
+            assert_eq!(
+                cache.coldest_key(),
+                cache.policy.eviction_order().first().copied()
+            );
+
+            let mut keys = cache.keys();
+            keys.sort();
+            residents.push(keys);
+        }
+        assert_ne!(residents[0], residents[1]);
     }
 }
@@ -172,6 +172,40 @@ impl BloomFilter {
         })
     }
 
+    /// Returns the number of bits in the filter.
+    #[must_use]
+    pub fn bit_capacity(&self) -> usize {
+        self.bits_mask + 1
+    }
+
+    /// Returns the number of hash functions applied per key.
+    #[must_use]
+    pub fn num_hashes(&self) -> usize {
+        self.slice_count
+    }
+
+    /// Returns the number of additions since the last reset.
+    #[must_use]
+    pub fn current_additions(&self) -> usize {
+        self.additions
+    }
+
+    /// Estimates the current false positive probability from the bit fill ratio.
+    ///
+    /// Computed as `(set_bits / total_bits) ^ slice_count`. The value grows as
+    /// additions approach the configured insertion count, so callers can use it
+    /// to decide when to reset proactively.
+    #[must_use]
+    pub fn current_fpp(&self) -> f64 {
+        let set_bits: u64 = self
+            .bits
+            .iter()
+            .map(|word| u64::from(word.count_ones()))
+            .sum();
+        let fill = set_bits as f64 / self.bit_capacity() as f64;
+        fill.powi(self.slice_count as i32)
+    }
+
     /// Resets the filter, clearing all bits and resetting the addition counter.
     fn reset(&mut self) {
         self.bits = vec![0; self.bits.len()];
@@ -183,6 +217,9 @@ impl BloomFilter {
 #[cfg(test)]
 mod tests {
 
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
     use super::BloomFilter;
 
     #[test]
@@ -242,4 +279,47 @@ mod tests {
         assert!(bf.contains(0));
         assert!(bf.contains(u64::MAX));
     }
+
+    #[test]
+    fn test_filter_geometry() {
+        let mut bf = BloomFilter::new(100, 0.001);
+        assert_eq!(bf.bit_capacity(), 2048);
+        assert_eq!(bf.num_hashes(), 14);
+        assert_eq!(bf.current_additions(), 0);
+
+        for i in 0..10 {
+            bf.put(i);
+        }
+        assert_eq!(bf.current_additions(), 10);
+        bf.reset();
+        assert_eq!(bf.current_additions(), 0);
+    }
+
+    #[test]
+    fn test_filter_current_fpp() {
+        let fpp = 0.001;
+        let mut bf = BloomFilter::new(1000, fpp);
+        assert_eq!(bf.current_fpp(), 0.0);
+
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut previous = 0.0;
+        // the filter resets itself on the insertions-th addition
+        for i in 1..1000 {
+            bf.put(rng.random());
+            if i % 100 == 0 {
+                let current = bf.current_fpp();
+                assert!(current >= previous);
+                previous = current;
+            }
+        }
+        assert!(bf.current_fpp() > 0.0);
+        assert!(bf.current_fpp() <= fpp * 2.0);
+
+        // at capacity it matches (1 - e^(-kn/m))^k for the rounded up geometry,
+        // which is at most the configured fpp
+        let (k, n, m) = (bf.num_hashes() as f64, 999.0, bf.bit_capacity() as f64);
+        let expected = (1.0 - (-k * n / m).exp()).powf(k);
+        assert!(expected <= fpp);
+        assert!((bf.current_fpp() - expected).abs() < expected * 0.1);
+    }
 }
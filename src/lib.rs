@@ -18,6 +18,10 @@ fn theine_core(m: &Bound<'_, PyModule>) -> PyResult<()> {
     let _ = log::logger();
 
     m.add_class::<core::TlfuCore>()?;
+    m.add_class::<core::KeyCursor>()?;
+    m.add_class::<lru::WindowPolicy>()?;
+    m.add_class::<sketch::CounterWidth>()?;
+    m.add_class::<tlfu::VictimOrder>()?;
     m.add_class::<filter::BloomFilter>()?;
     m.add_function(wrap_pyfunction!(core::spread, m)?)?;
     Ok(())
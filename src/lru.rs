@@ -5,8 +5,23 @@
 use crate::metadata::{Entry, List};
 use anyhow::Result;
 use dlv_list::Index;
+use pyo3::prelude::pyclass;
 use std::collections::HashMap;
 
+/// Recency policy used by the admission window.
+///
+/// - `Lru`: accessed entries are moved to the front of the window
+/// - `Fifo`: accessed entries only get their reference bit set, and a
+///   referenced entry reaching the tail gets a second chance instead of
+///   leaving the window
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum WindowPolicy {
+    #[default]
+    Lru,
+    Fifo,
+}
+
 /// Least Recently Used cache policy implementation.
 ///
 /// This policy maintains a doubly-linked list where newly accessed items
@@ -15,9 +30,10 @@ use std::collections::HashMap;
 /// # Note
 ///
 /// Policy list ID for LRU entries is `1`.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Lru {
     pub list: List<u64>,
+    pub policy: WindowPolicy,
 }
 
 impl Lru {
@@ -37,6 +53,7 @@ impl Lru {
         log::debug!("LRU created with maxsize={}", maxsize);
         Self {
             list: List::new(maxsize),
+            policy: WindowPolicy::Lru,
         }
     }
 
@@ -50,16 +67,53 @@ impl Lru {
         let index = self.list.insert_front(key);
         entry.policy_list_index = Some(index);
         entry.policy_list_id = 1;
+        entry.referenced = false;
     }
 
-    /// Marks an entry as accessed by moving it to the front of the list.
+    /// Marks an entry as accessed.
+    ///
+    /// In LRU mode the entry is moved to the front of the list, in FIFO mode
+    /// only its reference bit is set.
     ///
     /// # Arguments
     ///
     /// * `index` - The current position of the entry in the list
+    /// * `entry` - The entry metadata of the accessed key
     #[inline]
-    pub fn access(&mut self, index: Index<u64>) {
-        self.list.touch(index);
+    pub fn access(&mut self, index: Index<u64>, entry: &mut Entry) {
+        match self.policy {
+            WindowPolicy::Lru => self.list.touch(index),
+            WindowPolicy::Fifo => entry.referenced = true,
+        }
+    }
+
+    /// Removes and returns the tail key of the list.
+    ///
+    /// In FIFO mode, referenced entries found at the tail get a second chance:
+    /// their reference bit is cleared and they are moved to the front.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - Mutable reference to the cache entries map
+    ///
+    /// # Returns
+    ///
+    /// `Some(key)` of the removed entry, `None` if the list is empty
+    pub fn pop_tail(&mut self, entries: &mut HashMap<u64, Entry>) -> Option<u64> {
+        if self.policy == WindowPolicy::Fifo {
+            while let Some(&key) = self.list.tail() {
+                match entries.get_mut(&key) {
+                    Some(entry) if entry.referenced => {
+                        entry.referenced = false;
+                        if let Some(index) = entry.policy_list_index {
+                            self.list.touch(index);
+                        }
+                    }
+                    _ => break,
+                }
+            }
+        }
+        self.list.pop_tail()
     }
 
     /// Returns the current number of entries in the list.
@@ -71,16 +125,19 @@ impl Lru {
 
     /// Removes an entry from the LRU list.
     ///
+    /// Clears the entry's position so a stale index can't be reused.
+    ///
     /// # Arguments
     ///
-    /// * `entry` - The entry to remove
+    /// * `entry` - The entry to remove (modified in place)
     ///
     /// # Returns
     ///
     /// `Ok(())` if removal succeeded, `Err` if the entry's position was missing
-    pub fn remove(&mut self, entry: &Entry) -> Result<()> {
+    pub fn remove(&mut self, entry: &mut Entry) -> Result<()> {
         entry
             .policy_list_index
+            .take()
             .ok_or_else(|| {
                 let err = anyhow::anyhow!(
                     "LRU remove: missing policy_list_index for entry, this indicates a bug"
@@ -88,7 +145,10 @@ impl Lru {
                 log::error!("{}", err);
                 err
             })
-            .map(|index| self.list.remove(index))
+            .map(|index| {
+                self.list.remove(index);
+                entry.policy_list_id = 0;
+            })
     }
 }
 
@@ -102,7 +162,7 @@ impl Lru {
 ///
 /// - `2`: Probation list (80% capacity)
 /// - `3`: Protected list (20% capacity)
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Slru {
     pub probation: List<u64>,
     pub protected: List<u64>,
@@ -146,6 +206,20 @@ impl Slru {
         entry.policy_list_id = 2;
     }
 
+    /// Inserts a key straight into the protected list.
+    ///
+    /// Only used to restore exported state; normal inserts start in probation.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key to insert
+    /// * `entry` - The entry metadata to update with position information
+    pub fn insert_protected(&mut self, key: u64, entry: &mut Entry) {
+        let index = self.protected.insert_front(key);
+        entry.policy_list_index = Some(index);
+        entry.policy_list_id = 3;
+    }
+
     /// Updates policy state when an entry is accessed.
     ///
     /// If in probation (first access), promotes to protected.
@@ -224,14 +298,16 @@ impl Slru {
 
     /// Removes an entry from either the probation or protected list.
     ///
+    /// Clears the entry's position so a stale index can't be reused.
+    ///
     /// # Arguments
     ///
-    /// * `entry` - The entry to remove
+    /// * `entry` - The entry to remove (modified in place)
     ///
     /// # Returns
     ///
     /// `Ok(())` if removal succeeded, `Err` if entry state is invalid
-    pub fn remove(&mut self, entry: &Entry) -> Result<()> {
+    pub fn remove(&mut self, entry: &mut Entry) -> Result<()> {
         let list_index = entry
             .policy_list_index
             .ok_or_else(|| {
@@ -244,22 +320,20 @@ impl Slru {
             })?;
 
         match entry.policy_list_id {
-            2 => {
-                self.probation.remove(list_index);
-                Ok(())
-            }
-            3 => {
-                self.protected.remove(list_index);
-                Ok(())
-            }
+            2 => self.probation.remove(list_index),
+            3 => self.protected.remove(list_index),
             list_id => {
                 let err = anyhow::anyhow!(
                     "SLRU remove: unexpected policy_list_id {}, this indicates a bug",
                     list_id
                 );
                 log::error!("{}", err);
-                Err(err)
+                return Err(err);
             }
         }
+
+        entry.policy_list_index = None;
+        entry.policy_list_id = 0;
+        Ok(())
     }
 }
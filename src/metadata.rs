@@ -13,6 +13,15 @@ use dlv_list::{Index, Iter, VecList};
 /// - `wheel_list_index`: Position in the timer wheel for TTL expiration
 /// - `wheel_index`: Which bucket in the timer wheel (level, slot)
 /// - `expire`: Expiration time in nanoseconds (0 = no expiration)
+/// - `referenced`: Second-chance bit, only used by the FIFO window policy
+/// - `group`: Optional group tag used for bulk invalidation
+/// - `tti`: Idle timeout in nanoseconds (0 = no idle expiration)
+/// - `last_access`: Time of the last access in nanoseconds, used with `tti`
+/// - `inserted_at`: Time the entry was last written by `set`, in nanoseconds
+/// - `original_ttl`: TTL the entry was last written with, used by `renew` (0 = none)
+/// - `accessed`: Whether the entry was accessed since it was last written
+/// - `dirty`: Whether the entry holds writes not yet flushed, see `mark_dirty`
+/// - `access_count`: Number of live accesses since the entry was created
 #[derive(Debug, Clone)]
 pub struct Entry {
     pub policy_list_id: u8,
@@ -20,6 +29,15 @@ pub struct Entry {
     pub wheel_list_index: Option<Index<u64>>,
     pub wheel_index: (u8, u8),
     pub expire: u64,
+    pub referenced: bool,
+    pub group: Option<u32>,
+    pub tti: u64,
+    pub last_access: u64,
+    pub inserted_at: u64,
+    pub original_ttl: u64,
+    pub accessed: bool,
+    pub dirty: bool,
+    pub access_count: u32,
 }
 
 impl Default for Entry {
@@ -40,15 +58,48 @@ impl Entry {
             wheel_index: (0, 0),
             expire: 0,
             policy_list_id: 0,
+            referenced: false,
+            group: None,
+            tti: 0,
+            last_access: 0,
+            inserted_at: 0,
+            original_ttl: 0,
+            accessed: false,
+            dirty: false,
+            access_count: 0,
         }
     }
+
+    /// Returns the time at which the entry expires.
+    ///
+    /// This is the earlier of the hard TTL deadline and the idle deadline
+    /// (`last_access + tti`), or 0 if the entry never expires.
+    #[inline]
+    pub fn deadline(&self) -> u64 {
+        let idle = if self.tti > 0 {
+            self.last_access.saturating_add(self.tti)
+        } else {
+            0
+        };
+        match (self.expire, idle) {
+            (0, deadline) | (deadline, 0) => deadline,
+            (expire, idle) => expire.min(idle),
+        }
+    }
+
+    /// Returns whether the entry's deadline has passed at `now`.
+    #[inline]
+    pub fn is_expired(&self, now: u64) -> bool {
+        let deadline = self.deadline();
+        deadline != 0 && deadline <= now
+    }
 }
 
 /// A doubly-linked list wrapper for managing ordered entries in the cache policy.
 ///
 /// This list maintains entries in insertion order, with O(1) operations for
 /// inserting at the front and moving entries to the front (for LRU/SLRU policies).
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct List<T> {
     pub list: VecList<T>,
     pub capacity: usize,
@@ -115,6 +166,28 @@ impl<T> List<T> {
         self.list.back()
     }
 
+    /// Returns the value at the given index, if it is still valid.
+    ///
+    /// # Arguments
+    ///
+    /// * `index` - The index to look up
+    #[inline]
+    pub fn get(&self, index: Index<T>) -> Option<&T> {
+        self.list.get(index)
+    }
+
+    /// Replaces the value at the given index in place, keeping its position.
+    ///
+    /// # Returns
+    ///
+    /// The previous value, or `None` if the index is no longer valid
+    #[inline]
+    pub fn replace(&mut self, index: Index<T>, value: T) -> Option<T> {
+        self.list
+            .get_mut(index)
+            .map(|current| std::mem::replace(current, value))
+    }
+
     /// Returns the value previous to the value at the given index.
     ///
     /// # Arguments
@@ -179,4 +252,14 @@ impl<T> List<T> {
     pub fn clear(&mut self) {
         self.list.clear();
     }
+
+    /// Releases the backing storage of an empty list.
+    ///
+    /// A non-empty list is left alone, as reallocating would invalidate the
+    /// indices held by its entries.
+    pub fn shrink_to_fit(&mut self) {
+        if self.list.is_empty() {
+            self.list = VecList::new();
+        }
+    }
 }
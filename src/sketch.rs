@@ -4,22 +4,86 @@
 //! of elements in a stream. It provides O(1) insertion and lookup with controlled
 //! memory usage and accuracy via the false positive parameter.
 
+use anyhow::Result;
+use pyo3::prelude::*;
+
+/// Default number of 64-bit words per block, one 64-byte cache line
+pub const DEFAULT_BLOCK_WORDS: usize = 8;
+// each of the 4 probes needs at least one word; a probe's 8 hash bits select
+// its word and counter, which caps a block at 16 words per probe
+const MIN_BLOCK_WORDS: usize = 4;
+const MAX_BLOCK_WORDS: usize = 64;
+
+/// Upper bound on the sample size, so huge sketches still age their counters
+pub const MAX_SAMPLE_SIZE: usize = 1 << 30;
+
 const RESET_MASK: u64 = 0x7777777777777777;
 const ONE_MASK: u64 = 0x1111111111111111;
+const WIDE_RESET_MASK: u64 = 0x7f7f7f7f7f7f7f7f;
+const WIDE_ONE_MASK: u64 = 0x0101010101010101;
+
+/// Width of the sketch's frequency counters.
+///
+/// - `Four`: 4-bit counters saturating at 15 (default)
+/// - `Eight`: 8-bit counters saturating at 255, at twice the memory, for
+///   workloads whose hot keys are accessed far more than 15 times per sample
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CounterWidth {
+    #[default]
+    Four,
+    Eight,
+}
+
+impl CounterWidth {
+    #[inline]
+    fn bits(self) -> usize {
+        match self {
+            Self::Four => 4,
+            Self::Eight => 8,
+        }
+    }
+
+    #[inline]
+    fn max(self) -> u64 {
+        (1 << self.bits()) - 1
+    }
+
+    #[inline]
+    fn reset_mask(self) -> u64 {
+        match self {
+            Self::Four => RESET_MASK,
+            Self::Eight => WIDE_RESET_MASK,
+        }
+    }
+
+    #[inline]
+    fn one_mask(self) -> u64 {
+        match self {
+            Self::Four => ONE_MASK,
+            Self::Eight => WIDE_ONE_MASK,
+        }
+    }
+}
 
 /// A Count-Min Sketch data structure for frequency estimation.
 ///
-/// This implementation uses a 2D array of 4-bit counters to track item frequencies
-/// with automatic reduction when reaching sample capacity to control accuracy.
+/// This implementation uses a 2D array of 4-bit (or, optionally, 8-bit)
+/// counters to track item frequencies with automatic reduction when reaching
+/// sample capacity to control accuracy.
 ///
 /// # Thread Safety
 ///
 /// `CountMinSketch` is not thread-safe. Wrap it in a `Mutex` when sharing across threads.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CountMinSketch {
     block_mask: usize,
+    block_words: usize,
     table: Vec<u64>,
+    width: CounterWidth,
     additions: usize,
+    reset_count: u64,
+    seed: u64,
     pub sample_size: usize,
 }
 
@@ -36,8 +100,100 @@ impl CountMinSketch {
     /// let sketch = CountMinSketch::new(10000);
     /// ```
     pub fn new(size: usize) -> Self {
+        Self::with_counter_width(size, CounterWidth::Four)
+    }
+
+    /// Like `new`, but with the given counter width.
+    ///
+    /// 8-bit counters double the table so each key still gets the same
+    /// number of counters, and keep the same sample size.
+    pub fn with_counter_width(size: usize, width: CounterWidth) -> Self {
+        Self::build(size, width, DEFAULT_BLOCK_WORDS)
+    }
+
+    /// Like `new`, but with a seed mixed into every key's hash.
+    ///
+    /// Sketches with different seeds place the same keys differently, so
+    /// keys crafted to collide in one cache don't collide in another. A seed
+    /// of 0 gives the same layout as `new`.
+    pub fn with_seed(size: usize, seed: u64) -> Self {
+        let mut sketch = Self::new(size);
+        sketch.seed = seed;
+        sketch
+    }
+
+    /// Like `with_counter_width`, but with `block_words` 64-bit words per
+    /// block instead of 8.
+    ///
+    /// All 4 counters of a key live in one block, so the block size trades
+    /// memory locality against how widely a key's counters are spread.
+    /// The table is grown to at least 8 blocks.
+    ///
+    /// # Returns
+    ///
+    /// `Err` if `block_words` isn't a power of two between 4 and 64
+    pub fn with_block_words(size: usize, width: CounterWidth, block_words: usize) -> Result<Self> {
+        if !block_words.is_power_of_two()
+            || !(MIN_BLOCK_WORDS..=MAX_BLOCK_WORDS).contains(&block_words)
+        {
+            anyhow::bail!(
+                "block size must be a power of two between {} and {} words, got {}",
+                MIN_BLOCK_WORDS,
+                MAX_BLOCK_WORDS,
+                block_words
+            );
+        }
+        Ok(Self::build(size, width, block_words))
+    }
+
+    /// Creates a sketch without a table that counts nothing.
+    ///
+    /// `add` is a no-op, `estimate` is always 0 and the sample size is never
+    /// reached, for policies that don't use frequencies at all.
+    pub fn disabled() -> Self {
+        Self {
+            additions: 0,
+            reset_count: 0,
+            sample_size: usize::MAX,
+            table: Vec::new(),
+            width: CounterWidth::Four,
+            block_mask: 0,
+            block_words: DEFAULT_BLOCK_WORDS,
+            seed: 0,
+        }
+    }
+
+    /// Lowers the number of additions after which counters are halved.
+    ///
+    /// A sample size already below `max` is kept. A disabled sketch never
+    /// resets and keeps its unbounded sample size.
+    pub fn cap_sample_size(&mut self, max: usize) {
+        if self.is_enabled() {
+            self.sample_size = self.sample_size.min(max.max(1));
+        }
+    }
+
+    /// Halves all counters now, as reaching the sample size would.
+    ///
+    /// Counts as a reset in `reset_count`. A disabled sketch is left alone.
+    pub fn decay(&mut self) {
+        if self.is_enabled() {
+            self.reset();
+        }
+    }
+
+    /// Whether the sketch counts frequencies, false if created by `disabled`.
+    #[inline]
+    pub fn is_enabled(&self) -> bool {
+        !self.table.is_empty()
+    }
+
+    fn build(size: usize, width: CounterWidth, block_words: usize) -> Self {
         let sketch_size = size.max(64);
         let counter_size = sketch_size.next_power_of_two();
+        let table_size = counter_size
+            .saturating_mul(width.bits() / 4)
+            .max(block_words * 8);
 
         if counter_size > 1 << 20 {
             log::warn!(
@@ -46,23 +202,32 @@ impl CountMinSketch {
             );
         }
 
-        let block_mask = (counter_size >> 3).saturating_sub(1);
-        let table = vec![0; counter_size];
-        let sample_size = counter_size.saturating_mul(10);
+        let block_mask = (table_size / block_words).saturating_sub(1);
+        // flooring the size keeps at least 8 blocks, a zero mask would put
+        // every key into block 0
+        debug_assert!(block_mask >= 7);
+        let table = vec![0; table_size];
+        let sample_size = sample_size_for(counter_size);
 
         log::debug!(
-            "CountMinSketch created: size={}, counter_size={}, block_mask={}, sample_size={}",
+            "CountMinSketch created: size={}, width={:?}, table_size={}, block_words={}, block_mask={}, sample_size={}",
             size,
-            counter_size,
+            width,
+            table_size,
+            block_words,
             block_mask,
             sample_size
         );
 
         Self {
             additions: 0,
+            reset_count: 0,
             sample_size,
             table,
+            width,
             block_mask,
+            block_words,
+            seed: 0,
         }
     }
 
@@ -84,10 +249,14 @@ impl CountMinSketch {
             return (0, 0);
         }
 
+        // each probe owns a quarter of the block, its low hash bits pick a
+        // word within it and the following bits a counter within the word
+        let probe_words = (self.block_words >> 2) as u64;
+        let word_bits = probe_words.trailing_zeros();
         let h = counter_hash >> (offset << 3);
         let index = block
-            .saturating_add(h & 1)
-            .saturating_add((offset as u64) << 1);
+            .saturating_add(h & (probe_words - 1))
+            .saturating_add(offset as u64 * probe_words);
 
         let table_len = self.table.len();
         let index_safe = if index as usize >= table_len {
@@ -101,11 +270,13 @@ impl CountMinSketch {
             index as usize
         };
 
-        let offset_val = (h >> 1 & 0xf) as usize;
+        let slots = 64 / self.width.bits() as u64;
+        let offset_val = (h >> word_bits & (slots - 1)) as usize;
         (index_safe, offset_val)
     }
 
-    /// Increments a counter if it hasn't reached the maximum value (15).
+    /// Increments a counter if it hasn't reached the maximum value (15, or 255
+    /// with 8-bit counters).
     ///
     /// # Arguments
     ///
@@ -126,13 +297,18 @@ impl CountMinSketch {
             return false;
         }
 
-        if offset > 15 {
-            log::warn!("CountMinSketch: offset {} out of range [0-15]", offset);
+        let bits = self.width.bits();
+        if offset >= 64 / bits {
+            log::warn!(
+                "CountMinSketch: offset {} out of range [0-{})",
+                offset,
+                64 / bits
+            );
             return false;
         }
 
-        let offset_bits = offset << 2;
-        let mask = 0xF << offset_bits;
+        let offset_bits = offset * bits;
+        let mask = self.width.max() << offset_bits;
 
         if self.table[index] & mask != mask {
             self.table[index] = self.table[index].saturating_add(1 << offset_bits);
@@ -147,9 +323,12 @@ impl CountMinSketch {
     ///
     /// * `h` - Hash value to add
     pub fn add(&mut self, h: u64) {
-        let counter_hash = rehash(h);
-        let block_hash = h;
-        let block = (block_hash & (self.block_mask as u64)).saturating_mul(8);
+        if !self.is_enabled() {
+            return;
+        }
+        let block_hash = seeded(h, self.seed);
+        let counter_hash = rehash(block_hash);
+        let block = (block_hash & (self.block_mask as u64)).saturating_mul(self.block_words as u64);
 
         let indices: [(usize, usize); 4] = [
             self.index_of(counter_hash, block, 0),
@@ -158,7 +337,10 @@ impl CountMinSketch {
             self.index_of(counter_hash, block, 3),
         ];
 
-        let added = indices.iter().any(|(idx, offset)| self.inc(*idx, *offset));
+        // Every counter must be incremented, so avoid short-circuiting `any`
+        let added = indices.iter().fold(false, |added, (idx, offset)| {
+            self.inc(*idx, *offset) | added
+        });
 
         if added {
             self.additions = self.additions.saturating_add(1);
@@ -170,17 +352,20 @@ impl CountMinSketch {
 
     /// Reduces all counters by half to prevent overflow and decay old estimates.
     fn reset(&mut self) {
+        let one_mask = self.width.one_mask();
+        let reset_mask = self.width.reset_mask();
         let count: usize = self
             .table
             .iter_mut()
             .map(|cell| {
-                let ones = (*cell & ONE_MASK).count_ones() as usize;
-                *cell = (*cell >> 1) & RESET_MASK;
+                let ones = (*cell & one_mask).count_ones() as usize;
+                *cell = (*cell >> 1) & reset_mask;
                 ones
             })
             .sum();
 
         self.additions = self.additions.saturating_sub(count >> 2) >> 1;
+        self.reset_count = self.reset_count.saturating_add(1);
         log::debug!("CountMinSketch reset: additions={}", self.additions);
     }
 
@@ -194,17 +379,18 @@ impl CountMinSketch {
     ///
     /// # Returns
     ///
-    /// The current counter value (0-15)
+    /// The current counter value (0-15, or 0-255 with 8-bit counters)
     #[inline]
     fn count(&self, h: u64, block: u64, offset: u8) -> usize {
         let (index, offset) = self.index_of(h, block, offset);
+        let bits = self.width.bits();
 
-        if index >= self.table.len() || offset > 15 {
+        if index >= self.table.len() || offset >= 64 / bits {
             return 0;
         }
 
-        let offset_bits = offset << 2;
-        ((self.table[index] >> offset_bits) & 0xF) as usize
+        let offset_bits = offset * bits;
+        ((self.table[index] >> offset_bits) & self.width.max()) as usize
     }
 
     /// Estimates the frequency of a hash value.
@@ -222,9 +408,12 @@ impl CountMinSketch {
     #[must_use]
     #[inline]
     pub fn estimate(&self, h: u64) -> usize {
-        let counter_hash = rehash(h);
-        let block_hash = h;
-        let block = (block_hash & (self.block_mask as u64)).saturating_mul(8);
+        if !self.is_enabled() {
+            return 0;
+        }
+        let block_hash = seeded(h, self.seed);
+        let counter_hash = rehash(block_hash);
+        let block = (block_hash & (self.block_mask as u64)).saturating_mul(self.block_words as u64);
 
         [
             self.count(counter_hash, block, 0),
@@ -238,6 +427,61 @@ impl CountMinSketch {
         .unwrap_or(0)
     }
 
+    /// Zeroes every counter and the addition count, keeping the table size.
+    pub fn clear(&mut self) {
+        self.table.fill(0);
+        self.additions = 0;
+    }
+
+    /// Returns the number of 64-bit counter words in the table.
+    #[must_use]
+    pub fn table_size(&self) -> usize {
+        self.table.len()
+    }
+
+    /// Returns the largest value a counter can hold.
+    #[must_use]
+    pub fn max_count(&self) -> usize {
+        self.width.max() as usize
+    }
+
+    /// Returns the number of additions counted since the last reset.
+    #[must_use]
+    pub fn additions(&self) -> usize {
+        self.additions
+    }
+
+    /// Returns the fraction of counters stuck at their maximum value.
+    ///
+    /// Saturated counters can't tell hot keys apart, so a high fraction
+    /// means the counters are too narrow or the sample size too large for
+    /// the workload. Scans the whole table, 0.0 for a disabled sketch.
+    #[must_use]
+    pub fn saturation(&self) -> f64 {
+        if !self.is_enabled() {
+            return 0.0;
+        }
+        let bits = self.width.bits();
+        let max = self.width.max();
+        let saturated: usize = self
+            .table
+            .iter()
+            .map(|&word| {
+                (0..64)
+                    .step_by(bits)
+                    .filter(|&shift| (word >> shift) & max == max)
+                    .count()
+            })
+            .sum();
+        saturated as f64 / (self.table.len() * (64 / bits)) as f64
+    }
+
+    /// Returns how many times the sketch has been reset over its lifetime.
+    #[must_use]
+    pub fn reset_count(&self) -> u64 {
+        self.reset_count
+    }
+
     #[cfg(test)]
     fn table_counters(&self) -> Vec<Vec<i32>> {
         self.table
@@ -256,6 +500,25 @@ fn rehash(h: u64) -> u64 {
     h ^ (h >> 31)
 }
 
+/// Mixes a non-zero seed into a key's hash, 0 leaves it unchanged.
+///
+/// The xor-shift brings the high bits down, so the block chosen by the low
+/// bits depends on the whole seeded hash.
+#[inline]
+fn seeded(h: u64, seed: u64) -> u64 {
+    if seed == 0 {
+        return h;
+    }
+    let h = (h ^ seed).wrapping_mul(0xbf58476d1ce4e5b9);
+    h ^ (h >> 27)
+}
+
+/// Sample size for a table of `counter_size` counters: ten additions per
+/// counter, capped at `MAX_SAMPLE_SIZE`.
+fn sample_size_for(counter_size: usize) -> usize {
+    counter_size.saturating_mul(10).min(MAX_SAMPLE_SIZE)
+}
+
 #[cfg(test)]
 fn uint64_to_base10_slice(n: u64) -> Vec<i32> {
     (0..16)
@@ -270,7 +533,9 @@ mod tests {
 
     use ahash::RandomState;
 
-    use super::CountMinSketch;
+    use super::{
+        CountMinSketch, CounterWidth, DEFAULT_BLOCK_WORDS, MAX_SAMPLE_SIZE, sample_size_for,
+    };
 
     #[test]
     fn test_sketch() {
@@ -321,6 +586,7 @@ mod tests {
         assert_eq!(sketch.estimate(h), 15);
         sketch.reset();
         assert_eq!(sketch.estimate(h), 7);
+        assert_eq!(sketch.reset_count(), 1);
 
         for i in sketch.table_counters().iter() {
             for c in i.iter() {
@@ -329,6 +595,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sketch_add_increments_every_probe() {
+        let mut sketch = CountMinSketch::new(1000);
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        let h = hasher.hash_one("foo");
+        sketch.add(h);
+        assert_eq!(sketch.estimate(h), 1);
+        let total: i32 = sketch.table_counters().iter().flatten().sum();
+        assert_eq!(total, 4);
+    }
+
     #[test]
     fn test_sketch_reset_addition() {
         let mut sketch = CountMinSketch::new(500);
@@ -433,4 +710,206 @@ mod tests {
         let _ = sketch.estimate(u64::MAX);
         let _ = sketch.estimate(0);
     }
+
+    #[test]
+    fn test_sketch_auto_reset_count() {
+        let mut sketch = CountMinSketch::new(64);
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        assert_eq!(sketch.reset_count(), 0);
+
+        let mut i = 0;
+        while sketch.reset_count() == 0 {
+            sketch.add(hasher.hash_one(i));
+            i += 1;
+        }
+        assert!(sketch.additions() < sketch.sample_size);
+        assert!(i >= sketch.sample_size);
+    }
+
+    #[test]
+    fn test_sketch_wide_counters() {
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        let hot = hasher.hash_one("hot");
+        let warm = hasher.hash_one("warm");
+
+        let mut narrow = CountMinSketch::new(1000);
+        let mut wide = CountMinSketch::with_counter_width(1000, CounterWidth::Eight);
+        assert_eq!(wide.table.len(), 2 * narrow.table.len());
+        assert_eq!(wide.sample_size, narrow.sample_size);
+
+        for sketch in [&mut narrow, &mut wide] {
+            for _ in 0..100 {
+                sketch.add(hot);
+            }
+            for _ in 0..20 {
+                sketch.add(warm);
+            }
+        }
+
+        // 4-bit counters saturate both keys at the same estimate
+        assert_eq!(narrow.estimate(hot), 15);
+        assert_eq!(narrow.estimate(warm), 15);
+        assert_eq!(wide.estimate(hot), 100);
+        assert_eq!(wide.estimate(warm), 20);
+
+        wide.reset();
+        assert_eq!(wide.estimate(hot), 50);
+        assert_eq!(wide.estimate(warm), 10);
+
+        for cell in wide.table.iter_mut() {
+            *cell = !0;
+        }
+        assert_eq!(wide.estimate(hot), 255);
+        wide.add(hot);
+        assert_eq!(wide.estimate(hot), 255);
+    }
+
+    #[test]
+    fn test_sketch_min_block_mask() {
+        for size in [0, 1, 7, 8, 63, 64, 65] {
+            for width in [CounterWidth::Four, CounterWidth::Eight] {
+                let sketch = CountMinSketch::with_counter_width(size, width);
+                assert!(sketch.block_mask >= 7, "size {} width {:?}", size, width);
+                assert_eq!(sketch.table.len(), (sketch.block_mask + 1) * 8);
+            }
+        }
+    }
+
+    #[test]
+    fn test_sketch_block_words() {
+        for block_words in [0, 2, 3, 12, 128] {
+            assert!(
+                CountMinSketch::with_block_words(1000, CounterWidth::Four, block_words).is_err()
+            );
+        }
+
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        for block_words in [4, DEFAULT_BLOCK_WORDS, 16, 32, 64] {
+            for width in [CounterWidth::Four, CounterWidth::Eight] {
+                let mut sketch =
+                    CountMinSketch::with_block_words(10000, width, block_words).unwrap();
+                assert!(sketch.block_mask >= 7);
+                assert_eq!(sketch.table.len(), (sketch.block_mask + 1) * block_words);
+
+                let mut failed = 0;
+                for i in 0..2000 {
+                    let h = hasher.hash_one(format!("foo:bar:{}", i));
+                    for _ in 0..3 {
+                        sketch.add(h);
+                    }
+                    let estimate = sketch.estimate(h);
+                    assert!(estimate >= 3);
+                    if estimate != 3 {
+                        failed += 1;
+                    }
+                }
+                assert!(failed < 20, "block_words {} failed {}", block_words, failed);
+            }
+        }
+
+        // the default layout is unchanged
+        let sketch = CountMinSketch::with_block_words(10000, CounterWidth::Four, 8).unwrap();
+        assert_eq!(sketch.block_mask, CountMinSketch::new(10000).block_mask);
+    }
+
+    #[test]
+    fn test_sketch_disabled() {
+        let mut sketch = CountMinSketch::disabled();
+        assert!(!sketch.is_enabled());
+        assert_eq!(sketch.table_size(), 0);
+        for _ in 0..100 {
+            sketch.add(42);
+        }
+        assert_eq!(sketch.estimate(42), 0);
+        assert_eq!(sketch.additions(), 0);
+        sketch.clear();
+        assert!(CountMinSketch::new(0).is_enabled());
+    }
+
+    #[test]
+    fn test_sketch_sample_size_cap() {
+        assert_eq!(sample_size_for(1024), 10240);
+        assert_eq!(sample_size_for(1 << 40), MAX_SAMPLE_SIZE);
+        assert_eq!(sample_size_for(usize::MAX), MAX_SAMPLE_SIZE);
+
+        let mut sketch = CountMinSketch::new(1 << 16);
+        assert_eq!(sketch.sample_size, 655360);
+        sketch.cap_sample_size(1000);
+        assert_eq!(sketch.sample_size, 1000);
+        sketch.cap_sample_size(5000);
+        assert_eq!(sketch.sample_size, 1000);
+
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        for i in 0..2000 {
+            sketch.add(hasher.hash_one(i));
+        }
+        assert!(sketch.reset_count() >= 1);
+
+        let mut disabled = CountMinSketch::disabled();
+        disabled.cap_sample_size(1000);
+        assert_eq!(disabled.sample_size, usize::MAX);
+    }
+
+    #[test]
+    fn test_sketch_seed() {
+        let hasher = RandomState::with_seeds(9, 0, 7, 2);
+        let hot = hasher.hash_one("hot");
+        let mut sketches = [
+            CountMinSketch::new(64),
+            CountMinSketch::with_seed(64, 0),
+            CountMinSketch::with_seed(64, 1),
+            CountMinSketch::with_seed(64, 2),
+        ];
+        for sketch in sketches.iter_mut() {
+            for i in 0..200 {
+                sketch.add(hasher.hash_one(i));
+            }
+            for _ in 0..15 {
+                sketch.add(hot);
+            }
+            assert_eq!(sketch.estimate(hot), 15);
+        }
+
+        // keys never added that collide in the unseeded sketch
+        let colliding: Vec<u64> = (1000..100_000)
+            .map(|i| hasher.hash_one(i))
+            .filter(|&key| sketches[0].estimate(key) > 0)
+            .collect();
+        assert!(!colliding.is_empty());
+        let estimates = |sketch: &CountMinSketch| -> Vec<usize> {
+            colliding.iter().map(|&key| sketch.estimate(key)).collect()
+        };
+        assert_eq!(estimates(&sketches[0]), estimates(&sketches[1]));
+        assert_ne!(estimates(&sketches[0]), estimates(&sketches[2]));
+        assert_ne!(estimates(&sketches[2]), estimates(&sketches[3]));
+    }
+
+    #[test]
+    fn test_sketch_decay() {
+        let mut sketch = CountMinSketch::new(1000);
+        for _ in 0..8 {
+            sketch.add(42);
+        }
+        sketch.decay();
+        assert_eq!(sketch.estimate(42), 4);
+        assert_eq!(sketch.reset_count(), 1);
+
+        let mut disabled = CountMinSketch::disabled();
+        disabled.decay();
+        assert_eq!(disabled.reset_count(), 0);
+    }
+
+    #[test]
+    fn test_sketch_saturation() {
+        for width in [CounterWidth::Four, CounterWidth::Eight] {
+            let mut sketch = CountMinSketch::with_counter_width(1000, width);
+            assert_eq!(sketch.saturation(), 0.0);
+            for _ in 0..300 {
+                sketch.add(42);
+            }
+            let counters = sketch.table_size() * 64 / width.bits();
+            assert_eq!(sketch.saturation(), 4.0 / counters as f64);
+        }
+        assert_eq!(CountMinSketch::disabled().saturation(), 0.0);
+    }
 }
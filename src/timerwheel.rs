@@ -2,15 +2,20 @@
 //!
 //! A timer wheel is a data structure for scheduling events at specific times
 //! with O(1) insertion and removal operations. This implementation uses 5 levels
-//! with exponentially increasing time ranges to handle everything from milliseconds
-//! to days efficiently.
+//! by default with exponentially increasing time ranges to handle everything from
+//! milliseconds to days efficiently.
 
 use std::cmp;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
 
+use anyhow::Result;
+
 use crate::metadata::{Entry, List};
 
+/// Default bucket count per level, the last level is the overflow bucket
+const DEFAULT_BUCKETS: [usize; 5] = [64, 64, 32, 4, 1];
+
 /// A monotonic clock for tracking elapsed time since cache creation.
 ///
 /// Uses `Instant` internally for reliable measurements across system time changes.
@@ -65,7 +70,7 @@ impl Clock {
 
 /// A hierarchical timer wheel for efficient TTL expiration scheduling.
 ///
-/// Uses 5 levels with exponentially increasing time ranges:
+/// By default uses 5 levels with exponentially increasing time ranges:
 /// - Level 0: ~1.07 seconds (64 buckets)
 /// - Level 1: ~1.14 minutes (64 buckets)
 /// - Level 2: ~1.22 hours (32 buckets)
@@ -79,6 +84,7 @@ pub struct TimerWheel {
     wheel: Vec<Vec<List<u64>>>,
     pub clock: Clock,
     nanos: u64,
+    reschedules: u64,
 }
 
 impl Default for TimerWheel {
@@ -90,33 +96,74 @@ impl Default for TimerWheel {
 impl TimerWheel {
     /// Creates a new timer wheel with 5 hierarchical levels.
     pub fn new() -> Self {
-        let buckets = vec![64, 64, 32, 4, 1];
+        Self::build(DEFAULT_BUCKETS.to_vec())
+    }
+
+    /// Creates a timer wheel with a custom number of buckets per level.
+    ///
+    /// Level 0 buckets span ~1.07s and each following level's bucket spans a
+    /// full rotation of the previous level. The last level is the overflow
+    /// bucket for everything beyond the other levels, e.g. `[64, 64, 32, 4, 4, 1]`
+    /// adds a level of ~6.5 day buckets in front of the overflow bucket.
+    ///
+    /// # Arguments
+    ///
+    /// * `bucket_spec` - Bucket count per level, powers of two, at least 2 for
+    ///   every level but the last which must have exactly 1 bucket
+    ///
+    /// # Returns
+    ///
+    /// `Err` if the spec doesn't produce strictly increasing spans
+    pub fn with_levels(bucket_spec: Vec<usize>) -> Result<Self> {
+        let Some((&last, levels)) = bucket_spec.split_last() else {
+            anyhow::bail!("TimerWheel: bucket spec must have at least one level");
+        };
+        if bucket_spec.len() > u8::MAX as usize {
+            anyhow::bail!("TimerWheel: at most {} levels are supported", u8::MAX);
+        }
+        if last != 1 {
+            anyhow::bail!(
+                "TimerWheel: last level must have exactly 1 bucket, got {}",
+                last
+            );
+        }
+        let mut span = Self::base_span();
+        for (level, &count) in levels.iter().enumerate() {
+            if count < 2 || !count.is_power_of_two() || count > u8::MAX as usize + 1 {
+                anyhow::bail!(
+                    "TimerWheel: level {} bucket count {} must be a power of two between 2 and 256",
+                    level,
+                    count
+                );
+            }
+            span = span.checked_mul(count as u64).ok_or_else(|| {
+                anyhow::anyhow!("TimerWheel: level {} span overflows u64 nanoseconds", level)
+            })?;
+        }
+        Ok(Self::build(bucket_spec))
+    }
+
+    /// Width of a level 0 bucket, ~1.07s
+    fn base_span() -> u64 {
+        Duration::from_secs(1).as_nanos().next_power_of_two() as u64
+    }
+
+    fn build(buckets: Vec<usize>) -> Self {
         let clock = Clock::new();
         let nanos = clock.now_ns();
 
-        // Pre-calculate span sizes and bit shifts for each level
-        let spans = vec![
-            Duration::from_secs(1).as_nanos().next_power_of_two() as u64, // ~1.07s
-            Duration::from_secs(60).as_nanos().next_power_of_two() as u64, // ~1.14m
-            Duration::from_secs(60 * 60).as_nanos().next_power_of_two() as u64, // ~1.22h
-            Duration::from_secs(24 * 60 * 60)
-                .as_nanos()
-                .next_power_of_two() as u64, // ~1.63d
-            (Duration::from_secs(24 * 60 * 60)
-                .as_nanos()
-                .next_power_of_two()
-                * 4) as u64, // ~6.5d
-            (Duration::from_secs(24 * 60 * 60)
-                .as_nanos()
-                .next_power_of_two()
-                * 4) as u64, // ~6.5d
-        ];
+        // Pre-calculate span sizes and bit shifts for each level, the overflow
+        // level span is repeated as the upper bound of the last level
+        let mut spans = vec![Self::base_span()];
+        for (level, &count) in buckets.iter().enumerate().take(buckets.len() - 1) {
+            spans.push(spans[level] * count as u64);
+        }
+        spans.push(spans[spans.len() - 1]);
 
         let shift: Vec<u32> = spans.iter().map(|s| s.trailing_zeros()).collect();
 
         let wheel = buckets
             .iter()
-            .take(5)
             .map(|&bucket_count| (0..bucket_count).map(|_| List::new(8)).collect())
             .collect();
 
@@ -129,6 +176,7 @@ impl TimerWheel {
             wheel,
             clock,
             nanos,
+            reschedules: 0,
         }
     }
 
@@ -144,20 +192,21 @@ impl TimerWheel {
     #[inline]
     fn find_index(&self, expire: u64) -> (u8, u8) {
         let duration = expire.saturating_sub(self.nanos);
-        for i in 0..5 {
+        let levels = self.buckets.len();
+        for i in 0..levels {
             if duration < self.spans[i + 1] {
                 let ticks = expire >> self.shift[i];
                 let slot = ticks & (self.buckets[i] - 1) as u64;
                 return (i as u8, slot as u8);
             }
         }
-        (4, 0)
+        ((levels - 1) as u8, 0)
     }
 
     /// Schedules an entry in the timer wheel.
     ///
     /// First removes the entry from any existing wheel position, then inserts it
-    /// at the appropriate level and slot based on its expiration time.
+    /// at the appropriate level and slot based on its deadline (see `Entry::deadline`).
     ///
     /// # Arguments
     ///
@@ -165,8 +214,29 @@ impl TimerWheel {
     /// * `entry` - The entry to schedule (modified in place)
     pub fn schedule(&mut self, key: u64, entry: &mut Entry) {
         self.deschedule(entry);
-        if entry.expire > 0 {
-            let w_index = self.find_index(entry.expire);
+        self.place(key, entry);
+    }
+
+    /// Schedules a freshly created entry in the timer wheel.
+    ///
+    /// Produces the same placement as `schedule`, but skips the deschedule
+    /// step since a new entry has no previous wheel position.
+    ///
+    /// # Arguments
+    ///
+    /// * `key` - The cache key associated with the entry
+    /// * `entry` - The new entry to schedule (modified in place)
+    #[inline]
+    pub fn schedule_new(&mut self, key: u64, entry: &mut Entry) {
+        debug_assert!(entry.wheel_list_index.is_none());
+        self.place(key, entry);
+    }
+
+    /// Inserts an entry at the level and slot matching its deadline.
+    fn place(&mut self, key: u64, entry: &mut Entry) {
+        let deadline = entry.deadline();
+        if deadline > 0 {
+            let w_index = self.find_index(deadline);
 
             if let Some(level) = self.wheel.get_mut(w_index.0 as usize) {
                 if let Some(bucket) = level.get_mut(w_index.1 as usize) {
@@ -219,8 +289,29 @@ impl TimerWheel {
         entry.wheel_index = (0, 0);
     }
 
+    /// Replaces the key an entry is scheduled under, keeping its bucket.
+    ///
+    /// # Returns
+    ///
+    /// `false` if the entry isn't scheduled in the wheel
+    pub fn rename(&mut self, new_key: u64, entry: &Entry) -> bool {
+        let (level, slot) = entry.wheel_index;
+        match (
+            entry.wheel_list_index,
+            self.wheel
+                .get_mut(level as usize)
+                .and_then(|level| level.get_mut(slot as usize)),
+        ) {
+            (Some(index), Some(bucket)) => bucket.replace(index, new_key).is_some(),
+            _ => false,
+        }
+    }
+
     /// Advances the timer wheel to the current time and expires all stale entries.
     ///
+    /// Time never moves backward: a `now` earlier than the last advance is
+    /// ignored and the wheel keeps its current time.
+    ///
     /// # Arguments
     ///
     /// * `now` - The current time in nanoseconds
@@ -230,11 +321,19 @@ impl TimerWheel {
     ///
     /// Vector of keys that were expired and removed
     pub fn advance(&mut self, now: u64, entries: &mut HashMap<u64, Entry>) -> Vec<u64> {
+        if now < self.nanos {
+            log::debug!(
+                "TimerWheel advance: ignoring backward time {} < {}",
+                now,
+                self.nanos
+            );
+            return Vec::new();
+        }
         let previous = self.nanos;
         self.nanos = now;
         let mut removed_all = Vec::new();
 
-        for i in 0..5 {
+        for i in 0..self.buckets.len() {
             let prev_ticks = previous >> self.shift[i];
             let current_ticks = now >> self.shift[i];
             if current_ticks <= prev_ticks {
@@ -246,6 +345,89 @@ impl TimerWheel {
         removed_all
     }
 
+    /// Like `advance`, but stops short of `now` once `max_buckets` buckets
+    /// would be scanned, so a long pause is caught up over several calls.
+    ///
+    /// Advances to the latest time whose sweep stays within the budget, and
+    /// always by at least one level 0 tick so repeated calls make progress.
+    /// The wheel's time is where the next call resumes.
+    pub fn advance_incremental(
+        &mut self,
+        now: u64,
+        max_buckets: usize,
+        entries: &mut HashMap<u64, Entry>,
+    ) -> Vec<u64> {
+        if now <= self.nanos || self.buckets_to(now) <= max_buckets {
+            return self.advance(now, entries);
+        }
+        // the sweep cost only grows with the target time
+        let next_tick = ((self.nanos >> self.shift[0]) + 1) << self.shift[0];
+        let (mut low, mut high) = (next_tick.min(now), now);
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if self.buckets_to(mid) <= max_buckets {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+        log::trace!("TimerWheel advance_incremental: {} of {}", low, now);
+        self.advance(low, entries)
+    }
+
+    /// Number of buckets `advance` would scan going from the last advance to `now`.
+    fn buckets_to(&self, now: u64) -> usize {
+        let mut total = 0;
+        for i in 0..self.buckets.len() {
+            let prev_ticks = self.nanos >> self.shift[i];
+            let current_ticks = now >> self.shift[i];
+            if current_ticks <= prev_ticks {
+                break;
+            }
+            total += cmp::min(current_ticks - prev_ticks + 1, self.buckets[i] as u64) as usize;
+        }
+        total
+    }
+
+    /// Returns whether advancing to `now` would visit a non-empty bucket.
+    ///
+    /// Only checks the buckets `advance` would scan, without touching their
+    /// entries. A visited bucket may hold entries that only get rescheduled,
+    /// so this can report pending work that expires nothing yet.
+    pub fn is_advance_pending(&self, now: u64) -> bool {
+        for i in 0..self.buckets.len() {
+            let prev_ticks = self.nanos >> self.shift[i];
+            let current_ticks = now >> self.shift[i];
+            if current_ticks <= prev_ticks {
+                break;
+            }
+            let mask = (self.buckets[i] - 1) as u64;
+            let steps = cmp::min(current_ticks - prev_ticks + 1, self.buckets[i] as u64);
+            let start = prev_ticks & mask;
+            if (start..start + steps).any(|tick| self.wheel[i][(tick & mask) as usize].len() > 0) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Like `advance`, but returns an error instead of ignoring a `now`
+    /// earlier than the last advance.
+    pub fn advance_checked(
+        &mut self,
+        now: u64,
+        entries: &mut HashMap<u64, Entry>,
+    ) -> Result<Vec<u64>> {
+        if now < self.nanos {
+            anyhow::bail!(
+                "TimerWheel: time moved backward from {} to {}",
+                self.nanos,
+                now
+            );
+        }
+        Ok(self.advance(now, entries))
+    }
+
     /// Processes expiration for a specific wheel level.
     ///
     /// Scans through the affected buckets, separating expired entries from those
@@ -286,7 +468,7 @@ impl TimerWheel {
             // Collect keys that are expired vs. those that need rescheduling
             for key in self.wheel[index][bucket_idx].iter() {
                 if let Some(entry) = entries.get(key) {
-                    if entry.expire <= self.nanos {
+                    if entry.deadline() <= self.nanos {
                         removed.push(*key);
                     } else {
                         modified.push(*key);
@@ -305,6 +487,7 @@ impl TimerWheel {
             for &key in &modified {
                 if let Some(entry) = entries.get_mut(&key) {
                     self.schedule(key, entry);
+                    self.reschedules = self.reschedules.saturating_add(1);
                 }
             }
 
@@ -313,6 +496,138 @@ impl TimerWheel {
         removed_all
     }
 
+    /// Checks that the wheel and the entries' scheduling metadata agree.
+    ///
+    /// Verifies that every scheduled entry's `wheel_index`/`wheel_list_index`
+    /// resolve to a bucket node holding its key, that entries with a deadline
+    /// are scheduled (and those without are not), that every key found in a
+    /// bucket belongs to a resident entry pointing back at that bucket, and
+    /// that `find_index` agrees with the stored placement. As the wheel
+    /// advances an entry may legitimately sit in a coarser level than
+    /// `find_index` would now pick, so only finer-than-expected levels or
+    /// slot mismatches within the same level are reported. Entries with an
+    /// idle timeout are rescheduled lazily and skip the placement check.
+    ///
+    /// # Arguments
+    ///
+    /// * `entries` - The cache entries map
+    ///
+    /// # Returns
+    ///
+    /// Vector of (key, description) pairs, empty when the wheel is consistent
+    pub fn validate_wheel(&self, entries: &HashMap<u64, Entry>) -> Vec<(u64, String)> {
+        let mut issues = Vec::new();
+
+        for (&key, entry) in entries {
+            let (level, slot) = entry.wheel_index;
+            let deadline = entry.deadline();
+            let Some(index) = entry.wheel_list_index else {
+                if deadline > 0 {
+                    issues.push((key, format!("deadline {} but not scheduled", deadline)));
+                }
+                continue;
+            };
+
+            if deadline == 0 {
+                issues.push((key, "scheduled without a deadline".to_string()));
+            }
+            let Some(bucket) = self
+                .wheel
+                .get(level as usize)
+                .and_then(|l| l.get(slot as usize))
+            else {
+                issues.push((
+                    key,
+                    format!("wheel_index ({}, {}) out of bounds", level, slot),
+                ));
+                continue;
+            };
+            match bucket.get(index) {
+                Some(&found) if found == key => {}
+                Some(&found) => issues.push((
+                    key,
+                    format!(
+                        "wheel_list_index in ({}, {}) points to key {}",
+                        level, slot, found
+                    ),
+                )),
+                None => issues.push((
+                    key,
+                    format!("stale wheel_list_index in ({}, {})", level, slot),
+                )),
+            }
+
+            if deadline > 0 && entry.tti == 0 {
+                let expected = self.find_index(deadline);
+                if level < expected.0 || (level == expected.0 && slot != expected.1) {
+                    issues.push((
+                        key,
+                        format!(
+                            "stored in ({}, {}) but deadline maps to ({}, {})",
+                            level, slot, expected.0, expected.1
+                        ),
+                    ));
+                }
+            }
+        }
+
+        for (level, buckets) in self.wheel.iter().enumerate() {
+            for (slot, bucket) in buckets.iter().enumerate() {
+                for &key in bucket.iter() {
+                    match entries.get(&key) {
+                        None => issues.push((
+                            key,
+                            format!("in bucket ({}, {}) but not resident", level, slot),
+                        )),
+                        Some(entry) if entry.wheel_index != (level as u8, slot as u8) => issues
+                            .push((
+                                key,
+                                format!(
+                                    "in bucket ({}, {}) but entry points to ({}, {})",
+                                    level, slot, entry.wheel_index.0, entry.wheel_index.1
+                                ),
+                            )),
+                        Some(_) => {}
+                    }
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// Time in nanoseconds the wheel was last advanced to.
+    pub fn nanos(&self) -> u64 {
+        self.nanos
+    }
+
+    /// Number of not yet expired entries moved to another bucket by `advance`
+    /// over the wheel's lifetime.
+    pub fn reschedule_count(&self) -> u64 {
+        self.reschedules
+    }
+
+    /// Number of entries scheduled across all buckets of all levels.
+    pub fn scheduled_count(&self) -> usize {
+        self.level_occupancy().iter().sum()
+    }
+
+    /// Number of entries scheduled in each level, finest level first.
+    pub fn level_occupancy(&self) -> Vec<usize> {
+        self.wheel
+            .iter()
+            .map(|level| level.iter().map(List::len).sum())
+            .collect()
+    }
+
+    /// Releases the storage of every empty bucket, e.g. after a burst of
+    /// short-lived entries expired. Buckets holding entries keep theirs.
+    pub fn compact(&mut self) {
+        for bucket in self.wheel.iter_mut().flatten() {
+            bucket.shrink_to_fit();
+        }
+    }
+
     /// Clears all entries from all wheel levels.
     pub fn clear(&mut self) {
         for level in self.wheel.iter_mut() {
@@ -383,6 +698,9 @@ mod tests {
         assert!(tw.wheel[1].iter().any(|x| x.iter().any(|x| *x == 2)));
         assert!(tw.wheel[2].iter().any(|x| x.iter().any(|x| *x == 3)));
 
+        assert_eq!(tw.scheduled_count(), 3);
+        assert_eq!(tw.level_occupancy(), vec![1, 1, 1, 0, 0]);
+
         // deschedule test
         for key in [1, 2, 3] {
             if let Some(entry) = entries.get_mut(&key) {
@@ -399,6 +717,171 @@ mod tests {
         assert!(!tw.wheel[2].iter().any(|x| x.iter().any(|x| *x == 3)));
     }
 
+    #[test]
+    fn test_default_spans() {
+        let tw = TimerWheel::new();
+        let day = Duration::from_secs(24 * 60 * 60)
+            .as_nanos()
+            .next_power_of_two() as u64;
+        assert_eq!(
+            tw.spans,
+            vec![
+                Duration::from_secs(1).as_nanos().next_power_of_two() as u64,
+                Duration::from_secs(60).as_nanos().next_power_of_two() as u64,
+                Duration::from_secs(60 * 60).as_nanos().next_power_of_two() as u64,
+                day,
+                day * 4,
+                day * 4,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_levels() {
+        for spec in [
+            vec![],
+            vec![64, 64, 32, 4, 2],
+            vec![64, 1, 1],
+            vec![64, 48, 1],
+            vec![1 << 20, 1 << 20, 1 << 20, 1],
+        ] {
+            assert!(TimerWheel::with_levels(spec).is_err());
+        }
+
+        let day = Duration::from_secs(24 * 60 * 60).as_nanos() as u64;
+        let mut tw = TimerWheel::with_levels(vec![64, 64, 32, 4, 4, 1]).unwrap();
+        let now = tw.clock.now_ns();
+        let mut entries = HashMap::new();
+        for (key, days) in [(1, 10), (2, 20), (3, 40)] {
+            let mut entry = Entry::new();
+            entry.expire = now + days * day;
+            tw.schedule(key, &mut entry);
+            entries.insert(key, entry);
+        }
+        assert_eq!(entries[&1].wheel_index.0, 4);
+        assert_eq!(entries[&2].wheel_index.0, 4);
+        assert_ne!(entries[&1].wheel_index, entries[&2].wheel_index);
+        assert_eq!(entries[&3].wheel_index, (5, 0));
+
+        // the default wheel piles both into the overflow bucket
+        let mut default = TimerWheel::new();
+        default.nanos = now;
+        assert_eq!(default.find_index(now + 10 * day), (4, 0));
+        assert_eq!(default.find_index(now + 20 * day), (4, 0));
+
+        let mut tw = TimerWheel::with_levels(vec![64, 64, 32, 4, 4, 1]).unwrap();
+        tw.nanos = now;
+        for (key, entry) in entries.iter_mut() {
+            *entry = Entry {
+                expire: entry.expire,
+                ..Entry::new()
+            };
+            tw.schedule(*key, entry);
+        }
+        for (days, expected) in [
+            (5, vec![]),
+            (11, vec![1]),
+            (15, vec![]),
+            (21, vec![2]),
+            (41, vec![3]),
+        ] {
+            assert_eq!(tw.advance(now + days * day, &mut entries), expected);
+        }
+    }
+
+    #[test]
+    fn test_schedule_new_matches_schedule() {
+        let mut tw = TimerWheel::new();
+        let now = tw.clock.now_ns();
+        for (key, expire) in [
+            (1, 0u64),
+            (2, 1u64),
+            (3, 69u64),
+            (4, 4399u64),
+            (5, 600000u64),
+        ] {
+            let expire = if expire > 0 {
+                now + Duration::from_secs(expire).as_nanos() as u64
+            } else {
+                0
+            };
+            let mut scheduled = Entry::new();
+            scheduled.expire = expire;
+            tw.schedule(key, &mut scheduled);
+
+            let mut fresh = Entry::new();
+            fresh.expire = expire;
+            tw.schedule_new(key + 100, &mut fresh);
+
+            assert_eq!(scheduled.wheel_index, fresh.wheel_index);
+            assert_eq!(
+                scheduled.wheel_list_index.is_some(),
+                fresh.wheel_list_index.is_some()
+            );
+            let (level, slot) = fresh.wheel_index;
+            if expire > 0 {
+                let bucket = &tw.wheel[level as usize][slot as usize];
+                assert!(bucket.iter().any(|k| *k == key));
+                assert!(bucket.iter().any(|k| *k == key + 100));
+            }
+        }
+    }
+
+    #[test]
+    fn test_validate_wheel() {
+        let mut tw = TimerWheel::new();
+        let now = tw.clock.now_ns();
+        let mut entries = HashMap::new();
+        for (key, expire) in [(1, 0u64), (2, 1u64), (3, 69u64), (4, 4399u64)] {
+            let mut entry = Entry::new();
+            if expire > 0 {
+                entry.expire = now + Duration::from_secs(expire).as_nanos() as u64;
+            }
+            tw.schedule(key, &mut entry);
+            entries.insert(key, entry);
+        }
+        assert!(tw.validate_wheel(&entries).is_empty());
+
+        // coarser placement after the wheel moves on is fine
+        tw.nanos = now + Duration::from_secs(60).as_nanos() as u64;
+        assert!(tw.validate_wheel(&entries).is_empty());
+        tw.nanos = now;
+
+        // unscheduled entry with a deadline
+        let entry = entries.get_mut(&2).unwrap();
+        tw.deschedule(entry);
+        // wrong level
+        let entry = entries.get_mut(&3).unwrap();
+        entry.expire = now + Duration::from_secs(600_000).as_nanos() as u64;
+        // key in a bucket that is not resident
+        let mut ghost = Entry::new();
+        ghost.expire = now + Duration::from_secs(1).as_nanos() as u64;
+        tw.schedule(9, &mut ghost);
+
+        let issues = tw.validate_wheel(&entries);
+        let keys: Vec<u64> = {
+            let mut keys: Vec<u64> = issues.iter().map(|(k, _)| *k).collect();
+            keys.sort_unstable();
+            keys
+        };
+        assert_eq!(keys, vec![2, 3, 9]);
+        assert!(
+            issues
+                .iter()
+                .any(|(k, m)| *k == 2 && m.contains("not scheduled"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|(k, m)| *k == 3 && m.contains("deadline maps to"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|(k, m)| *k == 9 && m.contains("not resident"))
+        );
+    }
+
     #[test]
     fn test_advance_compact() {
         use std::collections::HashMap;
@@ -488,6 +971,34 @@ mod tests {
         assert_eq!(expired, vec![7]);
     }
 
+    #[test]
+    fn test_advance_backward() {
+        let mut tw = TimerWheel::new();
+        let mut entries = HashMap::new();
+        let now = tw.clock.now_ns();
+        let mut entry = Entry::new();
+        entry.expire = now + Duration::from_secs(30).as_nanos() as u64;
+        tw.schedule(1, &mut entry);
+        entries.insert(1, entry);
+
+        let later = now + Duration::from_secs(10).as_nanos() as u64;
+        assert!(tw.advance(later, &mut entries).is_empty());
+
+        // a backward advance is ignored and doesn't rewind the wheel
+        assert!(tw.advance(now, &mut entries).is_empty());
+        assert_eq!(tw.nanos, later);
+        assert!(tw.advance_checked(now, &mut entries).is_err());
+        assert_eq!(tw.nanos, later);
+
+        let expired = tw
+            .advance_checked(
+                now + Duration::from_secs(31).as_nanos() as u64,
+                &mut entries,
+            )
+            .unwrap();
+        assert_eq!(expired, vec![1]);
+    }
+
     // Simple no panic test
     #[test]
     fn test_advance_large() {
@@ -518,4 +1029,124 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_reschedule_count() {
+        let mut tw = TimerWheel::new();
+        let mut entries = HashMap::new();
+        let now = tw.clock.now_ns();
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut entry = Entry::new();
+        entry.expire = now + 200 * second;
+        tw.schedule(1, &mut entry);
+        entries.insert(1, entry);
+        assert_eq!(tw.reschedule_count(), 0);
+
+        // cascades down from level 1 before it expires
+        for step in 1..200 {
+            assert!(tw.advance(now + step * second, &mut entries).is_empty());
+        }
+        assert!(tw.reschedule_count() >= 1);
+        assert_eq!(tw.advance(now + 201 * second, &mut entries), vec![1]);
+    }
+
+    #[test]
+    fn test_is_advance_pending() {
+        let mut tw = TimerWheel::new();
+        let mut entries = HashMap::new();
+        let now = tw.clock.now_ns();
+        tw.advance(now, &mut entries);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        assert!(!tw.is_advance_pending(now + 100 * second));
+
+        let mut entry = Entry::new();
+        entry.expire = now + 2 * second;
+        tw.schedule(1, &mut entry);
+        entries.insert(1, entry);
+        assert!(!tw.is_advance_pending(now));
+        assert!(!tw.is_advance_pending(now - 1));
+        assert!(tw.is_advance_pending(now + 3 * second));
+
+        assert_eq!(tw.advance(now + 3 * second, &mut entries), vec![1]);
+        assert!(!tw.is_advance_pending(now + 100 * second));
+    }
+
+    #[test]
+    fn test_advance_incremental() {
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        let mut tw = TimerWheel::new();
+        let mut entries = HashMap::new();
+        let now = tw.clock.now_ns();
+        tw.advance(now, &mut entries);
+        for key in 1..=500u64 {
+            let mut entry = Entry::new();
+            entry.expire = now + key * 13 * second;
+            tw.schedule(key, &mut entry);
+            entries.insert(key, entry);
+        }
+
+        let target = now + 5000 * second;
+        let mut expired = Vec::new();
+        let mut calls = 0;
+        while tw.nanos() < target {
+            let cost = tw.buckets_to(target);
+            let before = tw.nanos();
+            expired.extend(tw.advance_incremental(target, 10, &mut entries));
+            assert!(tw.nanos() > before);
+            assert!(cost <= 10 || tw.nanos() < target);
+            calls += 1;
+        }
+        assert!(calls > 1);
+        expired.sort();
+        assert_eq!(expired, (1..=384).collect::<Vec<u64>>());
+        for key in &expired {
+            entries.remove(key);
+        }
+        assert!(tw.validate_wheel(&entries).is_empty());
+
+        // a budget too small for one tick still makes progress
+        let before = tw.nanos();
+        tw.advance_incremental(before + 100 * second, 0, &mut entries);
+        assert!(tw.nanos() > before);
+        assert!(tw.nanos() < before + 100 * second);
+    }
+
+    #[test]
+    fn test_compact_buckets() {
+        let mut tw = TimerWheel::new();
+        let mut entries = HashMap::new();
+        let now = tw.clock.now_ns();
+        tw.advance(now, &mut entries);
+        let second = Duration::from_secs(1).as_nanos() as u64;
+        for key in 1..=10_000u64 {
+            let mut entry = Entry::new();
+            entry.expire = now + second + key;
+            tw.schedule(key, &mut entry);
+            entries.insert(key, entry);
+        }
+        let mut entry = Entry::new();
+        entry.expire = now + 3600 * second;
+        tw.schedule(0, &mut entry);
+        entries.insert(0, entry);
+
+        let allocated = |tw: &TimerWheel| -> usize {
+            tw.wheel
+                .iter()
+                .flatten()
+                .map(|bucket| bucket.list.capacity())
+                .sum()
+        };
+        let expired = tw.advance(now + 3 * second, &mut entries);
+        assert_eq!(expired.len(), 10_000);
+        for key in expired {
+            entries.remove(&key);
+        }
+        assert!(allocated(&tw) >= 10_000);
+
+        tw.compact();
+        assert!(allocated(&tw) < 100);
+        assert_eq!(tw.scheduled_count(), 1);
+        assert!(tw.validate_wheel(&entries).is_empty());
+        assert_eq!(tw.advance(now + 7200 * second, &mut entries), vec![0]);
+    }
 }
@@ -1,17 +1,20 @@
 use crate::lru::Lru;
 use crate::lru::Slru;
-use crate::metadata::Entry;
-use crate::sketch::CountMinSketch;
+use crate::lru::WindowPolicy;
+use crate::metadata::{Entry, List};
+use crate::sketch::{CountMinSketch, CounterWidth};
 use crate::timerwheel::Clock;
 use anyhow::Result;
 
 use pyo3::prelude::pyclass;
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 const ADMIT_HASHDOS_THRESHOLD: usize = 6;
 const HILL_CLIMBER_STEP_DECAY_RATE: f32 = 0.98;
 const HILL_CLIMBER_STEP_PERCENT: f32 = 0.0625;
+// bound on window move loop iterations, as a multiple of the requested amount
+const MOVE_ITERATIONS_FACTOR: usize = 2;
 
 #[derive(PartialEq)]
 enum PolicyList {
@@ -20,17 +23,65 @@ enum PolicyList {
     Protected,
 }
 
+/// Where capacity eviction looks for its first victim.
+///
+/// - `ProbationFirst`: the probation tail, then protected, then the window
+/// - `WindowFirst`: the window tail, then probation, then protected
+#[pyclass(eq, eq_int)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum VictimOrder {
+    #[default]
+    ProbationFirst,
+    WindowFirst,
+}
+
+impl VictimOrder {
+    /// Lists victims are taken from, in order.
+    fn queues(self) -> [PolicyList; 3] {
+        match self {
+            VictimOrder::ProbationFirst => [
+                PolicyList::Probation,
+                PolicyList::Protected,
+                PolicyList::Window,
+            ],
+            VictimOrder::WindowFirst => [
+                PolicyList::Window,
+                PolicyList::Probation,
+                PolicyList::Protected,
+            ],
+        }
+    }
+}
+
+#[derive(Clone)]
 pub struct TinyLfu {
     size: usize,
     capacity: usize,
     window: Lru,
     main: Slru,
+    // window and protected capacities `full_reset` goes back to
+    initial_window: usize,
+    initial_protected: usize,
     pub sketch: CountMinSketch,
     hit_in_sample: usize,
     misses_in_sample: usize,
     hr: f32,
     step: f32,
     amount: isize,
+    hashdos_threshold: usize,
+    min_admission_frequency: usize,
+    weight_writes: bool,
+    overflow_allowance: usize,
+    segment_hits: [u64; 3],
+    prefer_candidate_on_tie: bool,
+    window_frozen: bool,
+    admission_trace: VecDeque<(usize, usize, bool)>,
+    admission_trace_cap: usize,
+    climber_trace: VecDeque<(f32, f32, f32, isize)>,
+    climber_trace_cap: usize,
+    victim_order: VictimOrder,
+    hot_window_promotion: usize,
+    demotion_repair: usize,
 }
 
 impl TinyLfu {
@@ -43,10 +94,7 @@ impl TinyLfu {
             size
         };
 
-        let mut lru_size = (capacity as f64 * 0.01) as usize;
-        if lru_size == 0 {
-            lru_size = 1;
-        }
+        let lru_size = Self::initial_window_size(capacity);
         let slru_size = capacity - lru_size;
 
         log::debug!(
@@ -56,18 +104,97 @@ impl TinyLfu {
             slru_size
         );
 
+        let main = Slru::new(slru_size);
         TinyLfu {
             size: 0,
             capacity,
             window: Lru::new(lru_size),
-            main: Slru::new(slru_size),
+            initial_window: lru_size,
+            initial_protected: main.protected.capacity,
+            main,
             sketch: CountMinSketch::new(capacity),
             hit_in_sample: 0,
             misses_in_sample: 0,
             hr: 0.0,
             step: -(capacity as f32) * 0.0625,
             amount: 0,
+            hashdos_threshold: ADMIT_HASHDOS_THRESHOLD,
+            min_admission_frequency: 0,
+            weight_writes: true,
+            overflow_allowance: 0,
+            segment_hits: [0; 3],
+            prefer_candidate_on_tie: false,
+            window_frozen: false,
+            admission_trace: VecDeque::new(),
+            admission_trace_cap: 0,
+            climber_trace: VecDeque::new(),
+            climber_trace_cap: 0,
+            victim_order: VictimOrder::ProbationFirst,
+            hot_window_promotion: 0,
+            demotion_repair: 0,
+        }
+    }
+
+    /// Like `new`, but sizes the frequency sketch for at most
+    /// `max_sketch_size` keys instead of the full capacity.
+    ///
+    /// The sketch stays correctly addressed at any size, a smaller one just
+    /// trades estimate accuracy (more collisions and more frequent resets)
+    /// for memory on very large caches.
+    pub fn with_max_sketch_size(size: usize, max_sketch_size: usize) -> TinyLfu {
+        let mut tlfu = TinyLfu::new(size);
+        if max_sketch_size < tlfu.capacity {
+            tlfu.sketch = CountMinSketch::new(max_sketch_size);
         }
+        tlfu
+    }
+
+    /// Like `new`, but with the given sketch counter width.
+    ///
+    /// 8-bit counters tell apart keys accessed far more than 15 times per
+    /// sample, at twice the sketch memory.
+    pub fn with_counter_width(size: usize, width: CounterWidth) -> TinyLfu {
+        let mut tlfu = TinyLfu::new(size);
+        tlfu.sketch = CountMinSketch::with_counter_width(tlfu.capacity, width);
+        tlfu
+    }
+
+    /// Like `new`, but with `block_words` 64-bit words per sketch block, see
+    /// `CountMinSketch::with_block_words`.
+    pub fn with_sketch_block_words(size: usize, block_words: usize) -> Result<TinyLfu> {
+        let mut tlfu = TinyLfu::new(size);
+        tlfu.sketch =
+            CountMinSketch::with_block_words(tlfu.capacity, CounterWidth::Four, block_words)?;
+        Ok(tlfu)
+    }
+
+    /// Like `new`, but with a seeded sketch, see `CountMinSketch::with_seed`.
+    pub fn with_sketch_seed(size: usize, seed: u64) -> TinyLfu {
+        let mut tlfu = TinyLfu::new(size);
+        tlfu.sketch = CountMinSketch::with_seed(tlfu.capacity, seed);
+        tlfu
+    }
+
+    /// A plain LRU over the whole capacity, without a frequency sketch.
+    ///
+    /// Every key lives in the window and eviction always takes the least
+    /// recently used one; there's no admission contest, no hill climbing and
+    /// no minimum admission frequency. Meant for tiny caches where the
+    /// window/main split degenerates and the sketch is pure overhead.
+    pub fn new_lru(size: usize) -> TinyLfu {
+        let mut tlfu = TinyLfu::new(size);
+        tlfu.window = Lru::new(tlfu.capacity);
+        tlfu.main = Slru::new(0);
+        tlfu.main.protected.capacity = 0;
+        tlfu.initial_window = tlfu.capacity;
+        tlfu.initial_protected = 0;
+        tlfu.sketch = CountMinSketch::disabled();
+        tlfu
+    }
+
+    /// Window capacity a new policy starts with, 1% of capacity.
+    fn initial_window_size(capacity: usize) -> usize {
+        ((capacity as f64 * 0.01) as usize).max(1)
     }
 
     #[cfg(test)]
@@ -92,29 +219,232 @@ impl TinyLfu {
             capacity: wsize + msize,
             window: Lru::new(wsize),
             main: Slru::new(msize),
+            initial_window: wsize,
+            initial_protected: psize,
             sketch: CountMinSketch::new(wsize + msize),
             hit_in_sample: 0,
             misses_in_sample: 0,
             hr: 0.0,
             step: -((wsize + msize) as f32) * 0.0625,
             amount: 0,
+            hashdos_threshold: ADMIT_HASHDOS_THRESHOLD,
+            min_admission_frequency: 0,
+            weight_writes: true,
+            overflow_allowance: 0,
+            segment_hits: [0; 3],
+            prefer_candidate_on_tie: false,
+            window_frozen: false,
+            admission_trace: VecDeque::new(),
+            admission_trace_cap: 0,
+            climber_trace: VecDeque::new(),
+            climber_trace_cap: 0,
+            victim_order: VictimOrder::ProbationFirst,
+            hot_window_promotion: 0,
+            demotion_repair: 0,
         };
         t.main.protected.capacity = psize;
         t
     }
 
+    pub fn set_window_policy(&mut self, policy: WindowPolicy) {
+        self.window.policy = policy;
+    }
+
+    /// Candidates estimated above this frequency may win a losing contest
+    /// through the pseudo-random tiebreak in `admit`.
+    pub fn set_hashdos_threshold(&mut self, threshold: usize) {
+        self.hashdos_threshold = threshold;
+    }
+
+    /// New keys estimated below this frequency, counting the current
+    /// insert, are rejected by `insert_new` instead of entering the window.
+    pub fn set_min_admission_frequency(&mut self, k: usize) {
+        self.min_admission_frequency = k;
+    }
+
+    /// When false, inserting a new key no longer counts towards its
+    /// frequency, only accesses do.
+    pub fn set_weight_writes(&mut self, weight_writes: bool) {
+        self.weight_writes = weight_writes;
+    }
+
+    pub fn weight_writes(&self) -> bool {
+        self.weight_writes
+    }
+
+    /// Sets the protected segment's capacity, demoting its least recently
+    /// used keys to probation if it shrinks.
+    ///
+    /// The hill climber's current sample is discarded, so its next
+    /// adjustment measures the hit rate under the new split instead of
+    /// reacting to the old one.
+    pub fn set_protected_capacity(
+        &mut self,
+        cap: usize,
+        entries: &mut HashMap<u64, Entry>,
+    ) -> Result<()> {
+        let main_capacity = self.capacity.saturating_sub(self.window.list.capacity);
+        if cap > main_capacity {
+            anyhow::bail!(
+                "protected capacity {} exceeds main capacity {}",
+                cap,
+                main_capacity
+            );
+        }
+        self.main.protected.capacity = cap;
+        self.demote_from_protected(entries);
+        self.hit_in_sample = 0;
+        self.misses_in_sample = 0;
+        self.amount = 0;
+        Ok(())
+    }
+
+    /// Sets the window's capacity, taking the difference from or giving it
+    /// back to the protected segment so the total capacity stays the same.
+    ///
+    /// A shrinking window moves its least recently used keys to probation
+    /// right away; a growing one fills up with new keys. As with
+    /// `set_protected_capacity` the hill climber's sample is discarded.
+    pub fn set_window_capacity(
+        &mut self,
+        cap: usize,
+        entries: &mut HashMap<u64, Entry>,
+    ) -> Result<()> {
+        if cap == 0 || cap >= self.capacity {
+            anyhow::bail!(
+                "window capacity {} must be at least 1 and below capacity {}",
+                cap,
+                self.capacity
+            );
+        }
+        let previous = self.window.list.capacity;
+        self.window.list.capacity = cap;
+        self.main.protected.capacity = (self.main.protected.capacity + previous)
+            .saturating_sub(cap)
+            .min(self.capacity - cap);
+        let overflow = self.window.len().saturating_sub(cap);
+        if overflow > 0 {
+            self.decrease_window(overflow as isize, entries)?;
+        }
+        self.demote_from_protected(entries);
+        self.hit_in_sample = 0;
+        self.misses_in_sample = 0;
+        self.amount = 0;
+        Ok(())
+    }
+
+    /// Keeps the last `capacity` admission contests, 0 disables and clears.
+    pub fn set_admission_trace(&mut self, capacity: usize) {
+        self.admission_trace_cap = capacity;
+        self.admission_trace = VecDeque::with_capacity(capacity);
+    }
+
+    /// Recorded `(candidate_freq, victim_freq, admitted)` contests, oldest first.
+    pub fn admission_trace(&self) -> Vec<(usize, usize, bool)> {
+        self.admission_trace.iter().copied().collect()
+    }
+
+    /// Keeps the last `capacity` hill climber steps, 0 disables and clears.
+    pub fn set_climber_trace(&mut self, capacity: usize) {
+        self.climber_trace_cap = capacity;
+        self.climber_trace = VecDeque::with_capacity(capacity);
+    }
+
+    /// Recorded `(sample_hr, delta, step, amount)` climbs, oldest first.
+    pub fn climber_trace(&self) -> Vec<(f32, f32, f32, isize)> {
+        self.climber_trace.iter().copied().collect()
+    }
+
+    /// Sets which list the admission contest takes its first victim from.
+    pub fn set_victim_order(&mut self, order: VictimOrder) {
+        self.victim_order = order;
+    }
+
+    /// When true, the hill climber stops adapting the window size.
+    pub fn set_window_frozen(&mut self, frozen: bool) {
+        self.window_frozen = frozen;
+    }
+
+    /// When true, a candidate whose frequency ties the victim's wins the
+    /// admission contest below the hashdos threshold.
+    pub fn set_prefer_candidate_on_tie(&mut self, prefer_candidate: bool) {
+        self.prefer_candidate_on_tie = prefer_candidate;
+    }
+
+    /// Window keys estimated above this frequency graduate straight into
+    /// protected while it has room, instead of probation. 0 disables it.
+    pub fn set_hot_window_promotion(&mut self, freq_threshold: usize) {
+        self.hot_window_promotion = freq_threshold;
+    }
+
+    /// Protected keys estimated above this frequency are passed over when
+    /// protected shrinks, the next colder key is demoted instead. Once only
+    /// such keys are left they are demoted from the tail as usual. 0
+    /// disables it.
+    pub fn set_demotion_repair(&mut self, freq_threshold: usize) {
+        self.demotion_repair = freq_threshold;
+    }
+
+    /// Lets the size exceed capacity by `extra` before `evict` kicks in.
+    pub fn set_overflow_allowance(&mut self, extra: usize) {
+        self.overflow_allowance = extra;
+    }
+
+    /// Exchanges the settings made through the setters with `other`, leaving
+    /// the contents, sketch and adapted geometry in place. Diagnostic traces
+    /// count as settings and move along with their capacities.
+    pub fn swap_config(&mut self, other: &mut TinyLfu) {
+        std::mem::swap(&mut self.hashdos_threshold, &mut other.hashdos_threshold);
+        std::mem::swap(
+            &mut self.min_admission_frequency,
+            &mut other.min_admission_frequency,
+        );
+        std::mem::swap(&mut self.weight_writes, &mut other.weight_writes);
+        std::mem::swap(&mut self.overflow_allowance, &mut other.overflow_allowance);
+        std::mem::swap(
+            &mut self.prefer_candidate_on_tie,
+            &mut other.prefer_candidate_on_tie,
+        );
+        std::mem::swap(&mut self.window_frozen, &mut other.window_frozen);
+        std::mem::swap(&mut self.admission_trace, &mut other.admission_trace);
+        std::mem::swap(
+            &mut self.admission_trace_cap,
+            &mut other.admission_trace_cap,
+        );
+        std::mem::swap(&mut self.climber_trace, &mut other.climber_trace);
+        std::mem::swap(&mut self.climber_trace_cap, &mut other.climber_trace_cap);
+        std::mem::swap(&mut self.victim_order, &mut other.victim_order);
+        std::mem::swap(&mut self.window.policy, &mut other.window.policy);
+        std::mem::swap(
+            &mut self.hot_window_promotion,
+            &mut other.hot_window_promotion,
+        );
+        std::mem::swap(&mut self.demotion_repair, &mut other.demotion_repair);
+    }
+
     fn increase_window(
         &mut self,
         amount: isize,
         entries: &mut HashMap<u64, Entry>,
     ) -> Result<isize> {
         let mut amount = amount;
+        let max_iterations = amount.unsigned_abs().saturating_mul(MOVE_ITERATIONS_FACTOR);
+        let mut iterations = 0;
 
         // try move from protected/probation to window
         loop {
             if amount <= 0 {
                 break;
             }
+            if iterations >= max_iterations {
+                log::warn!(
+                    "TinyLFU increase_window: gave up after {} iterations with {} entries left to move",
+                    iterations,
+                    amount
+                );
+                break;
+            }
+            iterations += 1;
             let mut key = self.main.probation.tail();
             if key.is_none() {
                 key = self.main.protected.tail()
@@ -122,7 +452,6 @@ impl TinyLfu {
             if key.is_none() {
                 break;
             }
-            amount -= 1;
             if let Some(&k) = key
                 && let Some(entry) = entries.get_mut(&k)
             {
@@ -132,10 +461,11 @@ impl TinyLfu {
                         k,
                         e
                     );
-                    // Continue despite error to avoid deadlock
+                    // Continue despite error, bounded by max_iterations
                     continue;
                 }
                 self.window.insert(k, entry);
+                amount -= 1;
             }
         }
         Ok(amount)
@@ -147,17 +477,27 @@ impl TinyLfu {
         entries: &mut HashMap<u64, Entry>,
     ) -> Result<isize> {
         let mut amount = amount;
+        let max_iterations = amount.unsigned_abs().saturating_mul(MOVE_ITERATIONS_FACTOR);
+        let mut iterations = 0;
 
         // try move from window to probation
         loop {
             if amount <= 0 {
                 break;
             }
+            if iterations >= max_iterations {
+                log::warn!(
+                    "TinyLFU decrease_window: gave up after {} iterations with {} entries left to move",
+                    iterations,
+                    amount
+                );
+                break;
+            }
+            iterations += 1;
             let key = self.window.list.tail();
             if key.is_none() {
                 break;
             }
-            amount -= 1;
             if let Some(&k) = key
                 && let Some(entry) = entries.get_mut(&k)
             {
@@ -167,10 +507,11 @@ impl TinyLfu {
                         k,
                         e
                     );
-                    // Continue despite error to avoid deadlock
+                    // Continue despite error, bounded by max_iterations
                     continue;
                 }
                 self.main.insert(k, entry);
+                amount -= 1;
             }
         }
         Ok(amount)
@@ -179,7 +520,26 @@ impl TinyLfu {
     // move entry from protected to probation
     fn demote_from_protected(&mut self, entries: &mut HashMap<u64, Entry>) {
         let mut demoted_count = 0;
+        let mut cursor = self.main.protected.tail().copied();
         while self.main.protected.len() > self.main.protected.capacity {
+            if self.demotion_repair > 0 {
+                // walk past hot keys, resuming where the last search stopped
+                while let Some(key) = cursor
+                    && self.sketch.estimate(key) > self.demotion_repair
+                {
+                    cursor = self.prev_key(Some(key), entries);
+                }
+                if let Some(key) = cursor
+                    && let Some(entry) = entries.get_mut(&key)
+                    && let Some(index) = entry.policy_list_index
+                {
+                    cursor = self.main.protected.prev(index).copied();
+                    self.main.protected.remove(index);
+                    self.main.insert(key, entry);
+                    demoted_count += 1;
+                    continue;
+                }
+            }
             if let Some(key) = self.main.protected.pop_tail()
                 && let Some(entry) = entries.get_mut(&key)
             {
@@ -200,19 +560,18 @@ impl TinyLfu {
     }
 
     fn resize_window(&mut self, entries: &mut HashMap<u64, Entry>) -> Result<()> {
-        // Validate capacity adjustments won't go negative or zero
-        let new_window_cap = self
-            .window
-            .list
-            .capacity
-            .saturating_add_signed(self.amount)
-            .max(1);
+        // Clamp the move so window keeps at least 1 slot and protected doesn't
+        // go negative, anything else would change the total capacity
+        self.amount = self.amount.clamp(
+            -(self.window.list.capacity.saturating_sub(1) as isize),
+            self.main.protected.capacity as isize,
+        );
+        let new_window_cap = self.window.list.capacity.saturating_add_signed(self.amount);
         let new_protected_cap = self
             .main
             .protected
             .capacity
-            .saturating_add_signed(-self.amount)
-            .max(1);
+            .saturating_add_signed(-self.amount);
 
         log::debug!(
             "TinyLFU resize_window: amount={}, new_window_cap={}, new_protected_cap={}",
@@ -281,23 +640,53 @@ impl TinyLfu {
         self.amount = amount as isize;
 
         // decrease protected, min protected is 0
-        if self.amount > 0 && self.amount as usize > self.main.protected.list.capacity() {
-            self.amount = self.main.protected.list.capacity() as isize;
+        if self.amount > 0 && self.amount as usize > self.main.protected.capacity {
+            self.amount = self.main.protected.capacity as isize;
         }
 
         if self.amount < 0 && self.amount.unsigned_abs() > (self.window.list.capacity - 1) {
             self.amount = -((self.window.list.capacity - 1) as isize);
         }
+
+        if self.climber_trace_cap > 0 {
+            if self.climber_trace.len() == self.climber_trace_cap {
+                self.climber_trace.pop_front();
+            }
+            self.climber_trace
+                .push_back((self.hr, delta, self.step, self.amount));
+        }
     }
 
     // add/update key
-    pub fn set(&mut self, key: u64, entries: &mut HashMap<u64, Entry>) -> Result<Option<u64>> {
+    #[cfg(test)]
+    pub fn set(&mut self, key: u64, entries: &mut HashMap<u64, Entry>) -> Result<Vec<u64>> {
+        if !self.insert_new(key, entries, false, true)? {
+            return Ok(Vec::new());
+        }
+        self.evict(entries)
+    }
+
+    /// Adds a new key without evicting, so the policy may exceed its capacity
+    /// until `evict` is called. Lets a batch insert run eviction once. With
+    /// `skip_window` the key starts in probation instead of the window.
+    ///
+    /// Returns `false` if the key was rejected by the minimum admission
+    /// frequency, in which case only its sketch counter was bumped.
+    pub fn insert_new(
+        &mut self,
+        key: u64,
+        entries: &mut HashMap<u64, Entry>,
+        skip_window: bool,
+        count_write: bool,
+    ) -> Result<bool> {
         // Validate key is not zero (reserved value)
         if key == 0 {
             log::warn!("TinyLFU set: key is 0, which is reserved");
         }
 
-        if self.hit_in_sample + self.misses_in_sample > self.sketch.sample_size {
+        if !self.window_frozen
+            && self.hit_in_sample + self.misses_in_sample > self.sketch.sample_size
+        {
             self.climb();
             self.resize_window(entries)?;
         }
@@ -306,14 +695,51 @@ impl TinyLfu {
             // new entry
             if entry.policy_list_id == 0 {
                 self.misses_in_sample = self.misses_in_sample.saturating_add(1);
-                self.window.insert(key, entry);
+                if self.weight_writes && count_write {
+                    self.sketch.add(key);
+                }
+                if self.sketch.is_enabled()
+                    && self.sketch.estimate(key) < self.min_admission_frequency
+                {
+                    return Ok(false);
+                }
+                if skip_window && self.sketch.is_enabled() {
+                    self.main.insert(key, entry);
+                } else {
+                    self.window.insert(key, entry);
+                }
                 self.size = self.size.saturating_add(1);
-                self.sketch.add(key);
             }
         }
 
         self.demote_from_protected(entries);
-        self.evict_entries(entries)
+        Ok(true)
+    }
+
+    /// Evicts once the size exceeds capacity plus the overflow allowance,
+    /// returning every evicted key.
+    #[cfg(test)]
+    pub fn evict(&mut self, entries: &mut HashMap<u64, Entry>) -> Result<Vec<u64>> {
+        self.evict_graced(entries, 0)
+    }
+
+    /// Like `evict`, but passes over keys inserted at or after `graced_from`
+    /// as long as another key can be evicted instead. 0 disables the grace.
+    pub fn evict_graced(
+        &mut self,
+        entries: &mut HashMap<u64, Entry>,
+        graced_from: u64,
+    ) -> Result<Vec<u64>> {
+        self.evict_entries(
+            self.capacity + self.overflow_allowance,
+            graced_from,
+            entries,
+        )
+    }
+
+    /// Evicts down to the hard capacity, ignoring the overflow allowance.
+    pub fn trim(&mut self, entries: &mut HashMap<u64, Entry>) -> Result<Vec<u64>> {
+        self.evict_entries(self.capacity, 0, entries)
     }
 
     /// Mark access, update sketch and lru/slru
@@ -323,7 +749,9 @@ impl TinyLfu {
         clock: &Clock,
         entries: &mut HashMap<u64, Entry>,
     ) -> Result<()> {
-        if self.hit_in_sample + self.misses_in_sample > self.sketch.sample_size {
+        if !self.window_frozen
+            && self.hit_in_sample + self.misses_in_sample > self.sketch.sample_size
+        {
             self.climb();
             self.resize_window(entries)?;
         }
@@ -331,14 +759,28 @@ impl TinyLfu {
 
         if let Some(entry) = entries.get_mut(&key) {
             self.hit_in_sample = self.hit_in_sample.saturating_add(1);
-            if entry.expire != 0 && entry.expire <= clock.now_ns() {
-                return Ok(());
+            let deadline = entry.deadline();
+            if deadline != 0 {
+                let now = clock.now_ns();
+                if deadline <= now {
+                    return Ok(());
+                }
+                entry.last_access = now;
+            }
+            entry.accessed = true;
+            entry.access_count = entry.access_count.saturating_add(1);
+
+            if let Some(hits) = (entry.policy_list_id as usize)
+                .checked_sub(1)
+                .and_then(|segment| self.segment_hits.get_mut(segment))
+            {
+                *hits = hits.saturating_add(1);
             }
 
             if let Some(index) = entry.policy_list_index {
                 match entry.policy_list_id {
                     1 => {
-                        self.window.access(index);
+                        self.window.access(index, entry);
                         Ok(())
                     }
                     2 | 3 => self.main.access(key, entries),
@@ -370,6 +812,251 @@ impl TinyLfu {
         self.size
     }
 
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn protected_capacity(&self) -> usize {
+        self.main.protected.capacity
+    }
+
+    /// Lifetime hits on live entries by the segment that served them:
+    /// `(window, probation, protected)`.
+    pub fn segment_hits(&self) -> (u64, u64, u64) {
+        let [window, probation, protected] = self.segment_hits;
+        (window, probation, protected)
+    }
+
+    /// Effective segment capacities and sketch size, as currently adapted.
+    pub fn geometry(&self) -> HashMap<String, usize> {
+        let window = self.window.list.capacity;
+        let protected = self.main.protected.capacity;
+        HashMap::from([
+            ("capacity".to_string(), self.capacity),
+            ("window".to_string(), window),
+            (
+                "probation".to_string(),
+                self.capacity.saturating_sub(window + protected),
+            ),
+            ("protected".to_string(), protected),
+            ("sketch".to_string(), self.sketch.table_size()),
+        ])
+    }
+
+    /// Drops every key from the window and main lists.
+    ///
+    /// The sketch and the adapted window size are kept, so frequency history
+    /// survives the clear.
+    pub fn clear(&mut self) {
+        self.window.list.clear();
+        self.main.probation.clear();
+        self.main.protected.clear();
+        self.size = 0;
+        self.hit_in_sample = 0;
+        self.misses_in_sample = 0;
+    }
+
+    /// Like `clear`, but also forgets all frequency history and puts the
+    /// segment split and the hill climber back to their initial state.
+    ///
+    /// The split is the one the policy was constructed with, so a `new_lru`
+    /// policy stays a plain LRU. Settings such as the window policy or
+    /// admission thresholds are kept.
+    pub fn full_reset(&mut self) {
+        let policy = self.window.policy;
+        self.window = Lru::new(self.initial_window);
+        self.window.policy = policy;
+        self.main = Slru::new(self.capacity - self.initial_window);
+        self.main.protected.capacity = self.initial_protected;
+        self.sketch.clear();
+        self.size = 0;
+        self.hit_in_sample = 0;
+        self.misses_in_sample = 0;
+        self.hr = 0.0;
+        self.step = -(self.capacity as f32) * HILL_CLIMBER_STEP_PERCENT;
+        self.amount = 0;
+        self.admission_trace.clear();
+        self.climber_trace.clear();
+    }
+
+    /// Keys in policy order: window, then probation, then protected, each
+    /// segment most recently used first.
+    pub fn ordered_keys(&self) -> Vec<u64> {
+        self.window
+            .list
+            .iter()
+            .chain(self.main.probation.iter())
+            .chain(self.main.protected.iter())
+            .copied()
+            .collect()
+    }
+
+    /// Moves a window key to the window front without counting an access.
+    ///
+    /// # Returns
+    ///
+    /// `true` if the key was in the window, `false` if it's elsewhere
+    pub fn touch_window(&mut self, key: u64, entries: &HashMap<u64, Entry>) -> bool {
+        match entries.get(&key) {
+            Some(entry) if entry.policy_list_id == 1 => {
+                if let Some(index) = entry.policy_list_index {
+                    self.window.list.touch(index);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Places a new key directly into the given segment, used when importing
+    /// exported state. Keys restored in eviction order rebuild each segment's
+    /// recency order. Call `rebalance` once all keys are restored.
+    pub fn restore(&mut self, key: u64, policy_list_id: u8, entry: &mut Entry) {
+        match policy_list_id {
+            3 => self.main.insert_protected(key, entry),
+            2 => self.main.insert(key, entry),
+            _ => self.window.insert(key, entry),
+        }
+        self.size = self.size.saturating_add(1);
+    }
+
+    /// Replaces `old_key` with `new_key` in place in the entry's segment, and
+    /// carries the old key's estimated frequency over to the new key.
+    pub fn rename(&mut self, old_key: u64, new_key: u64, entry: &Entry) -> Result<()> {
+        if entry.policy_list_id != 0 {
+            let list = match entry.policy_list_id {
+                1 => &mut self.window.list,
+                2 => &mut self.main.probation,
+                3 => &mut self.main.protected,
+                id => anyhow::bail!(
+                    "TinyLFU rename: unexpected policy_list_id {}, this indicates a bug",
+                    id
+                ),
+            };
+            let replaced = entry
+                .policy_list_index
+                .and_then(|index| list.replace(index, new_key));
+            if replaced != Some(old_key) {
+                anyhow::bail!(
+                    "TinyLFU rename: key {} not found at its policy index, this indicates a bug",
+                    old_key
+                );
+            }
+        }
+
+        let frequency = self.sketch.estimate(old_key);
+        for _ in self.sketch.estimate(new_key)..frequency {
+            self.sketch.add(new_key);
+        }
+        Ok(())
+    }
+
+    /// Moves overflow out of the protected and window segments after a
+    /// restore, returning any keys evicted if the cache is over capacity.
+    pub fn rebalance(&mut self, entries: &mut HashMap<u64, Entry>) -> Result<Vec<u64>> {
+        self.demote_from_protected(entries);
+        self.trim(entries)
+    }
+
+    /// Checks the policy lists against the cache entries.
+    ///
+    /// Verifies that every entry's `policy_list_index` resolves to its own
+    /// key in the list named by `policy_list_id`, that `size` matches the
+    /// segment lengths, and that every listed key is a resident entry
+    /// pointing back at that list.
+    pub fn consistency_issues(&self, entries: &HashMap<u64, Entry>) -> Vec<String> {
+        let mut issues = Vec::new();
+        let segments = [
+            (1, "window", &self.window.list),
+            (2, "probation", &self.main.probation),
+            (3, "protected", &self.main.protected),
+        ];
+
+        for (&key, entry) in entries {
+            match (entry.policy_list_id, entry.policy_list_index) {
+                (0, None) => {}
+                (0, Some(_)) => issues.push(format!(
+                    "key {}: not in policy but has a policy_list_index",
+                    key
+                )),
+                (id @ 1..=3, None) => {
+                    issues.push(format!("key {}: list {} but no policy_list_index", key, id))
+                }
+                (id @ 1..=3, Some(index)) => {
+                    let (_, name, list) = segments[id as usize - 1];
+                    if list.get(index) != Some(&key) {
+                        issues.push(format!(
+                            "key {}: policy_list_index doesn't resolve to it in {}",
+                            key, name
+                        ));
+                    }
+                }
+                (id, _) => issues.push(format!("key {}: invalid policy_list_id {}", key, id)),
+            }
+        }
+
+        if let Some((size, listed)) = self.size_drift() {
+            issues.push(format!(
+                "size {} doesn't match segment lengths totalling {}",
+                size, listed
+            ));
+        }
+
+        for (id, name, list) in segments {
+            for key in list.iter() {
+                match entries.get(key) {
+                    None => issues.push(format!("key {}: in {} but not in entries", key, name)),
+                    Some(entry) if entry.policy_list_id != id => issues.push(format!(
+                        "key {}: in {} but its entry names list {}",
+                        key, name, entry.policy_list_id
+                    )),
+                    Some(_) => {}
+                }
+            }
+        }
+
+        issues
+    }
+
+    /// The tracked size and the segment lengths' total, if they disagree.
+    ///
+    /// Unlike `consistency_issues` this doesn't walk the entries, the
+    /// segments keep their lengths.
+    pub fn size_drift(&self) -> Option<(usize, usize)> {
+        let listed = self.window.len() + self.main.probation.len() + self.main.protected.len();
+        (listed != self.size).then_some((self.size, listed))
+    }
+
+    #[cfg(test)]
+    pub fn force_size(&mut self, size: usize) {
+        self.size = size;
+    }
+
+    /// Keys in the order capacity eviction considers them: by default
+    /// probation, then protected, then window (see `VictimOrder`), each
+    /// segment least recently used first.
+    pub fn eviction_order(&self) -> Vec<u64> {
+        self.victim_order
+            .queues()
+            .iter()
+            .flat_map(|queue| self.list_of(queue).iter().rev())
+            .copied()
+            .collect()
+    }
+
+    /// First key of `eviction_order`, without materializing the rest.
+    pub fn coldest_key(&self) -> Option<u64> {
+        self.victim_order
+            .queues()
+            .iter()
+            .find_map(|queue| self.tail_of(queue))
+    }
+
+    /// Current window capacity as a fraction of total capacity.
+    pub fn window_ratio(&self) -> f64 {
+        self.window.list.capacity as f64 / self.capacity as f64
+    }
+
     // remove key
     pub fn remove(&mut self, entry: &mut Entry) -> Result<()> {
         match entry.policy_list_id {
@@ -398,81 +1085,82 @@ impl TinyLfu {
     fn evict_from_window(&mut self, entries: &mut HashMap<u64, Entry>) -> Option<u64> {
         let mut first = None;
         while self.window.len() > self.window.list.capacity {
-            if let Some(evicted) = self.window.list.pop_tail() {
+            if let Some(evicted) = self.window.pop_tail(entries) {
+                let Some(entry) = entries.get_mut(&evicted) else {
+                    continue;
+                };
+                if self.hot_window_promotion > 0
+                    && self.main.protected.len() < self.main.protected.capacity
+                    && self.sketch.estimate(evicted) > self.hot_window_promotion
+                {
+                    // hot keys skip the admission contest
+                    self.main.insert_protected(evicted, entry);
+                    continue;
+                }
                 if first.is_none() {
                     first = Some(evicted);
                 }
-                if let Some(entry) = entries.get_mut(&evicted) {
-                    self.main.insert(evicted, entry);
-                }
+                self.main.insert(evicted, entry);
             }
         }
         first
     }
 
-    // comapre and evict entries until cache size fit.
+    // comapre and evict entries until cache size fits limit.
     // candidate is the first entry evicted from window,
     // if head is null, start from last entry from window.
+    /// Runs the admission contest between window candidates and main
+    /// victims until the size is within `limit`.
+    ///
+    /// With a non-zero `graced_from`, keys inserted at or after that time are
+    /// passed over instead of evicted, so the contest may stop early with the
+    /// size still above `limit` once every remaining key is graced.
     fn evict_from_main(
         &mut self,
         candidate: Option<u64>,
+        limit: usize,
+        graced_from: u64,
         entries: &mut HashMap<u64, Entry>,
-    ) -> Result<Option<u64>> {
-        let mut victim_queue = PolicyList::Probation;
+    ) -> Result<Vec<u64>> {
+        let victim_queues = self.victim_order.queues();
+        let mut victim_stage = 0;
         let mut candidate_queue = PolicyList::Probation;
-        let mut victim = self.main.probation.tail().copied();
+        let mut victim = self.tail_of(&victim_queues[0]);
         let mut candidate = candidate;
-        let mut evicted = None;
+        let mut evicted = Vec::new();
 
-        while self.size > self.capacity {
+        while self.size > limit {
             if candidate.is_none() && candidate_queue == PolicyList::Probation {
                 candidate = self.window.list.tail().copied();
                 candidate_queue = PolicyList::Window;
             }
 
             if candidate.is_none() && victim.is_none() {
-                if victim_queue == PolicyList::Probation {
-                    victim = self.main.protected.tail().copied();
-                    victim_queue = PolicyList::Protected;
-                    continue;
-                } else if victim_queue == PolicyList::Protected {
-                    victim = self.window.list.tail().copied();
-                    victim_queue = PolicyList::Window;
-                    continue;
-                }
+                victim_stage += 1;
+                let Some(queue) = victim_queues.get(victim_stage) else {
+                    // every remaining key was passed over as graced
+                    break;
+                };
+                victim = self.tail_of(queue);
+                continue;
             }
 
             if victim.is_none() {
                 let prev = self.prev_key(candidate, entries);
                 let evict = candidate;
                 candidate = prev;
-                if let Some(key) = evict
-                    && let Some(entry) = entries.get_mut(&key)
-                {
-                    self.remove(entry)?;
-                    evicted = Some(key);
-                }
+                self.evict_key(evict, graced_from, entries, &mut evicted)?;
                 continue;
             } else if candidate.is_none() {
                 let evict = victim;
                 victim = self.prev_key(victim, entries);
-                if let Some(key) = evict
-                    && let Some(entry) = entries.get_mut(&key)
-                {
-                    self.remove(entry)?;
-                    evicted = Some(key);
-                }
+                self.evict_key(evict, graced_from, entries, &mut evicted)?;
                 continue;
             }
 
             if victim == candidate {
                 victim = self.prev_key(victim, entries);
-                if let Some(key) = candidate
-                    && let Some(entry) = entries.get_mut(&key)
-                {
-                    self.remove(entry)?;
-                    evicted = Some(key);
-                }
+                self.evict_key(candidate, graced_from, entries, &mut evicted)?;
                 candidate = None;
                 continue;
             }
@@ -481,28 +1169,30 @@ impl TinyLfu {
                 if self.admit(c, v) {
                     let evict = victim;
                     victim = self.prev_key(victim, entries);
-                    if let Some(key) = evict
-                        && let Some(entry) = entries.get_mut(&key)
-                    {
-                        self.remove(entry)?;
-                        evicted = Some(key);
-                    }
+                    self.evict_key(evict, graced_from, entries, &mut evicted)?;
                     candidate = self.prev_key(candidate, entries);
                 } else {
                     let evict = candidate;
                     candidate = self.prev_key(candidate, entries);
-                    if let Some(key) = evict
-                        && let Some(entry) = entries.get_mut(&key)
-                    {
-                        self.remove(entry)?;
-                        evicted = Some(key);
-                    }
+                    self.evict_key(evict, graced_from, entries, &mut evicted)?;
                 }
             }
         }
         Ok(evicted)
     }
 
+    fn list_of(&self, list: &PolicyList) -> &List<u64> {
+        match list {
+            PolicyList::Window => &self.window.list,
+            PolicyList::Probation => &self.main.probation,
+            PolicyList::Protected => &self.main.protected,
+        }
+    }
+
+    fn tail_of(&self, list: &PolicyList) -> Option<u64> {
+        self.list_of(list).tail().copied()
+    }
+
     fn prev_key(&self, key: Option<u64>, entries: &mut HashMap<u64, Entry>) -> Option<u64> {
         if let Some(k) = key {
             if let Some(entry) = entries.get(&k) {
@@ -523,25 +1213,95 @@ impl TinyLfu {
         }
     }
 
-    fn evict_entries(&mut self, entries: &mut HashMap<u64, Entry>) -> Result<Option<u64>> {
+    /// Eviction without a sketch: drops least recently used keys, probation
+    /// and protected ones first as those only come from restored state.
+    fn evict_lru(&mut self, limit: usize, entries: &mut HashMap<u64, Entry>) -> Result<Vec<u64>> {
+        let mut evicted = Vec::new();
+        while self.size > limit {
+            let Some(key) = self
+                .main
+                .probation
+                .tail()
+                .or_else(|| self.main.protected.tail())
+                .or_else(|| self.window.list.tail())
+                .copied()
+            else {
+                break;
+            };
+            let Some(entry) = entries.get_mut(&key) else {
+                anyhow::bail!(
+                    "TinyLFU evict: listed key {} has no entry, this indicates a bug",
+                    key
+                );
+            };
+            self.remove(entry)?;
+            evicted.push(key);
+        }
+        Ok(evicted)
+    }
+
+    /// Evicts `key` unless it was inserted at or after a non-zero `graced_from`.
+    fn evict_key(
+        &mut self,
+        key: Option<u64>,
+        graced_from: u64,
+        entries: &mut HashMap<u64, Entry>,
+        evicted: &mut Vec<u64>,
+    ) -> Result<()> {
+        if let Some(key) = key
+            && let Some(entry) = entries.get_mut(&key)
+        {
+            if graced_from > 0 && entry.inserted_at >= graced_from {
+                log::trace!("Sparing key {} inserted within the grace period", key);
+                return Ok(());
+            }
+            self.remove(entry)?;
+            evicted.push(key);
+        }
+        Ok(())
+    }
+
+    fn evict_entries(
+        &mut self,
+        limit: usize,
+        graced_from: u64,
+        entries: &mut HashMap<u64, Entry>,
+    ) -> Result<Vec<u64>> {
+        if !self.sketch.is_enabled() {
+            return self.evict_lru(limit, entries);
+        }
         let first = self.evict_from_window(entries);
-        self.evict_from_main(first, entries)
+        let mut evicted = self.evict_from_main(first, limit, graced_from, entries)?;
+        if self.size > limit && graced_from > 0 {
+            // only graced keys are left, capacity still wins
+            evicted.extend(self.evict_from_main(None, limit, 0, entries)?);
+        }
+        Ok(evicted)
     }
 
-    fn admit(&self, candidate: u64, victim: u64) -> bool {
+    fn admit(&mut self, candidate: u64, victim: u64) -> bool {
         let victim_freq = self.sketch.estimate(victim);
         let candidate_freq = self.sketch.estimate(candidate);
 
-        if candidate_freq > victim_freq {
+        let admitted = if candidate_freq > victim_freq {
             true
-        } else if candidate_freq > ADMIT_HASHDOS_THRESHOLD {
+        } else if candidate_freq > self.hashdos_threshold {
             // Use deterministic comparison based on hash values for robustness
             // This avoids relying on RNG state and provides consistent behavior
             let combined = candidate.wrapping_add(victim);
             (combined & 127) == 0
         } else {
-            false
+            self.prefer_candidate_on_tie && candidate_freq == victim_freq
+        };
+
+        if self.admission_trace_cap > 0 {
+            if self.admission_trace.len() == self.admission_trace_cap {
+                self.admission_trace.pop_front();
+            }
+            self.admission_trace
+                .push_back((candidate_freq, victim_freq, admitted));
         }
+        admitted
     }
 
     pub fn debug_info(&self) -> DebugInfo {
@@ -552,18 +1312,48 @@ impl TinyLfu {
             protected_len: self.main.protected.len(),
         }
     }
+
+    pub fn entry_info(&self, key: u64, entry: &Entry) -> EntryInfo {
+        EntryInfo {
+            policy_list_id: entry.policy_list_id,
+            expire: entry.expire,
+            wheel_index: entry.wheel_index,
+            in_window: entry.policy_list_id == 1,
+            in_probation: entry.policy_list_id == 2,
+            in_protected: entry.policy_list_id == 3,
+            frequency: self.sketch.estimate(key),
+        }
+    }
 }
 
 #[pyclass]
 pub struct DebugInfo {
     #[pyo3(get)]
-    len: usize,
+    pub(crate) len: usize,
+    #[pyo3(get)]
+    pub(crate) window_len: usize,
+    #[pyo3(get)]
+    pub(crate) probation_len: usize,
+    #[pyo3(get)]
+    pub(crate) protected_len: usize,
+}
+
+#[pyclass]
+pub struct EntryInfo {
+    #[pyo3(get)]
+    policy_list_id: u8,
     #[pyo3(get)]
-    window_len: usize,
+    expire: u64,
     #[pyo3(get)]
-    probation_len: usize,
+    wheel_index: (u8, u8),
     #[pyo3(get)]
-    protected_len: usize,
+    in_window: bool,
+    #[pyo3(get)]
+    in_probation: bool,
+    #[pyo3(get)]
+    in_protected: bool,
+    #[pyo3(get)]
+    frequency: usize,
 }
 
 #[cfg(test)]
@@ -571,10 +1361,11 @@ mod tests {
     use std::collections::HashMap;
     use std::str::FromStr;
 
+    use crate::lru::WindowPolicy;
     use crate::metadata::Entry;
     use crate::timerwheel::Clock;
 
-    use super::TinyLfu;
+    use super::{TinyLfu, VictimOrder};
 
     fn group_numbers(input: Vec<String>) -> String {
         if input.is_empty() {
@@ -711,7 +1502,7 @@ mod tests {
                     // Test setup error - continue with other entries
                 }
             }
-            if let Err(_) = tlfu.evict_entries(&mut entries) {
+            if let Err(_) = tlfu.evict(&mut entries) {
                 // Test eviction error - continue with test
             }
 
@@ -750,12 +1541,374 @@ mod tests {
 
         for i in 0..200 {
             let evicted = tlfu.set(i, &mut entries).unwrap_or_default();
-            assert!(evicted.is_none());
+            assert!(evicted.is_empty());
         }
 
         for i in 0..200 {
             let evicted = tlfu.set(i, &mut entries).unwrap_or_default();
-            assert!(evicted.is_none());
+            assert!(evicted.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_tlfu_fifo_window_second_chance() {
+        let mut tlfu = TinyLfu::new_sized(2, 10, 8);
+        tlfu.set_window_policy(WindowPolicy::Fifo);
+        let mut entries = HashMap::new();
+        let clock = Clock::new();
+
+        for i in [1, 2] {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        // FIFO access only marks the entry, window order is unchanged
+        tlfu.access(1, &clock, &mut entries).unwrap();
+        assert!(entries[&1].referenced);
+        assert_eq!(tlfu.window.list.tail(), Some(&1));
+
+        // 1 is at the tail but referenced, so 2 leaves the window instead
+        entries.insert(3, Entry::new());
+        tlfu.set(3, &mut entries).unwrap();
+        assert_eq!(entries[&1].policy_list_id, 1);
+        assert!(!entries[&1].referenced);
+        assert_eq!(entries[&2].policy_list_id, 2);
+        assert_eq!(entries[&3].policy_list_id, 1);
+        assert_eq!(
+            tlfu.window.list.iter().copied().collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+    }
+
+    #[test]
+    fn test_tlfu_hashdos_threshold() {
+        let mut tlfu = TinyLfu::new(100);
+        // candidate + victim = 128, so the tiebreak admits once enabled
+        let (candidate, victim) = (1, 127);
+        for _ in 0..3 {
+            tlfu.sketch.add(candidate);
+        }
+        for _ in 0..5 {
+            tlfu.sketch.add(victim);
+        }
+        assert!(!tlfu.admit(candidate, victim));
+
+        tlfu.set_hashdos_threshold(2);
+        assert!(tlfu.admit(candidate, victim));
+    }
+
+    #[test]
+    fn test_tlfu_window_ratio() {
+        let tlfu = TinyLfu::new(1000);
+        assert!((tlfu.window_ratio() - 0.01).abs() < f64::EPSILON);
+
+        let mut tlfu = TinyLfu::new_sized(50, 100, 80);
+        let mut entries = HashMap::new();
+        for i in 0..150 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
         }
+        assert!((tlfu.window_ratio() - 50.0 / 150.0).abs() < 1e-9);
+
+        // hit rate drop grows the window
+        tlfu.hr = 0.5;
+        tlfu.hit_in_sample = 10;
+        tlfu.misses_in_sample = 90;
+        tlfu.climb();
+        tlfu.resize_window(&mut entries).unwrap();
+        assert!(tlfu.window_ratio() > 50.0 / 150.0);
+    }
+
+    #[test]
+    fn test_tlfu_window_move_loops_terminate() {
+        let mut tlfu = TinyLfu::new_sized(10, 20, 10);
+        let mut entries = HashMap::new();
+        for i in 0..30 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+
+        // a probation tail that always fails to be removed
+        let tail = *tlfu.main.probation.tail().unwrap();
+        entries.get_mut(&tail).unwrap().policy_list_index = None;
+        let remain = tlfu.increase_window(5, &mut entries).unwrap();
+        assert_eq!(remain, 5);
+        assert_eq!(tlfu.window.len(), 10);
+
+        // a window tail missing from entries
+        let tail = *tlfu.window.list.tail().unwrap();
+        entries.remove(&tail);
+        let remain = tlfu.decrease_window(5, &mut entries).unwrap();
+        assert_eq!(remain, 5);
+        assert_eq!(tlfu.window.len(), 10);
+    }
+
+    #[test]
+    fn test_tlfu_entry_info() {
+        let mut tlfu = TinyLfu::new_sized(1, 10, 5);
+        let mut entries = HashMap::new();
+        let clock = Clock::new();
+        for i in [1, 2] {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        entries.get_mut(&2).unwrap().expire = 42;
+
+        let info = tlfu.entry_info(2, &entries[&2]);
+        assert_eq!(info.policy_list_id, 1);
+        assert!(info.in_window && !info.in_probation && !info.in_protected);
+        assert_eq!(info.expire, 42);
+        assert_eq!(info.frequency, 1);
+
+        // 1 was pushed to probation, a second access promotes it
+        tlfu.access(1, &clock, &mut entries).unwrap();
+        let info = tlfu.entry_info(1, &entries[&1]);
+        assert_eq!(info.policy_list_id, 3);
+        assert!(info.in_protected && !info.in_window && !info.in_probation);
+        assert_eq!(info.frequency, 2);
+    }
+
+    #[test]
+    fn test_tlfu_swap_config() {
+        let mut a = TinyLfu::new(100);
+        let mut b = TinyLfu::new(100);
+        let mut entries = HashMap::new();
+        entries.insert(1, Entry::new());
+        a.set(1, &mut entries).unwrap();
+        a.set_min_admission_frequency(3);
+        a.set_overflow_allowance(5);
+        a.set_window_policy(WindowPolicy::Fifo);
+        a.set_victim_order(VictimOrder::WindowFirst);
+        a.set_demotion_repair(4);
+        b.set_weight_writes(false);
+
+        a.swap_config(&mut b);
+        assert_eq!((a.min_admission_frequency, a.overflow_allowance), (0, 0));
+        assert_eq!(a.window.policy, WindowPolicy::Lru);
+        assert_eq!(a.victim_order, VictimOrder::ProbationFirst);
+        assert!(!a.weight_writes);
+        assert_eq!((b.min_admission_frequency, b.overflow_allowance), (3, 5));
+        assert_eq!(b.window.policy, WindowPolicy::Fifo);
+        assert_eq!(b.victim_order, VictimOrder::WindowFirst);
+        assert_eq!(b.demotion_repair, 4);
+        // contents stay put
+        assert_eq!((a.len(), b.len()), (1, 0));
+    }
+
+    #[test]
+    fn test_tlfu_remove_clears_position() {
+        let mut tlfu = TinyLfu::new_sized(1, 10, 5);
+        let mut entries = HashMap::new();
+        let clock = Clock::new();
+        for i in [1, 2, 3] {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        tlfu.access(1, &clock, &mut entries).unwrap();
+        let ids: Vec<u8> = [1, 2, 3]
+            .iter()
+            .map(|k| entries[k].policy_list_id)
+            .collect();
+        assert_eq!(ids, vec![3, 2, 1]);
+
+        for key in [1, 2, 3] {
+            let entry = entries.get_mut(&key).unwrap();
+            tlfu.remove(entry).unwrap();
+            assert!(entry.policy_list_index.is_none());
+            assert_eq!(entry.policy_list_id, 0);
+            // a second remove is a no-op rather than touching a stale index
+            tlfu.remove(entry).unwrap();
+        }
+        assert_eq!(tlfu.len(), 0);
+    }
+
+    #[test]
+    fn test_tlfu_ordered_keys() {
+        let mut tlfu = TinyLfu::new_sized(2, 10, 5);
+        let mut entries = HashMap::new();
+        let clock = Clock::new();
+        for i in 1..=6 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        tlfu.access(2, &clock, &mut entries).unwrap();
+        tlfu.access(1, &clock, &mut entries).unwrap();
+
+        // window [6, 5], probation [4, 3], protected [1, 2]
+        assert_eq!(tlfu.ordered_keys(), vec![6, 5, 4, 3, 1, 2]);
+        assert_eq!(tlfu.eviction_order(), vec![3, 4, 2, 1, 5, 6]);
+    }
+
+    #[test]
+    fn test_tlfu_increase_window_tiny_protected() {
+        let mut tlfu = TinyLfu::new_sized(10, 40, 2);
+        let mut entries = HashMap::new();
+        let clock = Clock::new();
+        for i in 0..50 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        for i in 10..12 {
+            tlfu.access(i, &clock, &mut entries).unwrap();
+        }
+        assert_eq!(tlfu.main.protected.len(), 2);
+        let total = tlfu.window.list.capacity + tlfu.main.protected.capacity;
+
+        // far more than protected can give up
+        tlfu.amount = 30;
+        tlfu.resize_window(&mut entries).unwrap();
+        assert_eq!(
+            tlfu.window.list.capacity + tlfu.main.protected.capacity,
+            total
+        );
+        assert!(tlfu.window.len() <= tlfu.window.list.capacity);
+        assert!(tlfu.main.protected.len() <= tlfu.main.protected.capacity);
+
+        // and back the other way, past the window floor
+        tlfu.amount = -100;
+        tlfu.resize_window(&mut entries).unwrap();
+        assert_eq!(tlfu.window.list.capacity, 1);
+        assert_eq!(
+            tlfu.window.list.capacity + tlfu.main.protected.capacity,
+            total
+        );
+        assert!(tlfu.window.len() <= tlfu.window.list.capacity);
+        assert_eq!(tlfu.len(), 50);
+    }
+
+    #[test]
+    fn test_tlfu_consistency_issues_size() {
+        let mut tlfu = TinyLfu::new_sized(2, 10, 5);
+        let mut entries = HashMap::new();
+        for i in 1..=5 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        assert!(tlfu.consistency_issues(&entries).is_empty());
+
+        tlfu.size += 1;
+        assert_eq!(
+            tlfu.consistency_issues(&entries),
+            vec!["size 6 doesn't match segment lengths totalling 5".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_small_sketch_floor() {
+        // every constructor floors the sketch at 64 words, 8 blocks
+        assert_eq!(TinyLfu::new_sized(1, 1, 0).sketch.table_size(), 64);
+        assert_eq!(TinyLfu::new(0).sketch.table_size(), 64);
+        assert_eq!(
+            TinyLfu::with_max_sketch_size(1000, 0).sketch.table_size(),
+            64
+        );
+    }
+
+    #[test]
+    fn test_tlfu_full_reset_keeps_split() {
+        let mut tlfu = TinyLfu::new_sized(10, 40, 2);
+        let geometry = tlfu.geometry();
+        let mut entries = HashMap::new();
+        for i in 1..=50 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+
+        tlfu.full_reset();
+        assert_eq!(tlfu.geometry(), geometry);
+    }
+
+    #[test]
+    fn test_tlfu_hot_window_promotion() {
+        let mut tlfu = TinyLfu::new_sized(1, 10, 1);
+        tlfu.set_hot_window_promotion(3);
+        let mut entries = HashMap::new();
+        for key in 1..=4 {
+            if key != 3 {
+                for _ in 0..5 {
+                    tlfu.sketch.add(key);
+                }
+            }
+            entries.insert(key, Entry::new());
+            tlfu.set(key, &mut entries).unwrap();
+        }
+
+        // 1 took the only protected slot, 2 found it full, 3 is cold
+        assert_eq!(entries[&1].policy_list_id, 3);
+        assert_eq!(entries[&2].policy_list_id, 2);
+        assert_eq!(entries[&3].policy_list_id, 2);
+        assert_eq!(entries[&4].policy_list_id, 1);
+        assert!(tlfu.consistency_issues(&entries).is_empty());
+    }
+
+    #[test]
+    fn test_tlfu_climber_trace() {
+        let mut tlfu = TinyLfu::new_sized(10, 90, 72);
+        tlfu.climb();
+        assert!(tlfu.climber_trace().is_empty());
+
+        tlfu.set_climber_trace(2);
+        for (hits, misses) in [(50, 50), (80, 20), (10, 90)] {
+            tlfu.hit_in_sample = hits;
+            tlfu.misses_in_sample = misses;
+            tlfu.climb();
+        }
+        let trace = tlfu.climber_trace();
+        assert_eq!(trace.len(), 2);
+        // the oldest step was dropped, the newest matches the climber state
+        assert_eq!(trace[0].0, 0.8);
+        assert!((trace[0].1 - 0.3).abs() < 1e-6);
+        assert_eq!(trace[1].0, 0.1);
+        assert!((trace[1].1 + 0.7).abs() < 1e-6);
+        assert_eq!((trace[1].2, trace[1].3), (tlfu.step, tlfu.amount));
+
+        tlfu.set_climber_trace(0);
+        tlfu.climb();
+        assert!(tlfu.climber_trace().is_empty());
+    }
+
+    #[test]
+    fn test_tlfu_size_drift() {
+        let mut tlfu = TinyLfu::new_sized(2, 10, 5);
+        let mut entries = HashMap::new();
+        for i in 1..=4 {
+            entries.insert(i, Entry::new());
+            tlfu.set(i, &mut entries).unwrap();
+        }
+        assert_eq!(tlfu.size_drift(), None);
+
+        tlfu.force_size(5);
+        assert_eq!(tlfu.size_drift(), Some((5, 4)));
+        assert_eq!(tlfu.consistency_issues(&entries).len(), 1);
+    }
+
+    #[test]
+    fn test_tlfu_demotion_repair() {
+        let mut tlfu = TinyLfu::new_sized(1, 10, 5);
+        tlfu.set_demotion_repair(3);
+        let clock = Clock::new();
+        let mut entries = HashMap::new();
+        for key in 1..=6 {
+            entries.insert(key, Entry::new());
+            tlfu.set(key, &mut entries).unwrap();
+        }
+        for key in 1..=5 {
+            tlfu.access(key, &clock, &mut entries).unwrap();
+        }
+        for _ in 0..5 {
+            tlfu.sketch.add(1);
+        }
+
+        // 1 is the protected tail but hot, 2 and 3 are demoted in its place
+        tlfu.set_protected_capacity(3, &mut entries).unwrap();
+        assert_eq!(entries[&1].policy_list_id, 3);
+        assert_eq!(entries[&2].policy_list_id, 2);
+        assert_eq!(entries[&3].policy_list_id, 2);
+        assert_eq!(entries[&4].policy_list_id, 3);
+
+        // with only hot keys left the tail goes anyway
+        tlfu.set_demotion_repair(1);
+        tlfu.set_protected_capacity(2, &mut entries).unwrap();
+        assert_eq!(entries[&1].policy_list_id, 2);
+        assert!(tlfu.consistency_issues(&entries).is_empty());
     }
 }
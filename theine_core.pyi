@@ -1,4 +1,17 @@
-from typing import Optional, List, Tuple
+from enum import Enum
+from typing import Callable, Dict, Optional, List, Tuple
+
+class WindowPolicy(Enum):
+    Lru = 0
+    Fifo = 1
+
+class VictimOrder(Enum):
+    ProbationFirst = 0
+    WindowFirst = 1
+
+class CounterWidth(Enum):
+    Four = 0
+    Eight = 1
 
 class CoreDebugInfo:
     len: int
@@ -6,6 +19,35 @@ class CoreDebugInfo:
     probation_len: int
     protected_len: int
 
+class EntryInfo:
+    policy_list_id: int
+    expire: int
+    wheel_index: Tuple[int, int]
+    in_window: bool
+    in_probation: bool
+    in_protected: bool
+    frequency: int
+
+class KeyCursor:
+    """
+    Batched iteration over a snapshot of a TlfuCore's keys, created by TlfuCore.key_cursor.
+    """
+
+    def next_batch(self, n: int) -> List[int]:
+        """
+        Returns the next batch of keys from the snapshot.
+
+        :param n: Maximum number of keys to return.
+        :return: Up to n keys, an empty list once exhausted.
+        """
+        ...
+
+    def remaining(self) -> int:
+        """
+        Returns the number of keys not yet returned.
+        """
+        ...
+
 class TlfuCore:
     """
     A Python class representing the TlfuCore Rust struct.
@@ -24,21 +66,324 @@ class TlfuCore:
         """
         ...
 
+    @staticmethod
+    def new_seeded(size: int, seed: int) -> TlfuCore:
+        """
+        Creates a TlfuCore whose frequency sketch mixes a seed into every key's hash, so keys crafted
+        to collide in one instance don't collide in another. A seed of 0 behaves like TlfuCore(size).
+
+        :param size: The maximum number of entries the cache can hold.
+        :param seed: Seed mixed into every key's sketch hash.
+        """
+        ...
+
+    @staticmethod
+    def new_small(size: int) -> TlfuCore:
+        """
+        Creates a TlfuCore that is a plain LRU without a frequency sketch when size is at most 64,
+        avoiding the sketch's memory and upkeep for tiny caches. Larger sizes behave like TlfuCore(size).
+
+        :param size: The maximum number of entries the cache can hold.
+        """
+        ...
+
+    @staticmethod
+    def with_window_policy(size: int, policy: WindowPolicy) -> TlfuCore:
+        """
+        Creates a TlfuCore whose admission window uses the given recency policy.
+
+        :param size: The maximum number of entries the cache can hold.
+        :param policy: WindowPolicy.Lru, or WindowPolicy.Fifo for second-chance FIFO.
+        """
+        ...
+
+    @staticmethod
+    def with_counter_width(size: int, width: CounterWidth) -> TlfuCore:
+        """
+        Creates a TlfuCore with the given frequency sketch counter width. 8-bit counters keep
+        telling hot keys apart past 15 accesses per sample, at twice the sketch memory.
+
+        :param size: The maximum number of entries the cache can hold.
+        :param width: CounterWidth.Four (default) or CounterWidth.Eight.
+        """
+        ...
+
+    @staticmethod
+    def with_sketch_block_size(size: int, block_words: int) -> TlfuCore:
+        """
+        Creates a TlfuCore whose frequency sketch uses blocks of block_words 64-bit words instead
+        of 8 (one 64-byte cache line). A performance knob for platforms with other cache lines.
+
+        :param size: The maximum number of entries the cache can hold.
+        :param block_words: Words per block, a power of two from 4 to 64.
+        :raises ValueError: If block_words is out of range or not a power of two.
+        """
+        ...
+
+    @staticmethod
+    def with_max_sketch_size(size: int, max_sketch_size: int) -> TlfuCore:
+        """
+        Creates a TlfuCore whose frequency sketch is sized for at most max_sketch_size keys instead of
+        the full capacity, trading estimate accuracy for memory on very large caches.
+
+        :param size: The maximum number of entries the cache can hold.
+        :param max_sketch_size: Upper bound on the number of keys the sketch is sized for.
+        """
+        ...
+
+    @staticmethod
+    def with_wheel_levels(size: int, bucket_spec: List[int]) -> TlfuCore:
+        """
+        Creates a TlfuCore with a custom number of timer wheel buckets per level.
+        Level 0 buckets span ~1.07s and each level's bucket spans a full rotation of the previous one.
+
+        :param size: The maximum number of entries the cache can hold.
+        :param bucket_spec: Bucket count per level, powers of two; the last level must have exactly 1 bucket.
+        :raises ValueError: If the spec doesn't produce strictly increasing spans.
+        """
+        ...
+
+    def set_promote_long_ttl_threshold(self, threshold: int) -> None:
+        """
+        Inserts new entries whose TTL exceeds the threshold (or that have no TTL) directly into probation,
+        skipping the admission window. This biases the policy toward long-lived keys; only use it when a
+        long TTL reliably indicates importance.
+
+        :param threshold: TTL threshold in nanoseconds, 0 disables.
+        """
+        ...
+
+    def set_min_admission_frequency(self, k: int) -> None:
+        """
+        Rejects new keys whose estimated frequency, counting the current set, is below k.
+        Rejected keys are not inserted and are returned by set as evicted. 0 or 1 disables the gate.
+
+        :param k: Minimum estimated frequency for a new key to be admitted.
+        """
+        ...
+
+    def protected_capacity(self) -> int:
+        """
+        Returns the current capacity of the SLRU protected segment.
+
+        :return: Protected capacity.
+        """
+        ...
+
+    def set_protected_capacity(self, cap: int) -> None:
+        """
+        Sets the capacity of the SLRU protected segment, demoting its least recently used keys to
+        probation if it shrinks. The hill climber adapts from the new split as its baseline.
+
+        :param cap: Protected capacity, at most the main (non-window) capacity.
+        :raises ValueError: If cap exceeds the main capacity.
+        """
+        ...
+
+    def set_window_capacity(self, cap: int) -> None:
+        """
+        Sets the admission window to an absolute capacity, taking the difference from or giving it
+        back to the protected segment. Combine with freeze_window to pin the window size.
+
+        :param cap: Window capacity, at least 1 and below the cache capacity.
+        :raises ValueError: If cap is out of range.
+        """
+        ...
+
+    def freeze_window(self, frozen: bool) -> None:
+        """
+        Stops or resumes the hill climber's adaptation of the window size.
+
+        :param frozen: Whether to pin the window size.
+        """
+        ...
+
+    def enable_admission_trace(self, capacity: int) -> None:
+        """
+        Records the most recent admission contests for admission_trace, to see the frequency gap at
+        the eviction boundary. Disabled by default.
+
+        :param capacity: Number of contests kept, 0 disables and drops the trace.
+        """
+        ...
+
+    def admission_trace(self) -> List[Tuple[int, int, bool]]:
+        """
+        Returns the recorded admission contests, oldest first.
+
+        :return: A list of (candidate_freq, victim_freq, admitted) tuples, empty when disabled.
+        """
+        ...
+
+    def enable_climber_trace(self, capacity: int) -> None:
+        """
+        Records the most recent hill climber steps for climber_trace, to see how the window size
+        evolved over a workload. Disabled by default.
+
+        :param capacity: Number of steps kept, 0 disables and drops the trace.
+        """
+        ...
+
+    def climber_trace(self) -> List[Tuple[float, float, float, int]]:
+        """
+        Returns the recorded hill climber steps, oldest first.
+
+        :return: A list of (sample_hr, delta, next_step, amount) tuples, where amount is the window
+            adjustment applied, empty when disabled.
+        """
+        ...
+
+    def set_victim_priority(self, order: VictimOrder) -> None:
+        """
+        Sets which list eviction takes its first victim from. ProbationFirst (the default) starts at
+        the probation tail, WindowFirst at the window tail. The admission contest is unchanged.
+
+        :param order: Where victim selection starts.
+        """
+        ...
+
+    def set_hot_window_promotion(self, freq_threshold: int) -> None:
+        """
+        Places keys leaving the window with an estimated frequency above the threshold straight
+        into protected while it has room, instead of probation.
+
+        :param freq_threshold: Frequency a key must exceed, 0 (the default) disables it.
+        """
+        ...
+
+    def set_demotion_repair(self, freq_threshold: int) -> None:
+        """
+        Keeps protected keys estimated above the threshold in protected when it shrinks, demoting
+        the next colder key instead. Once only hot keys are left, they're demoted as usual.
+
+        :param freq_threshold: Frequency a key must exceed to be kept, 0 (the default) disables it.
+        """
+        ...
+
+    def set_tie_breaker(self, prefer_candidate: bool) -> None:
+        """
+        Sets which side wins an admission contest between keys of equal frequency. By default the
+        resident victim wins; recency-favoring workloads may prefer the newer candidate.
+
+        :param prefer_candidate: Whether the candidate wins ties.
+        """
+        ...
+
+    def set_weight_writes(self, weight_writes: bool) -> None:
+        """
+        Sets whether inserting a new key counts towards its estimated frequency. When False, only
+        accesses influence admission. Defaults to True.
+
+        :param weight_writes: Whether set of a new key bumps its frequency.
+        """
+        ...
+
+    def set_hashdos_threshold(self, threshold: int) -> None:
+        """
+        Sets the candidate frequency above which a losing admission contest may still be won by a random tiebreak.
+        Lower values resist crafted key streams that try to evict a victim deterministically.
+
+        :param threshold: Frequency threshold, at least 1.
+        :raises ValueError: If threshold is 0.
+        """
+        ...
+
     def set(self, entries: List[Tuple[int, int]]) -> List[int]:
         """
-        Sets multiple entries in the cache.
+        Sets multiple entries in the cache. A key repeated in the batch is inserted once and the last TTL wins.
 
         :param entries: A list of tuples where each tuple contains a key and its time-to-live (TTL) in nanoseconds.
         :return: A list of keys that were evicted.
         """
         ...
 
+    def set_ordered(self, entries: List[Tuple[int, int]]) -> List[int]:
+        """
+        Like set, then moves the batch keys still in the window to the window front in batch
+        order, updated keys included, so the last key of the batch is the most recently used.
+
+        :param entries: A list of (key, ttl) pairs, where ttl=-1 means remove.
+        :return: Same as set.
+        """
+        ...
+
+    def set_reporting_admission(self, entries: List[Tuple[int, int]]) -> List[int]:
+        """
+        Sets multiple entries like set, but returns the keys of this batch that aren't cached
+        afterwards, because the frequency gate rejected them or they were evicted again within
+        the batch. Resident keys evicted to make room aren't reported.
+
+        :param entries: A list of (key, ttl) tuples. A ttl of -1 removes the key.
+        :return: Keys last inserted or updated in the batch that didn't stick, in batch order.
+        """
+        ...
+
+    def set_full_report(self, entries: List[Tuple[int, int]]) -> Tuple[List[int], List[int]]:
+        """
+        Sets multiple entries like set, but reports capacity evictions and explicit removals separately.
+
+        :param entries: A list of (key, ttl) tuples. A ttl of -1 removes the key.
+        :return: (evicted, removed) where evicted is what set returns and removed holds the
+                 present keys dropped by a ttl of -1.
+        """
+        ...
+
+    def set_with_frequency(self, entries: List[Tuple[int, int, int]]) -> List[int]:
+        """
+        Sets multiple entries, first bumping each new key's estimated frequency by the given seed
+        (capped at the counter maximum, 15 or 255 with 8-bit counters), so restored keys compete
+        for admission with their historical popularity.
+
+        :param entries: A list of (key, ttl, frequency) tuples. A ttl of -1 removes the key.
+        :return: A list of keys that were evicted.
+        """
+        ...
+
+    def set_with_tti(self, entries: List[Tuple[int, int, int]]) -> List[int]:
+        """
+        Sets multiple entries in the cache with both a TTL and an idle timeout.
+        An entry expires when its TTL elapses or when it hasn't been accessed for tti, whichever comes first.
+
+        :param entries: A list of tuples of key, time-to-live (TTL) and time-to-idle (TTI) in nanoseconds. A TTI of 0 disables idle expiration.
+        :return: A list of keys that were evicted.
+        """
+        ...
+
+    def set_grouped(self, entries: List[Tuple[int, int, int]]) -> List[int]:
+        """
+        Sets multiple entries in the cache, tagging each with a group for bulk invalidation.
+
+        :param entries: A list of tuples of key, time-to-live (TTL) in nanoseconds and group id.
+        :return: A list of keys that were evicted.
+        """
+        ...
+
+    def invalidate_group(self, group: int) -> List[int]:
+        """
+        Removes every entry tagged with the given group.
+
+        :param group: The group id.
+        :return: A list of keys that were removed.
+        """
+        ...
+
+    def preview_evictions(self, new_keys: List[int]) -> List[int]:
+        """
+        Returns the keys that would be evicted if new_keys were inserted, without modifying the cache.
+        Runs against a copy of the policy state, so prefer small batches.
+
+        :param new_keys: Keys to simulate inserting.
+        :return: A list of keys that would be evicted.
+        """
+        ...
+
     def remove(self, key: int) -> Optional[int]:
         """
         Removes an entry from the cache by its key.
 
         :param key: The key of the entry to remove.
         :return: The removed key if it was present, otherwise None.
+        :raises RuntimeError: In strict mode, on a policy inconsistency. The key is removed regardless.
         """
         ...
 
@@ -47,40 +392,751 @@ class TlfuCore:
         Marks multiple keys as accessed, updating their status in the cache.
 
         :param keys: A list of keys to mark as accessed.
+        :raises RuntimeError: In strict mode, on a policy inconsistency.
         """
         ...
 
-    def advance(self) -> List[int]:
+    def set_hashed(self, entries: List[Tuple[int, int]]) -> List[int]:
         """
-        Advances the internal clock and cleans up expired entries.
+        Sets multiple entries like set, but takes raw Python hashes and applies spread internally.
+
+        :param entries: A list of (hash, ttl) tuples. A ttl of -1 removes the key.
+        :return: A list of evicted keys, as spread hashes.
         """
         ...
 
-    def clear(self) -> None:
+    def access_hashed(self, keys: List[int]) -> None:
         """
-        Clears all entries from the cache.
+        Marks multiple keys as accessed like access, but takes raw Python hashes and applies spread internally.
+
+        :param keys: A list of Python hashes to mark as accessed.
         """
         ...
 
-    def len(self) -> int:
+    def access_extend(self, entries: List[Tuple[int, int]]) -> None:
         """
-        Returns the number of entries currently in the cache.
+        Marks multiple keys as accessed and resets the TTL of those still live.
 
-        :return: The number of entries.
+        :param entries: A list of tuples where each tuple contains a key and its new time-to-live (TTL) in nanoseconds.
         """
         ...
 
-    def debug_info(self) -> CoreDebugInfo:
+    def renew(self, key: int) -> bool:
         """
-        Returns the debug info of core.
+        Resets a live entry's expiration to the TTL it was last set with, without recording an access.
 
-        :return: Debug info.
+        :param key: The key to renew.
+        :return: False if the key is missing, already expired or was set without a TTL.
         """
         ...
 
-    def keys(self) -> List[int]:
+    def transfer_to(self, key: int, dest: TlfuCore) -> bool:
         """
-        Returns all keys, used in test only.
+        Moves a live entry into another cache, keeping its remaining TTL, idle timeout, group and
+        frequency estimate. The key then goes through dest's admission; keys evicted from dest are
+        kept until drained with dest.take_evicted.
+
+        :param key: The key to move.
+        :param dest: The cache to move the entry into, must not be this cache.
+        :return: False if the key is missing or expired here, or already in dest.
+        """
+        ...
+
+    def access_or_insert(self, key: int, ttl: int) -> bool:
+        """
+        Records an access if the key is live, otherwise inserts it with the given TTL.
+
+        :param key: The key to access or insert.
+        :param ttl: Time-to-live (TTL) in nanoseconds applied on miss.
+        :return: True on hit, False if the key was inserted.
+        """
+        ...
+
+    def access_with_freshness(self, key: int, stale_window_ns: int) -> int:
+        """
+        Records an access and reports how close the entry is to expiring, for stale-while-revalidate.
+
+        :param key: The key to access.
+        :param stale_window_ns: How long before expiry, in nanoseconds, an entry counts as stale.
+        :return: 1 if fresh, 0 if live but expiring within the stale window, -1 if missing or expired.
+        """
+        ...
+
+    def access_with_segment(self, key: int) -> int:
+        """
+        Records an access and reports which segment served the hit, before any promotion the access
+        causes.
+
+        :param key: The key to access.
+        :return: 1 for window, 2 for probation, 3 for protected, -1 if missing or expired.
+        """
+        ...
+
+    def simulate(self, keys: List[int]) -> float:
+        """
+        Replays a key stream through access_or_insert and returns the hit rate a cache of this size achieves.
+        Evictions caused by the replay are not queued for take_evicted.
+
+        :param keys: The key stream to replay.
+        :return: Fraction of keys that were hits, 0.0 for an empty stream.
+        """
+        ...
+
+    def take_evicted(self) -> List[int]:
+        """
+        Returns and clears the keys evicted by access_or_insert since the last call.
+
+        :return: A list of keys that were evicted.
+        """
+        ...
+
+    def set_refresh_ahead(
+        self, callback: Optional[Callable[[int], object]], freq_threshold: int, ttl_fraction: float
+    ) -> None:
+        """
+        Sets a callback that access signals, once per call, with each key whose estimated frequency
+        is at least freq_threshold and whose remaining TTL is below ttl_fraction of the TTL it was
+        last set with. The entry isn't modified. The callback must not call into the cache.
+
+        :param callback: Called with the key, or None to remove the callback.
+        :param freq_threshold: Minimum estimated frequency of the key.
+        :param ttl_fraction: Fraction of the original TTL, from 0.0 to 1.0.
+        :raises ValueError: If ttl_fraction is outside 0.0 to 1.0.
+        """
+        ...
+
+    def set_expiry_interceptor(self, callback: Optional[Callable[[int], Optional[int]]]) -> None:
+        """
+        Sets a callback called for each key about to expire, by advance and its variants as well as
+        purge_expired and remove_if_expired. Returning a positive TTL in nanoseconds renews the
+        entry, 0 keeps it with no expiration, and None, a negative value or an exception lets it
+        expire. The callback may call back into the cache; a key it rewrites or removes itself is
+        left as it is.
+
+        :param callback: The interceptor, or None to remove it.
+        """
+        ...
+
+    def mark_dirty(self, key: int) -> None:
+        """
+        Marks a resident entry as holding unflushed writes, so the set_flush_on_evict callback sees
+        it before it is evicted or expires. Missing keys are ignored.
+
+        :param key: The key to mark.
+        """
+        ...
+
+    def mark_clean(self, key: int) -> None:
+        """
+        Marks a resident entry as flushed. Missing keys are ignored.
+
+        :param key: The key to mark.
+        """
+        ...
+
+    def dirty_keys(self) -> List[int]:
+        """
+        Returns the resident keys marked dirty, in no particular order.
+
+        :return: A list of dirty keys.
+        """
+        ...
+
+    def set_flush_on_evict(self, callback: Optional[Callable[[int], object]]) -> None:
+        """
+        Sets a callback called with each dirty key the cache evicts or expires, once per entry. It
+        runs after the call that dropped the entry returns control of the cache, so it may use the
+        cache again. Entries removed by evict_coldest count as evicted; explicit removals (remove, a
+        ttl of -1, invalidate_group, clear) don't call it. Exceptions are logged and ignored.
+
+        :param callback: The flush callback, or None to remove it.
+        """
+        ...
+
+    def advance(self) -> List[int]:
+        """
+        Advances the internal clock and cleans up expired entries.
+
+        :return: A list of keys that were expired and removed.
+        :raises RuntimeError: In strict mode, on a policy inconsistency.
+        """
+        ...
+
+    def advance_incremental(self, max_buckets: int) -> List[int]:
+        """
+        Advances like advance, but stops short of the current time once max_buckets timer wheel
+        buckets would be scanned, so a long backlog can be drained over several calls. Each call moves
+        at least one level 0 tick forward.
+
+        :param max_buckets: Maximum number of buckets to scan.
+        :return: A list of keys that were expired and removed.
+        :raises RuntimeError: In strict mode, on a policy inconsistency.
+        """
+        ...
+
+    def advance_and_compact(self) -> List[int]:
+        """
+        Advances like advance, then releases the storage of emptied timer wheel buckets to reclaim
+        memory after a burst of short-lived entries expired.
+
+        :return: A list of keys that were expired and removed.
+        :raises RuntimeError: In strict mode, on a policy inconsistency.
+        """
+        ...
+
+    def shrink_entries(self) -> None:
+        """
+        Returns the entry map's excess capacity to the allocator after a burst of removals. Rehashes
+        every remaining entry, so it's meant for post-burst cleanup, not the hot path.
+        """
+        ...
+
+    def advance_notify(self, callback: Callable[[List[int]], object]) -> None:
+        """
+        Advances like advance, but passes all expired keys to callback in a single call instead of
+        returning them. The callback runs after cleanup completes and may call back into the cache.
+        It isn't called when nothing expired.
+
+        :param callback: Callable taking the list of expired keys.
+        :raises Exception: Whatever the callback raises.
+        """
+        ...
+
+    def advance_checked(self, now: int) -> List[int]:
+        """
+        Advances to an explicit time on the cache clock, for tests and simulations.
+
+        :param now: Time in nanoseconds on the cache clock, see clock_now_ns.
+        :return: A list of keys that were expired and removed.
+        :raises ValueError: If now is earlier than the last advance.
+        """
+        ...
+
+    def is_advance_pending(self) -> bool:
+        """
+        Returns whether an advance now would have timer wheel buckets to process. Entries in those
+        buckets may only move to a finer level, so True means expiration work is pending, not that a
+        key will expire.
+
+        :return: Whether an advance has work to do.
+        """
+        ...
+
+    def purge_expired(self) -> List[int]:
+        """
+        Removes every expired entry with a full scan, independent of the timer wheel.
+
+        :return: A list of keys that were expired and removed.
+        """
+        ...
+
+    def remove_if_expired(self, keys: List[int]) -> List[int]:
+        """
+        Removes those of the given keys that are resident and expired, leaving live and missing keys
+        alone.
+
+        :param keys: A list of keys to check.
+        :return: A list of keys that were expired and removed.
+        """
+        ...
+
+    def remove_if_expire_eq(self, key: int, expected_expire: int) -> bool:
+        """
+        Removes a key only if its expiration still matches expected_expire, so a stale
+        invalidation can't remove an entry rewritten with a new TTL since it was read.
+
+        :param key: The key to remove.
+        :param expected_expire: The expiration timestamp last observed via entry_info.
+        :return: True if the key was resident with a matching expiration and removed.
+        """
+        ...
+
+    def set_overflow_allowance(self, extra: int) -> None:
+        """
+        Lets the cache exceed its capacity by extra entries before inserts start evicting.
+        Use trim to evict back down to the capacity once a burst subsides.
+
+        :param extra: Number of entries tolerated above capacity, 0 disables.
+        """
+        ...
+
+    def set_insertion_grace(self, grace_ns: int) -> None:
+        """
+        Spares keys inserted within the last grace_ns as eviction victims while an older key can be
+        evicted instead. Once only graced keys remain, eviction proceeds anyway to respect capacity.
+
+        :param grace_ns: Grace period in nanoseconds, 0 disables.
+        """
+        ...
+
+    def set_strict(self, strict: bool) -> None:
+        """
+        Makes access, remove and advance raise RuntimeError on policy inconsistencies
+        instead of only logging them. Off by default; useful in CI and staging.
+
+        :param strict: Whether to raise instead of logging.
+        """
+        ...
+
+    def trim(self) -> List[int]:
+        """
+        Evicts down to the hard capacity, ignoring the overflow allowance.
+
+        :return: A list of keys that were evicted.
+        """
+        ...
+
+    def remove_inserted_before(self, cutoff_ns: int) -> List[int]:
+        """
+        Removes every entry last written by set before the cutoff, independent of TTLs.
+
+        :param cutoff_ns: Time in nanoseconds on the cache clock, see clock_now_ns.
+        :return: A list of keys that were removed.
+        """
+        ...
+
+    def coldest_key(self) -> Optional[int]:
+        """
+        Returns the key capacity eviction considers first: by default the least recently used
+        probation key, else the protected one, else the window's, see set_victim_priority.
+
+        :return: The key, or None if the cache is empty.
+        """
+        ...
+
+    def evict_coldest(self, count: int) -> List[int]:
+        """
+        Removes up to count entries with the lowest estimated frequency, keeping the capacity unchanged.
+        Ties are broken by eviction order. Removed dirty entries go to the set_flush_on_evict callback.
+
+        :param count: Maximum number of entries to remove.
+        :return: The removed keys, coldest first.
+        """
+        ...
+
+    def dump(self) -> List[Tuple[int, int, int, int, int]]:
+        """
+        Returns everything known about every live entry in eviction order, coldest first, as a
+        census for offline analysis. The access count is kept since the entry was created.
+
+        :return: A list of (key, remaining_ttl, policy_list_id, frequency, access_count) tuples,
+                 remaining_ttl in nanoseconds with 0 meaning no expiration, as in export_state. It
+                 is never negative.
+        """
+        ...
+
+    def export_state(self) -> List[Tuple[int, int, int]]:
+        """
+        Exports the segment placement of every live entry in eviction order, coldest first.
+        Idle timeouts, groups and frequency estimates are not exported.
+
+        :return: A list of (key, policy_list_id, remaining_ttl) tuples, remaining_ttl in nanoseconds with 0
+                 meaning no expiration.
+        """
+        ...
+
+    def adjust_frequency(self, key: int, delta: int) -> None:
+        """
+        Counts delta extra accesses for a key in the frequency sketch, biasing admission toward keys an
+        external signal predicts will be hot. The sketch can't decrement a single key, so a negative
+        delta only logs a warning.
+
+        :param key: The key hash to adjust.
+        :param delta: Number of extra accesses to count, between -128 and 127.
+        """
+        ...
+
+    def warm_fill(self, keys: List[Tuple[int, int]]) -> None:
+        """
+        Inserts keys straight into probation, bypassing the admission contest that would reject
+        most keys on a cold cache. New keys prime the frequency sketch like set does, subject to
+        set_weight_writes. They only fill the free room and nothing resident is evicted; later
+        keys win when there isn't room for all. TTLs are handled like with set, including
+        set_max_ttl and set_promote_long_ttl_threshold; short lived keys that don't fit in the
+        window spill into probation.
+
+        :param keys: A list of (key, ttl) tuples. A ttl of -1 removes the key.
+        """
+        ...
+
+    def import_state(self, state: List[Tuple[int, int, int]]) -> List[int]:
+        """
+        Replaces the cache contents with state from export_state, restoring each key's segment.
+        The coldest entries are dropped if the state exceeds this cache's capacity.
+
+        :param state: A list of (key, policy_list_id, remaining_ttl) tuples, coldest first.
+        :return: The keys that didn't fit and were dropped.
+        """
+        ...
+
+    def swap(self, other: TlfuCore) -> None:
+        """
+        Exchanges the policy state, timer wheel and entries of this cache with another one in place.
+        Keys pending for take_evicted, the unread expiry history and recent eviction counts move with
+        the contents; settings made through the setters stay with each cache. Each wheel keeps its
+        own clock, so expiration continues against the swapped-in wheel's clock base.
+
+        :param other: The cache to exchange contents with.
+        """
+        ...
+
+    def clear(self) -> None:
+        """
+        Clears all entries from the cache.
+        """
+        ...
+
+    def full_reset(self) -> None:
+        """
+        Clears all entries and also forgets all frequency history, restoring the segment split the
+        cache was created with (a new_small cache stays a plain LRU) and the initial hill climber
+        state. Settings such as thresholds and the window policy are kept.
+        """
+        ...
+
+    def len(self) -> int:
+        """
+        Returns the number of entries currently in the cache.
+
+        :return: The number of entries.
+        """
+        ...
+
+    def live_len(self) -> int:
+        """
+        Returns the number of entries that haven't expired yet. Unlike len, this excludes entries
+        past their deadline that advance hasn't reaped yet. O(n) in the number of entries.
+
+        :return: Live entry count.
+        """
+        ...
+
+    def immortal_count(self) -> int:
+        """
+        Returns the number of entries that never expire (no TTL and no idle timeout).
+
+        :return: The number of immortal entries.
+        """
+        ...
+
+    def reschedule_count(self) -> int:
+        """
+        Returns how many not yet expired entries advance moved to another timer wheel bucket over
+        the wheel's lifetime. A high ratio to expirations suggests a different level layout.
+
+        :return: Reschedule count.
+        """
+        ...
+
+    def scheduled_count(self) -> int:
+        """
+        Returns the number of entries scheduled for expiration in the timer wheel. Entries without
+        a TTL aren't scheduled, so a difference from the number of expiring entries signals a bug.
+
+        :return: Scheduled entry count.
+        """
+        ...
+
+    def ttl_distribution(self) -> List[int]:
+        """
+        Returns the number of scheduled entries in each timer wheel level, from the finest level to
+        the overflow level. Entries clustering in the first or last level suggest a different layout.
+
+        :return: One count per wheel level.
+        """
+        ...
+
+    def ttl_histogram(self, boundaries: List[int]) -> List[int]:
+        """
+        Counts live entries per band of remaining TTL. Boundaries b0 < ... < bn give the bands
+        [0, b0), [b0, b1), ..., [bn, inf), followed by one band for entries that never expire.
+
+        :param boundaries: Band boundaries in nanoseconds, strictly increasing.
+        :return: len(boundaries) + 2 counts, the last for entries without expiration.
+        :raises ValueError: If boundaries isn't strictly increasing.
+        """
+        ...
+
+    def validate_wheel(self) -> List[Tuple[int, str]]:
+        """
+        Checks that timer wheel scheduling agrees with entry metadata, used in test only.
+
+        :return: A list of (key, description) pairs, empty when consistent.
+        """
+        ...
+
+    def check_consistency(self) -> None:
+        """
+        Checks every policy list and timer wheel invariant against the cache entries.
+        Walks all entries, meant for diagnosing suspected corruption.
+
+        :raises RuntimeError: Listing every violated invariant.
+        """
+        ...
+
+    def free_slots(self) -> int:
+        """
+        Returns how many more entries fit before inserts start evicting, not counting the overflow
+        allowance.
+
+        :return: Capacity minus the number of entries.
+        """
+        ...
+
+    def set_if_fits(self, entries: List[Tuple[int, int]]) -> List[int]:
+        """
+        Sets a batch all or nothing: only if the cache size after the whole batch fits within
+        capacity, so nothing is evicted. Removals by ttl=-1 in the batch make room.
+
+        :param entries: A list of (key, ttl) pairs, where ttl=-1 means remove.
+        :return: Same as set.
+        :raises ValueError: If the batch exceeds the free slots; the cache is left untouched.
+        """
+        ...
+
+    def wheel_level_of(self, key: int) -> Optional[int]:
+        """
+        Returns the timer wheel level a key is scheduled in, a single-key view of ttl_distribution.
+
+        :param key: The key to look up.
+        :return: The level, or None if the key isn't cached or never expires.
+        """
+        ...
+
+    def current_window_ratio(self) -> float:
+        """
+        Returns the current window capacity as a fraction of total capacity, as adjusted by the hill climber.
+
+        :return: Window ratio between 0 and 1.
+        """
+        ...
+
+    def sketch_additions(self) -> int:
+        """
+        Returns the number of frequency sketch additions since the last reset.
+
+        :return: Additions counter.
+        """
+        ...
+
+    def segment_hit_stats(self) -> Tuple[int, int, int]:
+        """
+        Returns hit counts on live entries broken down by the segment that served them.
+
+        :return: A tuple of (window_hits, probation_hits, protected_hits).
+        """
+        ...
+
+    def sketch_reset_count(self) -> int:
+        """
+        Returns how many times the frequency sketch has been halved over the cache's lifetime.
+
+        :return: Reset count.
+        """
+        ...
+
+    def decay_sketch(self) -> None:
+        """
+        Halves every frequency estimate now, as the sketch's periodic reset does. Meant for tests
+        and diagnostics that need the aging step without pumping traffic.
+        """
+        ...
+
+    def sketch_table_len(self) -> int:
+        """
+        Returns the number of 64-bit counter words in the frequency sketch.
+
+        :return: Table length; memory use is eight bytes per word.
+        """
+        ...
+
+    def sketch_sample_size(self) -> int:
+        """
+        Returns the number of sketch additions after which all counters are halved.
+
+        :return: Sample size.
+        """
+        ...
+
+    def set_debug_invariants(self, enabled: bool) -> None:
+        """
+        Checks the policy's size accounting against its segment lengths after every set, access,
+        remove and advance. A mismatch is logged with the operation that caused it; in strict mode
+        access, remove and advance raise instead, while set only logs. Meant for development and CI.
+
+        :param enabled: Whether to check, off by default.
+        """
+        ...
+
+    def set_max_ttl(self, max_ns: int) -> None:
+        """
+        Clamps TTLs passed to set and the other write methods to at most max_ns, guarding against
+        unit mix-ups. A TTL of 0 still means no expiration.
+
+        :param max_ns: Largest TTL in nanoseconds, 0 (the default) disables the clamp.
+        """
+        ...
+
+    def set_expiry_penalty(self, penalty: int) -> None:
+        """
+        Stops counting writes of a key toward its frequency once it has expired unread penalty times,
+        so churny write-only keys can't win admission on write volume alone. An expiry after a read
+        clears the key's history.
+
+        :param penalty: Unread expiries before writes stop counting, 0 disables.
+        """
+        ...
+
+    def set_sketch_max_sample_size(self, max: int) -> None:
+        """
+        Caps the number of sketch additions after which all counters are halved, so very large
+        sketches age sooner. The default is ten additions per counter, bounded by 2^30.
+
+        :param max: Largest sample size, at least 1.
+        :raises ValueError: If max is 0.
+        """
+        ...
+
+    def recent_eviction_rate(self, window: int) -> float:
+        """
+        Returns the average number of evictions per set batch over the most recent batches.
+
+        :param window: Number of most recent batches to average over.
+        :return: Average evictions per batch, 0.0 if nothing was recorded yet.
+        """
+        ...
+
+    def health_score(self) -> int:
+        """
+        Returns a 0-100 health score for monitoring, the summed weights of the passing
+        health_details checks. The accounting checks weigh 35 each and the load checks 15 each, so
+        a score below 70 means a bookkeeping bug rather than just load.
+
+        :return: The health score.
+        """
+        ...
+
+    def health_details(self) -> List[Tuple[str, bool]]:
+        """
+        Lists the checks behind health_score and whether each passed: size_matches_segments,
+        wheel_matches_entries, sketch_not_saturated (at most 10% of counters maxed out) and
+        eviction_pressure_low (recent batches evicted at most 10% of capacity on average). Scans
+        the sketch and all entries, so it's meant for a monitoring endpoint.
+
+        :return: A list of (check, passed) pairs.
+        """
+        ...
+
+    def set_eviction_sample_size(self, samples: int) -> None:
+        """
+        Sets how many per-batch eviction counts are kept for recent_eviction_rate, 64 by default.
+
+        :param samples: Ring buffer size, at least 1.
+        :raises ValueError: If samples is 0.
+        """
+        ...
+
+    def wheel_nanos(self) -> int:
+        """
+        Returns the time in nanoseconds the timer wheel was last advanced to.
+
+        :return: Wheel time in nanoseconds.
+        """
+        ...
+
+    def clock_now_ns(self) -> int:
+        """
+        Returns the cache clock's current time in nanoseconds since the cache was created.
+
+        :return: Clock time in nanoseconds.
+        """
+        ...
+
+    def geometry(self) -> Dict[str, int]:
+        """
+        Returns the effective cache geometry: capacity, window, probation and protected segment
+        capacities, and the sketch size in counters.
+
+        :return: A dict of geometry values.
+        """
+        ...
+
+    def debug_info(self) -> CoreDebugInfo:
+        """
+        Returns the debug info of core.
+
+        :return: Debug info.
+        """
+        ...
+
+    def entry_info(self, key: int) -> Optional[EntryInfo]:
+        """
+        Returns the policy segment, expiration, wheel position and estimated frequency of a key.
+
+        :param key: The key to inspect.
+        :return: Entry info, or None if the key is not in the cache.
+        """
+        ...
+
+    def rename(self, old_key: int, new_key: int) -> bool:
+        """
+        Moves an entry to a new key, keeping its policy position, TTL and estimated frequency.
+
+        :param old_key: The key currently cached.
+        :param new_key: The key to move the entry to.
+        :return: True if renamed, False if old_key isn't cached or new_key already is.
+        """
+        ...
+
+    def is_protected(self, key: int) -> Optional[bool]:
+        """
+        Checks whether a key is in the protected segment of the main space.
+
+        :param key: The key to check.
+        :return: True if protected, False if in the window or probation, None if not cached.
+        """
+        ...
+
+    def top_keys(self, n: int) -> List[Tuple[int, int]]:
+        """
+        Returns the n resident keys with the highest estimated access frequency.
+
+        :param n: Maximum number of keys to return.
+        :return: (key, estimate) pairs sorted by estimate, highest first.
+        """
+        ...
+
+    def keys(self) -> List[int]:
+        """
+        Returns all keys, used in test only.
+
+        :return: Keys list.
+        """
+        ...
+
+    def key_cursor(self) -> KeyCursor:
+        """
+        Returns a cursor over a one-time snapshot of the key set, to read keys in batches
+        without building one large list on the Python side.
+
+        :return: A KeyCursor over the current keys.
+        """
+        ...
+
+    def snapshot_keys_chunked(self, offset: int, limit: int) -> List[int]:
+        """
+        Returns one page of the key set, so large caches can be inspected without holding a lock for long.
+        Not transactional across pages: keys changed between calls may be missed or repeated.
+
+        :param offset: Number of keys to skip.
+        :param limit: Maximum number of keys to return.
+        :return: Keys list.
+        """
+        ...
+
+    def keys_ordered(self) -> List[int]:
+        """
+        Returns all keys in policy order: window, probation, then protected, each most recently used first.
 
         :return: Keys list.
         """
@@ -101,3 +1157,7 @@ def spread(h: int) -> int:
 class BloomFilter:
     def put(self, key: str) -> None: ...
     def contains(self, key: str) -> bool: ...
+    def bit_capacity(self) -> int: ...
+    def num_hashes(self) -> int: ...
+    def current_additions(self) -> int: ...
+    def current_fpp(self) -> float: ...